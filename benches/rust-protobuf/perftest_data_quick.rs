@@ -7,14 +7,20 @@ use std::io::{Read, Write};
 use quick_protobuf::{MessageRead, MessageWrite, Reader, Writer, Result};
 use quick_protobuf::sizeofs::*;
 
-#[derive(Debug, Default, PartialEq, Clone)]
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
 pub struct Test1 {
     pub value: Option<i32>,
 }
 
 impl MessageRead for Test1 {
-    fn from_reader<R: Read>(mut r: &mut Reader<R>) -> Result<Self> {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
         let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        let msg = self;
         while !r.is_eof() {
             match r.next_tag() {
                 Ok(8) => msg.value = Some(r.read_int32()?),
@@ -22,81 +28,149 @@ impl MessageRead for Test1 {
                 Err(e) => return Err(e),
             }
         }
-        Ok(msg)
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.value = Default::default();
     }
 }
 
 impl MessageWrite for Test1 {
     fn get_size(&self) -> usize {
-        self.value.as_ref().map_or(0, |m| 1 + sizeof_int32(*m))
+        self.value_encoded_len()
     }
 
     fn write_message<W: Write>(&self, r: &mut Writer<W>) -> Result<()> {
+        let start = r.written();
         if let Some(ref s) = self.value { r.write_int32_with_tag(8, *s)?; }
+        debug_assert_eq!(r.written() - start, self.get_size(), "Test1: written size differs from get_size");
         Ok(())
     }
 }
 
+impl Test1 {
+    /// Size of the `value` field once written, tag included, 0 if it is not written
+    pub fn value_encoded_len(&self) -> usize {
+        self.value.as_ref().map_or(0, |m| 1 + sizeof_int32(*m))
+    }
+
+    /// Lower bound of `get_size`: the minimum size of the required fields, which are always written
+    pub const fn size_hint() -> usize {
+        0
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct TestRepeatedBool {
     pub values: Vec<bool>,
 }
 
 impl MessageRead for TestRepeatedBool {
-    fn from_reader<R: Read>(mut r: &mut Reader<R>) -> Result<Self> {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
         let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        let msg = self;
         while !r.is_eof() {
             match r.next_tag() {
-                Ok(8) => msg.values.push(r.read_bool()?),
+                Ok(8) => { r.check_repeated_len(msg.values.len())?; msg.values.push(r.read_bool()?); }
+                Ok(10) => msg.values.extend(r.read_packed_repeated_field::<bool, _>(|r| r.read_bool())?),
                 Ok(t) => { r.read_unknown(t)?; }
                 Err(e) => return Err(e),
             }
         }
-        Ok(msg)
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
     }
 }
 
 impl MessageWrite for TestRepeatedBool {
     fn get_size(&self) -> usize {
-        self.values.iter().map(|s| 1 + sizeof_bool(*s)).sum::<usize>()
+        self.values_encoded_len()
     }
 
     fn write_message<W: Write>(&self, r: &mut Writer<W>) -> Result<()> {
+        let start = r.written();
         for s in &self.values { r.write_bool_with_tag(8, *s)? }
+        debug_assert_eq!(r.written() - start, self.get_size(), "TestRepeatedBool: written size differs from get_size");
         Ok(())
     }
 }
 
+impl TestRepeatedBool {
+    /// Size of the `values` field once written, tag included, 0 if it is not written
+    pub fn values_encoded_len(&self) -> usize {
+        self.values.iter().map(|s| 1 + sizeof_bool(*s)).sum::<usize>()
+    }
+
+    /// Lower bound of `get_size`: the minimum size of the required fields, which are always written
+    pub const fn size_hint() -> usize {
+        0
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct TestRepeatedPackedInt32 {
     pub values: Vec<i32>,
 }
 
 impl MessageRead for TestRepeatedPackedInt32 {
-    fn from_reader<R: Read>(mut r: &mut Reader<R>) -> Result<Self> {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
         let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        let msg = self;
         while !r.is_eof() {
             match r.next_tag() {
-                Ok(10) => msg.values = r.read_packed_repeated_field(|r| r.read_int32())?,
+                Ok(10) => msg.values.extend(r.read_packed_repeated_field::<i32, _>(|r| r.read_int32())?),
+                Ok(8) => { r.check_repeated_len(msg.values.len())?; msg.values.push(r.read_int32()?); }
                 Ok(t) => { r.read_unknown(t)?; }
                 Err(e) => return Err(e),
             }
         }
-        Ok(msg)
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
     }
 }
 
 impl MessageWrite for TestRepeatedPackedInt32 {
     fn get_size(&self) -> usize {
-        if self.values.is_empty() { 0 } else { 1 + sizeof_var_length(self.values.iter().map(|s| sizeof_int32(*s)).sum::<usize>()) }
+        self.values_encoded_len()
     }
 
     fn write_message<W: Write>(&self, r: &mut Writer<W>) -> Result<()> {
+        let start = r.written();
         r.write_packed_repeated_field_with_tag(10, &self.values, |r, m| r.write_int32(*m), &|m| sizeof_int32(*m))?;
+        debug_assert_eq!(r.written() - start, self.get_size(), "TestRepeatedPackedInt32: written size differs from get_size");
         Ok(())
     }
 }
 
+impl TestRepeatedPackedInt32 {
+    /// Size of the `values` field once written, tag included, 0 if it is not written
+    pub fn values_encoded_len(&self) -> usize {
+        if self.values.is_empty() { 0 } else { 1 + sizeof_var_length(self.values.iter().map(|s| sizeof_int32(*s)).sum::<usize>()) }
+    }
+
+    /// Lower bound of `get_size`: the minimum size of the required fields, which are always written
+    pub const fn size_hint() -> usize {
+        0
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct TestRepeatedMessages {
     pub messages1: Vec<TestRepeatedMessages>,
@@ -105,36 +179,72 @@ pub struct TestRepeatedMessages {
 }
 
 impl MessageRead for TestRepeatedMessages {
-    fn from_reader<R: Read>(mut r: &mut Reader<R>) -> Result<Self> {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
         let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        let msg = self;
         while !r.is_eof() {
             match r.next_tag() {
-                Ok(10) => msg.messages1.push(r.read_message()?),
-                Ok(18) => msg.messages2.push(r.read_message()?),
-                Ok(26) => msg.messages3.push(r.read_message()?),
+                Ok(10) => r.read_message_to_vec(&mut msg.messages1)?,
+                Ok(18) => r.read_message_to_vec(&mut msg.messages2)?,
+                Ok(26) => r.read_message_to_vec(&mut msg.messages3)?,
                 Ok(t) => { r.read_unknown(t)?; }
                 Err(e) => return Err(e),
             }
         }
-        Ok(msg)
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.messages1.clear();
+        self.messages2.clear();
+        self.messages3.clear();
     }
 }
 
 impl MessageWrite for TestRepeatedMessages {
     fn get_size(&self) -> usize {
-        self.messages1.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
-        + self.messages2.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
-        + self.messages3.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
+        self.messages1_encoded_len()
+        + self.messages2_encoded_len()
+        + self.messages3_encoded_len()
     }
 
     fn write_message<W: Write>(&self, r: &mut Writer<W>) -> Result<()> {
+        let start = r.written();
         for s in &self.messages1 { r.write_message_with_tag(10, s)? }
         for s in &self.messages2 { r.write_message_with_tag(18, s)? }
         for s in &self.messages3 { r.write_message_with_tag(26, s)? }
+        debug_assert_eq!(r.written() - start, self.get_size(), "TestRepeatedMessages: written size differs from get_size");
         Ok(())
     }
 }
 
+impl TestRepeatedMessages {
+    /// Size of the `messages1` field once written, tag included, 0 if it is not written
+    pub fn messages1_encoded_len(&self) -> usize {
+        self.messages1.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
+    }
+
+    /// Size of the `messages2` field once written, tag included, 0 if it is not written
+    pub fn messages2_encoded_len(&self) -> usize {
+        self.messages2.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
+    }
+
+    /// Size of the `messages3` field once written, tag included, 0 if it is not written
+    pub fn messages3_encoded_len(&self) -> usize {
+        self.messages3.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
+    }
+
+    /// Lower bound of `get_size`: the minimum size of the required fields, which are always written
+    pub const fn size_hint() -> usize {
+        0
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct TestOptionalMessages {
     pub message1: Option<Box<TestOptionalMessages>>,
@@ -143,36 +253,72 @@ pub struct TestOptionalMessages {
 }
 
 impl MessageRead for TestOptionalMessages {
-    fn from_reader<R: Read>(mut r: &mut Reader<R>) -> Result<Self> {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
         let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        let msg = self;
         while !r.is_eof() {
             match r.next_tag() {
-                Ok(10) => msg.message1 = Some(Box::new(r.read_message()?)),
-                Ok(18) => msg.message2 = Some(Box::new(r.read_message()?)),
-                Ok(26) => msg.message3 = Some(Box::new(r.read_message()?)),
+                Ok(10) => r.merge_message(&mut **msg.message1.get_or_insert_with(Default::default))?,
+                Ok(18) => r.merge_message(&mut **msg.message2.get_or_insert_with(Default::default))?,
+                Ok(26) => r.merge_message(&mut **msg.message3.get_or_insert_with(Default::default))?,
                 Ok(t) => { r.read_unknown(t)?; }
                 Err(e) => return Err(e),
             }
         }
-        Ok(msg)
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.message1 = Default::default();
+        self.message2 = Default::default();
+        self.message3 = Default::default();
     }
 }
 
 impl MessageWrite for TestOptionalMessages {
     fn get_size(&self) -> usize {
-        self.message1.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.get_size()))
-        + self.message2.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.get_size()))
-        + self.message3.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.get_size()))
+        self.message1_encoded_len()
+        + self.message2_encoded_len()
+        + self.message3_encoded_len()
     }
 
     fn write_message<W: Write>(&self, r: &mut Writer<W>) -> Result<()> {
+        let start = r.written();
         if let Some(ref s) = self.message1 { r.write_message_with_tag(10, &**s)?; }
         if let Some(ref s) = self.message2 { r.write_message_with_tag(18, &**s)?; }
         if let Some(ref s) = self.message3 { r.write_message_with_tag(26, &**s)?; }
+        debug_assert_eq!(r.written() - start, self.get_size(), "TestOptionalMessages: written size differs from get_size");
         Ok(())
     }
 }
 
+impl TestOptionalMessages {
+    /// Size of the `message1` field once written, tag included, 0 if it is not written
+    pub fn message1_encoded_len(&self) -> usize {
+        self.message1.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.get_size()))
+    }
+
+    /// Size of the `message2` field once written, tag included, 0 if it is not written
+    pub fn message2_encoded_len(&self) -> usize {
+        self.message2.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.get_size()))
+    }
+
+    /// Size of the `message3` field once written, tag included, 0 if it is not written
+    pub fn message3_encoded_len(&self) -> usize {
+        self.message3.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.get_size()))
+    }
+
+    /// Lower bound of `get_size`: the minimum size of the required fields, which are always written
+    pub const fn size_hint() -> usize {
+        0
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct TestStrings {
     pub s1: Option<String>,
@@ -181,8 +327,14 @@ pub struct TestStrings {
 }
 
 impl MessageRead for TestStrings {
-    fn from_reader<R: Read>(mut r: &mut Reader<R>) -> Result<Self> {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
         let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        let msg = self;
         while !r.is_eof() {
             match r.next_tag() {
                 Ok(10) => msg.s1 = Some(r.read_string()?),
@@ -192,33 +344,69 @@ impl MessageRead for TestStrings {
                 Err(e) => return Err(e),
             }
         }
-        Ok(msg)
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.s1 = Default::default();
+        self.s2 = Default::default();
+        self.s3 = Default::default();
     }
 }
 
 impl MessageWrite for TestStrings {
     fn get_size(&self) -> usize {
-        self.s1.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
-        + self.s2.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
-        + self.s3.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+        self.s1_encoded_len()
+        + self.s2_encoded_len()
+        + self.s3_encoded_len()
     }
 
     fn write_message<W: Write>(&self, r: &mut Writer<W>) -> Result<()> {
+        let start = r.written();
         if let Some(ref s) = self.s1 { r.write_string_with_tag(10, s)?; }
         if let Some(ref s) = self.s2 { r.write_string_with_tag(18, s)?; }
         if let Some(ref s) = self.s3 { r.write_string_with_tag(26, s)?; }
+        debug_assert_eq!(r.written() - start, self.get_size(), "TestStrings: written size differs from get_size");
         Ok(())
     }
 }
 
+impl TestStrings {
+    /// Size of the `s1` field once written, tag included, 0 if it is not written
+    pub fn s1_encoded_len(&self) -> usize {
+        self.s1.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `s2` field once written, tag included, 0 if it is not written
+    pub fn s2_encoded_len(&self) -> usize {
+        self.s2.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `s3` field once written, tag included, 0 if it is not written
+    pub fn s3_encoded_len(&self) -> usize {
+        self.s3.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Lower bound of `get_size`: the minimum size of the required fields, which are always written
+    pub const fn size_hint() -> usize {
+        0
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct TestBytes {
     pub b1: Option<Vec<u8>>,
 }
 
 impl MessageRead for TestBytes {
-    fn from_reader<R: Read>(mut r: &mut Reader<R>) -> Result<Self> {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
         let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        let msg = self;
         while !r.is_eof() {
             match r.next_tag() {
                 Ok(10) => msg.b1 = Some(r.read_bytes()?),
@@ -226,21 +414,39 @@ impl MessageRead for TestBytes {
                 Err(e) => return Err(e),
             }
         }
-        Ok(msg)
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.b1 = Default::default();
     }
 }
 
 impl MessageWrite for TestBytes {
     fn get_size(&self) -> usize {
-        self.b1.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+        self.b1_encoded_len()
     }
 
     fn write_message<W: Write>(&self, r: &mut Writer<W>) -> Result<()> {
+        let start = r.written();
         if let Some(ref s) = self.b1 { r.write_bytes_with_tag(10, s)?; }
+        debug_assert_eq!(r.written() - start, self.get_size(), "TestBytes: written size differs from get_size");
         Ok(())
     }
 }
 
+impl TestBytes {
+    /// Size of the `b1` field once written, tag included, 0 if it is not written
+    pub fn b1_encoded_len(&self) -> usize {
+        self.b1.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Lower bound of `get_size`: the minimum size of the required fields, which are always written
+    pub const fn size_hint() -> usize {
+        0
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct PerftestData {
     pub test1: Vec<Test1>,
@@ -254,39 +460,57 @@ pub struct PerftestData {
 }
 
 impl MessageRead for PerftestData {
-    fn from_reader<R: Read>(mut r: &mut Reader<R>) -> Result<Self> {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
         let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        let msg = self;
         while !r.is_eof() {
             match r.next_tag() {
-                Ok(10) => msg.test1.push(r.read_message()?),
-                Ok(18) => msg.test_repeated_bool.push(r.read_message()?),
-                Ok(26) => msg.test_repeated_messages.push(r.read_message()?),
-                Ok(34) => msg.test_optional_messages.push(r.read_message()?),
-                Ok(42) => msg.test_strings.push(r.read_message()?),
-                Ok(50) => msg.test_repeated_packed_int32.push(r.read_message()?),
-                Ok(58) => msg.test_small_bytearrays.push(r.read_message()?),
-                Ok(66) => msg.test_large_bytearrays.push(r.read_message()?),
+                Ok(10) => r.read_message_to_vec(&mut msg.test1)?,
+                Ok(18) => r.read_message_to_vec(&mut msg.test_repeated_bool)?,
+                Ok(26) => r.read_message_to_vec(&mut msg.test_repeated_messages)?,
+                Ok(34) => r.read_message_to_vec(&mut msg.test_optional_messages)?,
+                Ok(42) => r.read_message_to_vec(&mut msg.test_strings)?,
+                Ok(50) => r.read_message_to_vec(&mut msg.test_repeated_packed_int32)?,
+                Ok(58) => r.read_message_to_vec(&mut msg.test_small_bytearrays)?,
+                Ok(66) => r.read_message_to_vec(&mut msg.test_large_bytearrays)?,
                 Ok(t) => { r.read_unknown(t)?; }
                 Err(e) => return Err(e),
             }
         }
-        Ok(msg)
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.test1.clear();
+        self.test_repeated_bool.clear();
+        self.test_repeated_messages.clear();
+        self.test_optional_messages.clear();
+        self.test_strings.clear();
+        self.test_repeated_packed_int32.clear();
+        self.test_small_bytearrays.clear();
+        self.test_large_bytearrays.clear();
     }
 }
 
 impl MessageWrite for PerftestData {
     fn get_size(&self) -> usize {
-        self.test1.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
-        + self.test_repeated_bool.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
-        + self.test_repeated_messages.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
-        + self.test_optional_messages.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
-        + self.test_strings.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
-        + self.test_repeated_packed_int32.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
-        + self.test_small_bytearrays.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
-        + self.test_large_bytearrays.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
+        self.test1_encoded_len()
+        + self.test_repeated_bool_encoded_len()
+        + self.test_repeated_messages_encoded_len()
+        + self.test_optional_messages_encoded_len()
+        + self.test_strings_encoded_len()
+        + self.test_repeated_packed_int32_encoded_len()
+        + self.test_small_bytearrays_encoded_len()
+        + self.test_large_bytearrays_encoded_len()
     }
 
     fn write_message<W: Write>(&self, r: &mut Writer<W>) -> Result<()> {
+        let start = r.written();
         for s in &self.test1 { r.write_message_with_tag(10, s)? }
         for s in &self.test_repeated_bool { r.write_message_with_tag(18, s)? }
         for s in &self.test_repeated_messages { r.write_message_with_tag(26, s)? }
@@ -295,6 +519,54 @@ impl MessageWrite for PerftestData {
         for s in &self.test_repeated_packed_int32 { r.write_message_with_tag(50, s)? }
         for s in &self.test_small_bytearrays { r.write_message_with_tag(58, s)? }
         for s in &self.test_large_bytearrays { r.write_message_with_tag(66, s)? }
+        debug_assert_eq!(r.written() - start, self.get_size(), "PerftestData: written size differs from get_size");
         Ok(())
     }
 }
+
+impl PerftestData {
+    /// Size of the `test1` field once written, tag included, 0 if it is not written
+    pub fn test1_encoded_len(&self) -> usize {
+        self.test1.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
+    }
+
+    /// Size of the `test_repeated_bool` field once written, tag included, 0 if it is not written
+    pub fn test_repeated_bool_encoded_len(&self) -> usize {
+        self.test_repeated_bool.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
+    }
+
+    /// Size of the `test_repeated_messages` field once written, tag included, 0 if it is not written
+    pub fn test_repeated_messages_encoded_len(&self) -> usize {
+        self.test_repeated_messages.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
+    }
+
+    /// Size of the `test_optional_messages` field once written, tag included, 0 if it is not written
+    pub fn test_optional_messages_encoded_len(&self) -> usize {
+        self.test_optional_messages.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
+    }
+
+    /// Size of the `test_strings` field once written, tag included, 0 if it is not written
+    pub fn test_strings_encoded_len(&self) -> usize {
+        self.test_strings.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
+    }
+
+    /// Size of the `test_repeated_packed_int32` field once written, tag included, 0 if it is not written
+    pub fn test_repeated_packed_int32_encoded_len(&self) -> usize {
+        self.test_repeated_packed_int32.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
+    }
+
+    /// Size of the `test_small_bytearrays` field once written, tag included, 0 if it is not written
+    pub fn test_small_bytearrays_encoded_len(&self) -> usize {
+        self.test_small_bytearrays.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
+    }
+
+    /// Size of the `test_large_bytearrays` field once written, tag included, 0 if it is not written
+    pub fn test_large_bytearrays_encoded_len(&self) -> usize {
+        self.test_large_bytearrays.iter().map(|s| 1 + sizeof_var_length(s.get_size())).sum::<usize>()
+    }
+
+    /// Lower bound of `get_size`: the minimum size of the required fields, which are always written
+    pub const fn size_hint() -> usize {
+        0
+    }
+}
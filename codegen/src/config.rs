@@ -0,0 +1,195 @@
+use types::Container;
+
+/// Generator options, set from the command line
+///
+/// Most of them can be overridden per item with a custom proto option
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Default container for `repeated` fields (`Vec` if not set)
+    pub container: Option<Container>,
+    /// Embeds the serialized `FileDescriptorSet` as `FILE_DESCRIPTOR`
+    pub descriptor: bool,
+    /// Generates proto3 enums as exhaustive C-like enums, without `Unknown(i32)`
+    pub exhaustive_enums: bool,
+    /// Extra attributes for a message (`Name`) or a field (`Name.field`)
+    pub attributes: Vec<(String, String)>,
+    /// User types of fields (`Name.field`), implementing `ProtoConvert`
+    pub user_types: Vec<(String, String)>,
+    /// Rust names of fields (`Name.field`), instead of their .proto name
+    pub renames: Vec<(String, String)>,
+    /// Other .proto file and the rust module it is generated into, to convert messages from
+    pub from_file: Option<(String, String)>,
+    /// Imported .proto files generated in another rust module, as (import path, module) pairs
+    pub imports: Vec<(String, String)>,
+    /// `From` conversions to generate, as (message, other file message) pairs
+    pub conversions: Vec<(String, String)>,
+    /// Messages implementing `Eq`, `Hash` and `Ord`
+    pub ord: Vec<String>,
+    /// Derives serde `Serialize` and `Deserialize`, following the protobuf JSON field names
+    pub serde: bool,
+    /// Generates `to_value` and `from_value`, converting messages to and from `serde_json::Value`
+    pub serde_json: bool,
+    /// Serializes unknown fields with serde, as the base64 of their encoded bytes under `@unknown`
+    pub serde_unknown_fields: bool,
+    /// Follows prost naming conventions
+    pub prost: bool,
+    /// Keeps unknown fields to write them back
+    pub unknown_fields: bool,
+    /// Generates builders checking at compile time that required fields are set
+    pub builders: bool,
+    /// Implements `Display` on messages and enums, printing messages in the text format
+    pub display: bool,
+    /// Generates an async trait per service
+    pub services: bool,
+    /// Generates `eq_bitwise` on messages, comparing NaN floats as equal
+    pub eq_bitwise: bool,
+    /// Implements `Validate` on messages, checking the `(rust.required)`, `(rust.min)` ... constraints
+    pub validate: bool,
+    /// Implements `FieldByNumber` on messages, getting their fields by number as `FieldValue`s
+    pub field_values: bool,
+    /// Generates a `<Message>Tag` newtype per message, with a constant per field number
+    pub typed_tags: bool,
+    /// Generates `validate_bytes` on messages, checking an encoded message without building it
+    pub validate_bytes: bool,
+}
+
+impl Config {
+
+    /// Description of all the options, for the command line usage
+    pub fn usage() -> &'static str {
+        "    --repeated=<container>           container for repeated fields: vec, box, smallvec:N or pool \
+                                              (default: vec)\r\n\
+         \x20   --descriptor                     embed the serialized FileDescriptorSet\r\n\
+         \x20   --exhaustive-enums               no #[non_exhaustive] and Unknown(i32) on proto3 enums\r\n\
+         \x20   --attr=<Message[.field]>=<attr>  add an attribute, e.g. --attr='Foo=#[derive(Hash)]'\r\n\
+         \x20   --type=<Message.field>=<Type>    use a type implementing ProtoConvert for a field\r\n\
+         \x20   --rename=<Message.field>=<name>  rust name of a field\r\n\
+         \x20   --from-file=<file.proto>=<module>  other file to convert messages from\r\n\
+         \x20   --from=<Message>[=<Other>]       generate From<module::Other> for Message\r\n\
+         \x20   --import=<file.proto>=<module>   use the types of an import generated in a module\r\n\
+         \x20   --ord=<Message>                  implement Eq, Hash and Ord on a message\r\n\
+         \x20   --serde                          derive serde Serialize and Deserialize\r\n\
+         \x20   --serde-json                     --serde, with serde_json::Value conversions\r\n\
+         \x20   --serde-unknown-fields           --serde and --unknown-fields, serializing unknown fields\r\n\
+         \x20   --prost                          name fields like prost (snake_case)\r\n\
+         \x20   --unknown-fields                 keep unknown fields and write them back\r\n\
+         \x20   --builders                       generate builders checking required fields\r\n\
+         \x20   --display                        implement Display with the text format\r\n\
+         \x20   --services                       generate async traits for services\r\n\
+         \x20   --eq-bitwise                     generate eq_bitwise, with NaN floats equal\r\n\
+         \x20   --validate                       implement Validate, checking field constraints\r\n\
+         \x20   --field-values                   implement FieldByNumber, getting fields by number\r\n\
+         \x20   --typed-tags                     generate a <Message>Tag newtype of field numbers\r\n\
+         \x20   --validate-bytes                 generate validate_bytes, checking encoded messages"
+    }
+
+    /// Splits command line arguments into a `Config` and the remaining (file) arguments
+    pub fn from_args(args: &[String]) -> Result<(Config, Vec<String>), String> {
+        let mut config = Config::default();
+        let mut files = Vec::new();
+        for arg in args {
+            if !arg.starts_with("--") {
+                files.push(arg.clone());
+                continue;
+            }
+            let mut kv = arg[2..].splitn(2, '=');
+            match (kv.next().unwrap(), kv.next()) {
+                ("repeated", Some(v)) => config.container = Some(v.parse()?),
+                ("descriptor", None) => config.descriptor = true,
+                ("exhaustive-enums", None) => config.exhaustive_enums = true,
+                ("attr", Some(v)) => {
+                    let mut item_attr = v.splitn(2, '=');
+                    match (item_attr.next(), item_attr.next()) {
+                        (Some(item), Some(attr)) if !item.is_empty() && !attr.is_empty() => {
+                            config.attributes.push((item.to_string(), attr.to_string()))
+                        }
+                        _ => return Err(format!("Invalid attribute '{}', expecting <Message[.field]>=<attr>", v)),
+                    }
+                }
+                ("type", Some(v)) => {
+                    let mut field_type = v.splitn(2, '=');
+                    match (field_type.next(), field_type.next()) {
+                        (Some(field), Some(typ)) if field.contains('.') && !typ.is_empty() => {
+                            config.user_types.push((field.to_string(), typ.to_string()))
+                        }
+                        _ => return Err(format!("Invalid type '{}', expecting <Message.field>=<Type>", v)),
+                    }
+                }
+                ("rename", Some(v)) => {
+                    let mut field_name = v.splitn(2, '=');
+                    match (field_name.next(), field_name.next()) {
+                        (Some(field), Some(name)) if field.contains('.') && !name.is_empty() => {
+                            config.renames.push((field.to_string(), name.to_string()))
+                        }
+                        _ => return Err(format!("Invalid rename '{}', expecting <Message.field>=<name>", v)),
+                    }
+                }
+                ("from-file", Some(v)) => {
+                    let mut file_module = v.splitn(2, '=');
+                    match (file_module.next(), file_module.next()) {
+                        (Some(file), Some(module)) if !file.is_empty() && !module.is_empty() => {
+                            config.from_file = Some((file.to_string(), module.to_string()))
+                        }
+                        _ => return Err(format!("Invalid file '{}', expecting <file.proto>=<module>", v)),
+                    }
+                }
+                ("import", Some(v)) => {
+                    let mut file_module = v.splitn(2, '=');
+                    match (file_module.next(), file_module.next()) {
+                        (Some(file), Some(module)) if !file.is_empty() && !module.is_empty() => {
+                            config.imports.push((file.to_string(), module.to_string()))
+                        }
+                        _ => return Err(format!("Invalid import '{}', expecting <file.proto>=<module>", v)),
+                    }
+                }
+                ("ord", Some(v)) => config.ord.push(v.to_string()),
+                ("serde", None) => config.serde = true,
+                ("serde-json", None) => {
+                    config.serde = true;
+                    config.serde_json = true;
+                }
+                ("serde-unknown-fields", None) => {
+                    config.serde = true;
+                    config.unknown_fields = true;
+                    config.serde_unknown_fields = true;
+                }
+                ("prost", None) => config.prost = true,
+                ("unknown-fields", None) => config.unknown_fields = true,
+                ("builders", None) => config.builders = true,
+                ("display", None) => config.display = true,
+                ("services", None) => config.services = true,
+                ("eq-bitwise", None) => config.eq_bitwise = true,
+                ("validate", None) => config.validate = true,
+                ("field-values", None) => config.field_values = true,
+                ("typed-tags", None) => config.typed_tags = true,
+                ("validate-bytes", None) => config.validate_bytes = true,
+                ("from", Some(v)) => {
+                    let mut names = v.splitn(2, '=');
+                    let name = names.next().unwrap().to_string();
+                    let other = names.next().map_or_else(|| name.clone(), |o| o.to_string());
+                    config.conversions.push((name, other));
+                }
+                _ => return Err(format!("Unknown option '{}'", arg)),
+            }
+        }
+        if !config.conversions.is_empty() && config.from_file.is_none() {
+            return Err("--from requires --from-file".to_string());
+        }
+        Ok((config, files))
+    }
+
+    /// Attributes set on the command line for a message (`Name`) or a field (`Name.field`)
+    pub fn attributes<'a>(&'a self, item: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.attributes.iter().filter(move |a| a.0 == item).map(|a| &*a.1)
+    }
+
+    /// User type set on the command line for a field (`Name.field`)
+    pub fn user_type(&self, field: &str) -> Option<&str> {
+        self.user_types.iter().find(|t| t.0 == field).map(|t| &*t.1)
+    }
+
+    /// Rust name set on the command line for a field (`Name.field`)
+    pub fn rename(&self, field: &str) -> Option<&str> {
+        self.renames.iter().find(|r| r.0 == field).map(|r| &*r.1)
+    }
+}
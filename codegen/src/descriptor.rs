@@ -0,0 +1,182 @@
+//! Serialization of the parsed file as a protobuf `FileDescriptorSet`
+//!
+//! Messages and tags are the ones defined in google/protobuf/descriptor.proto
+
+use quick_protobuf::{Writer, Result};
+
+use types::{FileDescriptor, Message, Enumerator, Field, Frequency, Syntax, Service};
+
+/// Encodes a nested message into its own buffer, to be written with `write_bytes_with_tag`
+fn encode<F: FnOnce(&mut Writer<&mut Vec<u8>>) -> Result<()>>(f: F) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    f(&mut Writer::new(&mut buf))?;
+    Ok(buf)
+}
+
+/// `FieldDescriptorProto.Type`
+fn field_type(f: &Field, enums: &[&str]) -> i32 {
+    match f.typ {
+        "double" => 1,
+        "float" => 2,
+        "int64" => 3,
+        "uint64" => 4,
+        "int32" => 5,
+        "fixed64" => 6,
+        "fixed32" => 7,
+        "bool" => 8,
+        "string" => 9,
+        "bytes" => 12,
+        "uint32" => 13,
+        "sfixed32" => 15,
+        "sfixed64" => 16,
+        "sint32" => 17,
+        "sint64" => 18,
+        t if enums.contains(&t) || f.extern_enum => 14,
+        _ => 11,
+    }
+}
+
+fn encode_field(f: &Field, desc: &FileDescriptor, enums: &[&str]) -> Result<Vec<u8>> {
+    let typ = field_type(f, enums);
+    let is_proto2 = match desc.syntax {
+        Syntax::Proto2 => true,
+        Syntax::Proto3 => false,
+    };
+    encode(|w| {
+        w.write_string_with_tag(10, f.name)?;
+        w.write_int32_with_tag(24, f.number)?;
+        w.write_enum_with_tag(32, match f.frequency {
+            Frequency::Optional => 1,
+            Frequency::Required => 2,
+            Frequency::Repeated => 3,
+        })?;
+        w.write_enum_with_tag(40, typ)?;
+        if typ == 11 || typ == 14 {
+            let type_name = match desc.package {
+                // a type of an imported file, qualified with its package
+                _ if f.extern_type.is_some() && f.typ.contains('.') => format!(".{}", f.typ.trim_start_matches('.')),
+                Some(p) => format!(".{}.{}", p, f.typ),
+                None => format!(".{}", f.typ),
+            };
+            w.write_string_with_tag(50, &type_name)?;
+        }
+        // proto3 defaults are set by the generator, they are not part of the file
+        if let (true, Some(d)) = (is_proto2, f.default) {
+            w.write_string_with_tag(58, d.trim_matches('"'))?;
+        }
+        let packed = match f.packed {
+            Some(p) if is_proto2 || !p => Some(p),
+            _ => None,
+        };
+        if packed.is_some() || f.deprecated {
+            let options = encode(|w| {
+                if let Some(p) = packed {
+                    w.write_bool_with_tag(16, p)?;
+                }
+                if f.deprecated {
+                    w.write_bool_with_tag(24, true)?;
+                }
+                Ok(())
+            })?;
+            w.write_bytes_with_tag(66, &options)?;
+        }
+        Ok(())
+    })
+}
+
+fn encode_message(m: &Message, desc: &FileDescriptor, enums: &[&str]) -> Result<Vec<u8>> {
+    encode(|w| {
+        w.write_string_with_tag(10, m.name)?;
+        for f in &m.fields {
+            w.write_bytes_with_tag(18, &encode_field(f, desc, enums)?)?;
+        }
+        Ok(())
+    })
+}
+
+fn encode_enum(e: &Enumerator) -> Result<Vec<u8>> {
+    encode(|w| {
+        w.write_string_with_tag(10, e.name)?;
+        for &(name, number) in &e.fields {
+            let value = encode(|w| {
+                w.write_string_with_tag(10, name)?;
+                w.write_int32_with_tag(16, number)
+            })?;
+            w.write_bytes_with_tag(18, &value)?;
+        }
+        Ok(())
+    })
+}
+
+fn encode_service(s: &Service, desc: &FileDescriptor) -> Result<Vec<u8>> {
+    let type_name = |t| match desc.package {
+        Some(p) => format!(".{}.{}", p, t),
+        None => format!(".{}", t),
+    };
+    encode(|w| {
+        w.write_string_with_tag(10, s.name)?;
+        for rpc in &s.rpcs {
+            let method = encode(|w| {
+                w.write_string_with_tag(10, rpc.name)?;
+                w.write_string_with_tag(18, &type_name(rpc.input))?;
+                w.write_string_with_tag(26, &type_name(rpc.output))?;
+                if rpc.input_stream {
+                    w.write_bool_with_tag(40, true)?;
+                }
+                if rpc.output_stream {
+                    w.write_bool_with_tag(48, true)?;
+                }
+                Ok(())
+            })?;
+            w.write_bytes_with_tag(18, &method)?;
+        }
+        Ok(())
+    })
+}
+
+/// Serializes a `FileDescriptorSet` containing the `FileDescriptorProto` of `desc`
+pub fn encode_file_descriptor_set(desc: &FileDescriptor, filename: &str) -> Result<Vec<u8>> {
+    let enums = desc.enums.iter().map(|e| e.name).collect::<Vec<_>>();
+    let file = encode(|w| {
+        w.write_string_with_tag(10, filename)?;
+        if let Some(p) = desc.package {
+            w.write_string_with_tag(18, p)?;
+        }
+        for i in &desc.imports {
+            w.write_string_with_tag(26, i)?;
+        }
+        for m in &desc.messages {
+            w.write_bytes_with_tag(34, &encode_message(m, desc, &enums)?)?;
+        }
+        for e in &desc.enums {
+            w.write_bytes_with_tag(42, &encode_enum(e)?)?;
+        }
+        for s in &desc.services {
+            w.write_bytes_with_tag(50, &encode_service(s, desc)?)?;
+        }
+        if let Syntax::Proto3 = desc.syntax {
+            w.write_string_with_tag(98, "proto3")?;
+        }
+        Ok(())
+    })?;
+    encode(|w| w.write_bytes_with_tag(10, &file))
+}
+
+#[test]
+fn test_encode_enum() {
+    let desc = FileDescriptor::from_bytes(b"enum Kind { A = 0; B = 1; }", &Default::default()).unwrap();
+    let bytes = encode_file_descriptor_set(&desc, "k.proto").unwrap();
+    assert_eq!(vec![10, 31, 10, 7, b'k', b'.', b'p', b'r', b'o', b't', b'o',
+                    42, 20, 10, 4, b'K', b'i', b'n', b'd',
+                    18, 5, 10, 1, b'A', 16, 0,
+                    18, 5, 10, 1, b'B', 16, 1], bytes);
+}
+
+#[test]
+fn test_encode_service() {
+    let desc = FileDescriptor::from_bytes(b"service S { rpc M(stream A) returns (B); }", &Default::default()).unwrap();
+    let bytes = encode_file_descriptor_set(&desc, "s").unwrap();
+    assert_eq!(vec![10, 23, 10, 1, b's',
+                    50, 18, 10, 1, b'S',
+                    18, 13, 10, 1, b'M', 18, 2, b'.', b'A', 26, 2, b'.', b'B', 40, 1], bytes);
+}
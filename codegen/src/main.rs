@@ -1,26 +1,39 @@
 #[macro_use]
 extern crate nom;
+extern crate quick_protobuf;
 
 mod parser;
 mod types;
+mod config;
+mod descriptor;
 
 use std::env;
 use std::path::PathBuf;
 use std::fs::File;
 use std::io::{Read, BufReader, BufWriter};
 use types::FileDescriptor;
+use config::Config;
 
 fn main() {
 
     let args = env::args().collect::<Vec<_>>();
-    let usage = format!("{} <file.proto>", args[0]);
+    let usage = format!("{} [options] <file.proto>\r\n\r\nOptions:\r\n{}", args[0], Config::usage());
 
-    if args.len() == 0 {
+    let (config, files) = match Config::from_args(&args[1..]) {
+        Ok(c) => c,
+        Err(e) => {
+            println!("{}", usage);
+            println!("\r\n{}", e);
+            return;
+        }
+    };
+
+    if files.is_empty() {
         println!("{}", usage);
         return;
     }
 
-    let in_file: PathBuf = args.get(1).map(|a| a.into()).unwrap();
+    let in_file: PathBuf = files[0].clone().into();
     match in_file.extension().and_then(|e| e.to_str()) {
         Some("proto") => (),
         _ => {
@@ -35,15 +48,36 @@ fn main() {
     let mut data = Vec::with_capacity(in_file.metadata()
                                      .expect("Cannot get input file length")
                                      .len() as usize);
+    // imports are relative to the directory of the file
+    let dir = in_file.parent().map_or_else(PathBuf::new, |d| d.to_path_buf());
+    let import_data = config.imports.iter().map(|(import, _)| {
+        let mut data = Vec::new();
+        File::open(dir.join(import)).and_then(|mut f| f.read_to_end(&mut data))
+            .expect("Cannot read --import file");
+        data
+    }).collect::<Vec<_>>();
+    let imports = config.imports.iter().zip(import_data.iter()).map(|((import, module), data)| {
+        let imported = FileDescriptor::from_bytes(data, &Default::default()).expect("Cannot parse --import file");
+        (&**import, imported, &**module)
+    }).collect::<Vec<_>>();
+
     let parsed_file = {
         let f = File::open(&in_file).expect(&usage);
         let mut reader = BufReader::new(f);
         reader.read_to_end(&mut data).expect("Cannot read input file");
-        FileDescriptor::from_bytes(&data).expect("Cannot parse protobuf messages")
+        FileDescriptor::from_bytes_with_imports(&data, &config, &imports).expect("Cannot parse protobuf messages")
     };
 
     let name = in_file.file_name().and_then(|e| e.to_str()).unwrap();
     let mut w = BufWriter::new(File::create(out_file).expect("Cannot create output file"));
-    parsed_file.write(&mut w, name).expect("Cannot write rust module");
+    parsed_file.write(&mut w, name, &config).expect("Cannot write rust module");
+
+    if let Some((ref other_file, ref module)) = config.from_file {
+        let mut other_data = Vec::new();
+        File::open(other_file).and_then(|mut f| f.read_to_end(&mut other_data))
+            .expect("Cannot read --from-file file");
+        let other = FileDescriptor::from_bytes(&other_data, &config).expect("Cannot parse --from-file file");
+        parsed_file.write_conversions(&mut w, &other, module, &config).expect("Cannot write conversions");
+    }
 
 }
@@ -1,5 +1,5 @@
 use std::str;
-use types::{Frequency, Field, Message, Enumerator, MessageOrEnum, FileDescriptor, Syntax};
+use types::{Frequency, Field, Message, Enumerator, MessageOrEnum, FileDescriptor, Syntax, Container, Service, Rpc};
 use nom::{multispace, digit};
 
 fn is_word(b: u8) -> bool {
@@ -9,32 +9,62 @@ fn is_word(b: u8) -> bool {
     }
 }
 
+/// Item of a message body
+enum MessageItem<'a> {
+    Field(Box<Field<'a>>),
+    Option((&'a str, &'a str)),
+    Oneof(&'a str),
+}
+
+enum EnumItem<'a> {
+    Field((&'a str, i32)),
+    Option((&'a str, &'a str)),
+}
+
+fn get_option<'a>(options: &[(&'a str, &'a str)], key: &str) -> Option<&'a str> {
+    options.iter().find(|o| o.0 == key).map(|o| o.1)
+}
+
 named!(word<&str>, map_res!(take_while!(is_word), str::from_utf8));
 
+// type of a field, possibly qualified with a package (`common.Money`, `.common.Money`)
+named!(type_name<&'a str>, map_res!(take_while1!(|b| is_word(b) || b == b'.'), str::from_utf8));
+
 named!(comment<()>, do_parse!(tag!("//") >> take_until_and_consume!("\n") >> ()));
 named!(block_comment<()>, do_parse!(tag!("/*") >> take_until_and_consume!("*/") >> ()));
 
 /// word break: multispace or comment
 named!(br<()>, alt!(map!(multispace, |_| ()) | comment | block_comment));
 
-named!(syntax<Syntax>, do_parse!(tag!("syntax") >> many0!(br) >> tag!("=") >>
+named!(syntax<Syntax>, do_parse!(tag!("syntax") >> many0!(br) >> tag!("=") >> many0!(br) >>
     proto: alt!(tag!("\"proto2\"") => { |_| Syntax::Proto2 } |
-                tag!("\"proto3\"") => { |_| Syntax::Proto3 }) >> (proto)));
+                tag!("\"proto3\"") => { |_| Syntax::Proto3 }) >> many0!(br) >>
+    tag!(";") >> (proto)));
+
+fn is_option_value(b: u8) -> bool {
+    is_word(b) || b == b'-' || b == b'+' || b == b'.'
+}
 
-named!(default_value<&str>, do_parse!(
-    tag!("[") >> many0!(br) >> tag!("default") >> many0!(br) >> tag!("=") >> many0!(br) >> 
-    default: word >> many0!(br) >> tag!("]") >>
-    (default)));
+// option key, either a builtin (`default`) or a custom (`(rust.container)`) option
+named!(option_key<&'a str>, map_res!(alt!(
+    take_while1!(is_word) |
+    recognize!(delimited!(tag!("("), take_until!(")"), tag!(")")))), str::from_utf8));
 
-named!(deprecated<bool>, do_parse!(
-    tag!("[") >> many0!(br) >> tag!("deprecated") >> many0!(br) >> tag!("=") >> many0!(br) >> 
-    deprecated: map_res!(word, str::FromStr::from_str) >> many0!(br) >> tag!("]") >>
-    (deprecated)));
+// option value, a word, a number or a quoted string (quotes are kept)
+named!(option_value<&'a str>, map_res!(alt!(
+    take_while1!(is_option_value) |
+    recognize!(delimited!(tag!("\""), take_until!("\""), tag!("\"")))), str::from_utf8));
 
-named!(packed<bool>, do_parse!(
-    tag!("[") >> many0!(br) >> tag!("packed") >> many0!(br) >> tag!("=") >> many0!(br) >> 
-    packed: map_res!(word, str::FromStr::from_str) >> many0!(br) >> tag!("]") >>
-    (packed)));
+named!(field_option<(&'a str, &'a str)>, do_parse!(
+    key: option_key >> many0!(br) >> tag!("=") >> many0!(br) >>
+    value: option_value >> many0!(br) >>
+    ((key, value))));
+
+named!(field_options<Vec<(&'a str, &'a str)>>, do_parse!(
+    tag!("[") >> many0!(br) >>
+    options: separated_list!(do_parse!(tag!(",") >> many0!(br) >> ()), field_option) >>
+    tag!("]") >>
+    (options)));
 
 named!(frequency<Frequency>,
        alt!(tag!("optional") => { |_| Frequency::Optional } |
@@ -42,69 +72,178 @@ named!(frequency<Frequency>,
             tag!("required") => { |_| Frequency::Required } ));
 
 named!(message_field<Field>, do_parse!(
-    frequency: opt!(frequency) >> many1!(br) >>
-    typ: word >> many1!(br) >>
+    frequency: opt!(do_parse!(f: frequency >> many1!(br) >> (f))) >>
+    typ: type_name >> many1!(br) >>
     name: word >> many0!(br) >>
     tag!("=") >> many0!(br) >>
     number: map_res!(map_res!(digit, str::from_utf8), str::FromStr::from_str) >> many0!(br) >> 
-    default: opt!(default_value) >> many0!(br) >> 
-    deprecated: opt!(deprecated) >> many0!(br) >> 
-    packed: opt!(packed) >> many0!(br) >> tag!(";") >> many0!(br) >>
-    (Field {
-       name: name,
-       frequency: frequency.unwrap_or(Frequency::Optional),
-       typ: typ,
-       number: number,
-       default: default,
-       packed: packed,
-       boxed: false,
-       deprecated: deprecated.unwrap_or(false),
+    options: opt!(field_options) >> many0!(br) >> tag!(";") >> many0!(br) >>
+    ({
+        let options = options.unwrap_or_else(Vec::new);
+        Field {
+           name: name,
+           rust_name: name.to_string(),
+           labeled: frequency.is_some(),
+           frequency: frequency.unwrap_or(Frequency::Optional),
+           typ: typ,
+           number: number,
+           default: get_option(&options, "default"),
+           packed: get_option(&options, "packed").and_then(|p| p.parse().ok()),
+           boxed: false,
+           deprecated: get_option(&options, "deprecated") == Some("true"),
+           container: Container::Vec,
+           options,
+           attributes: Vec::new(),
+           user_type: None,
+           feature: None,
+           collection: false,
+           borrow: false,
+           intern: false,
+           lazy: false,
+           lifetime: false,
+           nested_required: false,
+           constraints: Vec::new(),
+           extern_type: None,
+           extern_enum: false,
+        }
     })));
 
+named!(message_option<(&'a str, &'a str)>, do_parse!(
+    tag!("option") >> many1!(br) >>
+    option: field_option >> tag!(";") >> many0!(br) >>
+    (option)));
+
+// `oneof`s are not supported yet: only their name is kept, to reject them with an error
+named!(oneof<&'a str>, do_parse!(
+    tag!("oneof") >> many1!(br) >>
+    name: word >> many0!(br) >>
+    tag!("{") >> many0!(br) >>
+    many0!(message_item) >>
+    tag!("}") >> many0!(br) >>
+    (name)));
+
+named!(message_item<MessageItem<'a>>, alt!(
+    message_option => { MessageItem::Option } |
+    oneof => { MessageItem::Oneof } |
+    message_field => { |f| MessageItem::Field(Box::new(f)) }));
+
 named!(message<Message>, do_parse!(
     tag!("message") >> many0!(br) >> 
     name: word >> many0!(br) >> 
     tag!("{") >> many0!(br) >>
-    fields: many0!(message_field) >> 
+    items: many0!(message_item) >>
     tag!("}") >> many0!(br) >>
-    (Message { name: name, fields: fields })));
+    ({
+        let mut fields = Vec::new();
+        let mut options = Vec::new();
+        let mut oneofs = Vec::new();
+        for item in items {
+            match item {
+                MessageItem::Field(f) => fields.push(*f),
+                MessageItem::Option(o) => options.push(o),
+                MessageItem::Oneof(o) => oneofs.push(o),
+            }
+        }
+        Message { name: name, fields: fields, options, oneofs, attributes: Vec::new(), ord: false, unknown_fields: None,
+                  builder: false, code_size: false, lifetime: false, tuple: false, copy: false }
+    })));
 
 named!(enum_field<(&str, i32)>, do_parse!(
     name: word >> many0!(br) >>
     tag!("=") >> many0!(br) >>
-    number: map_res!(map_res!(digit, str::from_utf8), str::FromStr::from_str) >> many0!(br) >>
-    tag!(";") >> many0!(br) >>
+    number: map_res!(map_res!(recognize!(pair!(opt!(tag!("-")), digit)), str::from_utf8), str::FromStr::from_str) >>
+    many0!(br) >> tag!(";") >> many0!(br) >>
     ((name, number))));
+
+named!(enum_item<EnumItem<'a>>, alt!(
+    message_option => { EnumItem::Option } |
+    enum_field => { EnumItem::Field }));
     
 named!(enumerator<Enumerator>, do_parse!(
     tag!("enum") >> many1!(br) >>
     name: word >> many0!(br) >>
     tag!("{") >> many0!(br) >>
-    fields: many0!(enum_field) >> 
+    items: many0!(enum_item) >> 
     tag!("}") >> many0!(br) >>
-    (Enumerator { name: name, fields: fields })));
+    ({
+        let mut fields = Vec::new();
+        let mut options = Vec::new();
+        for item in items {
+            match item {
+                EnumItem::Field(f) => fields.push(f),
+                EnumItem::Option(o) => options.push(o),
+            }
+        }
+        Enumerator { name: name, fields: fields, options, open: false, repr: None, attributes: Vec::new(),
+                     unknown_attributes: Vec::new() }
+    })));
+
+// `import "path";`, with an optional `public` or `weak` modifier
+named!(import<&'a str>, do_parse!(
+    tag!("import") >> many1!(br) >>
+    opt!(do_parse!(alt!(tag!("public") | tag!("weak")) >> many1!(br) >> ())) >>
+    tag!("\"") >> path: map_res!(take_until_and_consume!("\""), str::from_utf8) >> many0!(br) >>
+    tag!(";") >> many0!(br) >>
+    (path)));
 
 named!(ignore<()>, do_parse!(
     alt!(tag!("package") | tag!("option") | tag!("import")) >> many1!(br) >> 
     take_until_and_consume!(";") >> many0!(br) >> ()));
 
-named!(service_ignore<()>, do_parse!(tag!("service") >> many1!(br) >> word >> many0!(br) >> tag!("{") >>
-                                     take_until_and_consume!("}") >> many0!(br) >> ()));
+named!(package<&str>, do_parse!(
+    tag!("package") >> many1!(br) >>
+    package: map_res!(take_until_and_consume!(";"), str::from_utf8) >> many0!(br) >>
+    (package.trim())));
+
+// rpc input or output type, `(stream Foo)` or `(Foo)`
+named!(rpc_type<(bool, &'a str)>, do_parse!(
+    tag!("(") >> many0!(br) >>
+    stream: opt!(do_parse!(tag!("stream") >> many1!(br) >> ())) >>
+    typ: word >> many0!(br) >> tag!(")") >>
+    ((stream.is_some(), typ))));
+
+named!(rpc<Rpc<'a>>, do_parse!(
+    tag!("rpc") >> many1!(br) >>
+    name: word >> many0!(br) >>
+    input: rpc_type >> many0!(br) >>
+    tag!("returns") >> many0!(br) >>
+    output: rpc_type >> many0!(br) >>
+    alt!(tag!(";") => { |_| () } | do_parse!(tag!("{") >> take_until_and_consume!("}") >> ())) >> many0!(br) >>
+    (Rpc { name, input: input.1, input_stream: input.0, output: output.1, output_stream: output.0 })));
+
+named!(service_option<()>, do_parse!(tag!("option") >> many1!(br) >> take_until_and_consume!(";") >> many0!(br) >> ()));
+
+named!(service_item<Option<Rpc<'a>>>, alt!(rpc => { Some } | service_option => { |_| None }));
+
+named!(service<Service<'a>>, do_parse!(
+    tag!("service") >> many1!(br) >>
+    name: word >> many0!(br) >>
+    tag!("{") >> many0!(br) >>
+    rpcs: many0!(service_item) >>
+    tag!("}") >> many0!(br) >>
+    (Service { name, rpcs: rpcs.into_iter().flatten().collect() })));
 
 named!(message_or_enum<MessageOrEnum>, alt!(
          message => { |m| MessageOrEnum::Msg(m) } | 
          enumerator => { |e| MessageOrEnum::Enum(e) } |
+         package => { MessageOrEnum::Package } |
+         message_option => { MessageOrEnum::Option } |
+         import => { MessageOrEnum::Import } |
          ignore => { |_| MessageOrEnum::Ignore } |
-         service_ignore => { |_| MessageOrEnum::Ignore } ));
+         service => { MessageOrEnum::Service } ));
 
 named!(pub file_descriptor<FileDescriptor>, do_parse!(
     many0!(br) >> syntax: opt!(syntax) >> many0!(br) >>
     message_and_enums: many0!(message_or_enum) >>
     (FileDescriptor {
         syntax: syntax.unwrap_or(Syntax::Proto2),
+        package: None,
+        options: Vec::new(),
         message_and_enums: message_and_enums,
         messages: Vec::new(),
         enums: Vec::new(),
+        services: Vec::new(),
+        imports: Vec::new(),
     })));
 
 #[test]
@@ -129,6 +268,25 @@ fn test_message() {
     }
 }
 
+#[test]
+fn test_service() {
+    let svc = r#"service RouteGuide {
+    option deprecated = false;
+    rpc GetFeature(Point) returns (Feature) {}
+    rpc RouteChat (stream RouteNote) returns (stream RouteNote);
+}"#;
+
+    match service(svc.as_bytes()) {
+        ::nom::IResult::Done(_, svc) => {
+            assert_eq!("RouteGuide", svc.name);
+            assert_eq!(2, svc.rpcs.len());
+            assert!(!svc.rpcs[0].input_stream && !svc.rpcs[0].output_stream);
+            assert_eq!(("RouteNote", true, true), (svc.rpcs[1].input, svc.rpcs[1].input_stream, svc.rpcs[1].output_stream));
+        }
+        r => panic!("cannot parse service: {:?}", r),
+    }
+}
+
 #[test]
 fn test_enum() {
     let msg = r#"enum PairingStatus {
@@ -144,6 +302,43 @@ fn test_enum() {
     }
 }
 
+#[test]
+fn test_enum_options() {
+    let msg = r#"enum Color {
+    option (rust.repr) = "u8";
+    RED = 0;
+    GREEN = 1;
+}"#;
+
+    match enumerator(msg.as_bytes()) {
+        ::nom::IResult::Done(_, e) => {
+            assert_eq!(vec![("(rust.repr)", "\"u8\"")], e.options);
+            assert_eq!(vec![("RED", 0), ("GREEN", 1)], e.fields);
+        }
+        e => panic!("Expecting done {:?}", e),
+    }
+}
+
+#[test]
+fn test_oneof() {
+    let msg = r#"message Payload {
+    optional int32 id = 1;
+    oneof value {
+        string text = 2;
+        bytes data = 3;
+    }
+}"#;
+
+    match message(msg.as_bytes()) {
+        ::nom::IResult::Done(rest, m) => {
+            assert!(rest.is_empty());
+            assert_eq!(1, m.fields.len());
+            assert_eq!(vec!["value"], m.oneofs);
+        }
+        e => panic!("Expecting done {:?}", e),
+    }
+}
+
 #[test]
 fn test_ignore() {
     let msg = r#"package com.test.v0;
@@ -156,3 +351,47 @@ option optimize_for = SPEED;
         e => panic!("Expecting done {:?}", e),
     }
 }
+
+#[test]
+fn test_field_options() {
+    let msg = r#"repeated int32 ids = 2 [packed = true, (rust.container) = "smallvec:4"];"#;
+
+    match message_field(msg.as_bytes()) {
+        ::nom::IResult::Done(_, f) => {
+            assert_eq!(Some(true), f.packed);
+            assert_eq!(vec![("packed", "true"), ("(rust.container)", "\"smallvec:4\"")], f.options);
+        }
+        e => panic!("Expecting done {:?}", e),
+    }
+}
+
+#[test]
+fn test_proto3_field() {
+    let msg = r#"Color color = 3;"#;
+
+    match message_field(msg.as_bytes()) {
+        ::nom::IResult::Done(_, f) => {
+            assert!(matches!(f.frequency, Frequency::Optional));
+            assert_eq!("Color", f.typ);
+            assert_eq!(3, f.number);
+        }
+        e => panic!("Expecting done {:?}", e),
+    }
+}
+
+#[test]
+fn test_message_options() {
+    let msg = r##"message Point {
+    option (rust.attr) = "#[derive(Hash)]";
+    int32 x = 1 [(rust.attr) = "#[serde(skip)]"];
+}"##;
+
+    match message(msg.as_bytes()) {
+        ::nom::IResult::Done(_, m) => {
+            assert_eq!(vec![("(rust.attr)", "\"#[derive(Hash)]\"")], m.options);
+            assert_eq!(1, m.fields.len());
+        }
+        e => panic!("Expecting done {:?}", e),
+    }
+}
+
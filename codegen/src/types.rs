@@ -1,8 +1,13 @@
+use std::io::Error;
 use std::io::Result as IoResult;
 use std::io::Write;
+use std::str::FromStr;
+
+use nom::IResult;
 
-use nom::IError;
 use parser::file_descriptor;
+use config::Config;
+use descriptor::encode_file_descriptor_set;
 
 fn sizeof_varint(v: u32) -> usize {
     match v {
@@ -27,16 +32,163 @@ pub enum Frequency {
     Required,
 }
 
+/// The rust container used for a `repeated` field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Container {
+    /// `Vec<T>`
+    Vec,
+    /// `Box<[T]>`, immutable after parsing
+    BoxSlice,
+    /// `SmallVec<[T; N]>`, requires the `smallvec` crate
+    SmallVec(usize),
+    /// `PooledVec<T>` of messages, keeping its messages once cleared to read into them again
+    Pool,
+}
+
+impl Container {
+    fn rust_type(&self, typ: &str) -> String {
+        match *self {
+            Container::Vec => format!("Vec<{}>", typ),
+            Container::BoxSlice => format!("Box<[{}]>", typ),
+            Container::SmallVec(n) => format!("SmallVec<[{}; {}]>", typ, n),
+            Container::Pool => format!("PooledVec<{}>", typ),
+        }
+    }
+
+    /// Type of the owning iterator over the container, and the expression creating it from `v`
+    fn owned_iter(&self, typ: &str, v: &str) -> (String, String) {
+        match *self {
+            Container::Vec => (format!("::std::vec::IntoIter<{}>", typ), format!("{}.into_iter()", v)),
+            Container::BoxSlice => (format!("::std::vec::IntoIter<{}>", typ), format!("{}.into_vec().into_iter()", v)),
+            Container::SmallVec(n) => (format!("::smallvec::IntoIter<[{}; {}]>", typ, n), format!("{}.into_iter()", v)),
+            Container::Pool => (format!("::std::vec::IntoIter<{}>", typ), format!("{}.into_iter()", v)),
+        }
+    }
+}
+
+impl FromStr for Container {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Container, String> {
+        match s {
+            "vec" => Ok(Container::Vec),
+            "box" => Ok(Container::BoxSlice),
+            "pool" => Ok(Container::Pool),
+            s if s.starts_with("smallvec:") => s[9..].parse()
+                .map(Container::SmallVec)
+                .map_err(|_| format!("Invalid smallvec size in '{}'", s)),
+            s => Err(format!("Unknown container '{}', expecting vec, box, smallvec:N or pool", s)),
+        }
+    }
+}
+
+/// A constraint checked by the generated `validate_into`, set with a custom field option
+///
+/// Each variant is a `quick_protobuf::validate::Constraint`, numeric bounds are kept as written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Constraint<'a> {
+    /// `(rust.required) = true`: an optional field must be set
+    Required,
+    /// `(rust.non_empty) = true`: a repeated, string or bytes field must not be empty
+    NonEmpty,
+    /// `(rust.max_len) = N`: at most N chars in a string, N bytes in bytes
+    MaxLen(usize),
+    /// `(rust.min) = N`: a number must be at least N
+    Min(&'a str),
+    /// `(rust.max) = N`: a number must be at most N
+    Max(&'a str),
+}
+
+/// Rust keywords, which must be escaped when used as identifiers
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop", "macro",
+    "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "self", "Self", "static",
+    "struct", "super", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield",
+];
+
+/// Converts a (camelCase or snake_case) proto name into a snake_case rust identifier, like prost
+fn snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 && chars[i - 1] != '_' {
+            let prev_lower = chars[i - 1].is_lowercase() || chars[i - 1].is_numeric();
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev_lower || (chars[i - 1].is_uppercase() && next_lower) {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+/// Fails on the input left unparsed, instead of silently dropping the items it contains
+fn check_fully_parsed(b: &[u8], rest: &[u8]) -> Result<(), String> {
+    let rest = String::from_utf8_lossy(rest);
+    let trimmed = rest.trim();
+    // a trailing line comment without a final newline
+    if trimmed.is_empty() || (trimmed.starts_with("//") && !trimmed.contains('\n')) {
+        return Ok(());
+    }
+    let line = b[..b.len() - rest.len()].iter().filter(|&&c| c == b'\n').count() + 1;
+    Err(format!("line {}: cannot parse '{}'", line, trimmed.lines().next().unwrap_or("")))
+}
+
+/// Extra attributes of an item: `(rust.attr)` options then command line ones
+fn attributes(options: &[(&str, &str)], config: &Config, item: &str) -> Vec<String> {
+    options.iter()
+        .filter(|o| o.0 == "(rust.attr)")
+        .map(|o| o.1.trim_matches('"'))
+        .chain(config.attributes(item))
+        .map(|a| a.to_string())
+        .collect()
+}
+
 #[derive(Debug)]
 pub struct Field<'a> {
     pub name: &'a str,
+    /// Identifier of the generated struct field
+    pub rust_name: String,
     pub frequency: Frequency,
+    /// Written with a label: a proto3 field without one has no presence
+    pub labeled: bool,
     pub typ: &'a str,
     pub number: i32,
     pub default: Option<&'a str>,
     pub packed: Option<bool>,
+    /// The message is boxed, to break a recursion cycle or with the `(rust.box_it)` option
     pub boxed: bool,
     pub deprecated: bool,
+    pub container: Container,
+    pub options: Vec<(&'a str, &'a str)>,
+    /// Extra attributes emitted on the struct field
+    pub attributes: Vec<String>,
+    /// User type of the struct field, implementing `ProtoConvert` from the field rust type
+    pub user_type: Option<String>,
+    /// Cargo feature the field is compiled with, `(rust.feature)` option
+    pub feature: Option<&'a str>,
+    /// The message is a collection of the items of this repeated field, `(rust.collection)` option
+    pub collection: bool,
+    /// `string` or `bytes` field borrowed from the input as a `Cow`, `(rust.borrow)` option
+    pub borrow: bool,
+    /// `string` field shared with the equal strings read before as an `Arc<str>`, `(rust.intern)` option
+    pub intern: bool,
+    /// Message field parsed on first access, as a `LazyMessage`, `(rust.lazy)` option
+    pub lazy: bool,
+    /// The type of the field has a lifetime: it is borrowed or a message with a lifetime
+    pub lifetime: bool,
+    /// The message of the field has required fields, or fields of such messages: the path of its
+    /// missing required fields is prefixed with the field name
+    pub nested_required: bool,
+    /// Constraints checked by `validate_into`, `(rust.required)`, `(rust.min)` ... options
+    pub constraints: Vec<Constraint<'a>>,
+    /// Rust path of the message or enum of an imported file the field is of, `--import` option
+    pub extern_type: Option<String>,
+    /// The imported type of the field is an enum
+    pub extern_enum: bool,
 }
 
 impl<'a> Field<'a> {
@@ -44,6 +196,315 @@ impl<'a> Field<'a> {
         self.packed.unwrap_or(false)
     }
 
+    /// Gets a field option value, without the quotes if any
+    fn option(&self, key: &str) -> Option<&'a str> {
+        self.options.iter().find(|o| o.0 == key).map(|o| o.1.trim_matches('"'))
+    }
+
+    /// Local accumulator used while parsing a repeated field which cannot be pushed into
+    fn needs_buffer(&self) -> bool {
+        matches!(self.frequency, Frequency::Repeated) && self.container == Container::BoxSlice
+    }
+
+    /// Repeated field whose items are (at least partially) stored inline
+    fn is_inline_container(&self) -> bool {
+        matches!(self.container, Container::SmallVec(_))
+    }
+
+    fn set_config(&mut self, config: &Config, message: &str) -> Result<(), String> {
+        let item = format!("{}.{}", message, self.name);
+        self.attributes = attributes(&self.options, config, &item);
+        self.rust_name = match self.option("(rust.name)").or_else(|| config.rename(&item)) {
+            Some(name) if name.starts_with(|c: char| c.is_ascii_digit())
+                || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+                return Err(format!("field '{}': '{}' is not a valid rust identifier", self.name, name));
+            }
+            Some(name) => name.to_string(),
+            None if config.prost => snake_case(self.name),
+            None => self.name.to_string(),
+        };
+        if KEYWORDS.contains(&&*self.rust_name) {
+            self.rust_name = match &*self.rust_name {
+                "self" | "super" | "crate" | "Self" => format!("{}_", self.rust_name),
+                n => format!("r#{}", n),
+            };
+        }
+        if config.serde {
+            let json_name = self.json_name();
+            if json_name != self.rust_name.trim_start_matches("r#") {
+                self.attributes.insert(0, format!("#[serde(rename = \"{}\")]", json_name));
+            }
+        }
+        self.user_type = self.option("(rust.type)").or_else(|| config.user_type(&item)).map(|t| t.to_string());
+        if let Some(ref t) = self.user_type {
+            if !self.is_numeric() && !matches!(self.typ, "bool" | "string" | "bytes") {
+                return Err(format!("field '{}': type {} cannot be converted to {}, only scalar, string \
+                                    and bytes fields can", self.name, self.typ, t));
+            }
+            if self.default.is_some() {
+                return Err(format!("field '{}': cannot have both a default and a {} type", self.name, t));
+            }
+        }
+        if let Frequency::Repeated = self.frequency {
+            self.container = match self.option("(rust.container)") {
+                Some(c) => c.parse().map_err(|e| format!("field '{}': {}", self.name, e))?,
+                None => config.container.unwrap_or(Container::Vec),
+            };
+        }
+        self.collection = self.option("(rust.collection)") == Some("true");
+        if self.collection && !matches!(self.frequency, Frequency::Repeated) {
+            return Err(format!("field '{}': only repeated fields can be a collection", self.name));
+        }
+        self.borrow = self.option("(rust.borrow)") == Some("true");
+        if self.borrow {
+            if !matches!(self.typ, "string" | "bytes") || self.user_type.is_some() {
+                return Err(format!("field '{}': only string and bytes fields can be borrowed", self.name));
+            }
+            if config.prost {
+                return Err(format!("field '{}': borrowed fields cannot be used with --prost", self.name));
+            }
+        }
+        self.intern = self.option("(rust.intern)") == Some("true");
+        if self.intern {
+            if self.typ != "string" || self.user_type.is_some() || self.borrow {
+                return Err(format!("field '{}': only string fields without user type or borrow can be interned",
+                                   self.name));
+            }
+            if self.default.is_some() {
+                return Err(format!("field '{}': interned fields cannot have a default", self.name));
+            }
+        }
+        self.lazy = self.option("(rust.lazy)") == Some("true");
+        if self.lazy && (matches!(self.frequency, Frequency::Repeated) || self.user_type.is_some()) {
+            return Err(format!("field '{}': only singular message fields can be lazy", self.name));
+        }
+        self.boxed = self.option("(rust.box_it)") == Some("true");
+        if self.boxed && (!matches!(self.frequency, Frequency::Optional) || self.user_type.is_some() || self.lazy) {
+            return Err(format!("field '{}': only optional message fields, not lazy, can be boxed", self.name));
+        }
+        self.feature = self.option("(rust.feature)");
+        if let Some(feature) = self.feature {
+            if let Frequency::Required = self.frequency {
+                return Err(format!("field '{}': required fields cannot be feature gated", self.name));
+            }
+            self.attributes.insert(0, format!("#[cfg(feature = \"{}\")]", feature));
+        }
+        Ok(())
+    }
+
+    /// The struct field is an `Option`
+    fn is_option(&self) -> bool {
+        matches!(self.frequency, Frequency::Optional) && (self.boxed || self.default.is_none())
+    }
+
+    /// Reads the constraints of the field from its options, once its defaults are known
+    fn set_constraints(&mut self) -> Result<(), String> {
+        let string_or_bytes = matches!(self.typ, "string" | "bytes") && self.user_type.is_none();
+        let mut constraints = Vec::new();
+        for &(key, value) in &self.options {
+            let value = value.trim_matches('"');
+            let constraint = match key {
+                "(rust.required)" if value == "true" => {
+                    if !self.is_option() {
+                        return Err(format!("field '{}': only optional fields without default can be required",
+                                           self.name));
+                    }
+                    Constraint::Required
+                }
+                "(rust.non_empty)" if value == "true" => {
+                    if !matches!(self.frequency, Frequency::Repeated) && !string_or_bytes {
+                        return Err(format!("field '{}': only repeated, string and bytes fields can be non empty",
+                                           self.name));
+                    }
+                    Constraint::NonEmpty
+                }
+                "(rust.max_len)" => {
+                    if !string_or_bytes {
+                        return Err(format!("field '{}': only string and bytes fields can have a max length",
+                                           self.name));
+                    }
+                    Constraint::MaxLen(value.parse()
+                        .map_err(|_| format!("field '{}': invalid max length '{}'", self.name, value))?)
+                }
+                "(rust.min)" | "(rust.max)" => {
+                    if !self.is_numeric() || self.user_type.is_some() {
+                        return Err(format!("field '{}': only numeric fields can have a min or a max", self.name));
+                    }
+                    if !self.is_valid_number(value) {
+                        return Err(format!("field '{}': '{}' is not a valid {}", self.name, value, self.typ));
+                    }
+                    if key == "(rust.min)" { Constraint::Min(value) } else { Constraint::Max(value) }
+                }
+                _ => continue,
+            };
+            constraints.push(constraint);
+        }
+        self.constraints = constraints;
+        Ok(())
+    }
+
+    /// Checks that `v` is a finite number of the rust type of the field
+    fn is_valid_number(&self, v: &str) -> bool {
+        match self.rust_type() {
+            "i32" => v.parse::<i32>().is_ok(),
+            "i64" => v.parse::<i64>().is_ok(),
+            "u32" => v.parse::<u32>().is_ok(),
+            "u64" => v.parse::<u64>().is_ok(),
+            "f32" => v.parse::<f32>().is_ok_and(|f| f.is_finite()),
+            _ => v.parse::<f64>().is_ok_and(|f| f.is_finite()),
+        }
+    }
+
+    /// Literal of a numeric bound, typed like the field
+    fn bound_literal(&self, v: &str) -> String {
+        match self.rust_type() {
+            "f32" => format!("{:?}f32", v.parse::<f32>().unwrap()),
+            "f64" => format!("{:?}f64", v.parse::<f64>().unwrap()),
+            t => format!("{}{}", v.trim_start_matches('+'), t),
+        }
+    }
+
+    /// Expression of a `quick_protobuf::validate::Constraint`
+    fn constraint_value(&self, c: &Constraint) -> String {
+        let v = match *c {
+            Constraint::Required => "Required".to_string(),
+            Constraint::NonEmpty => "NonEmpty".to_string(),
+            Constraint::MaxLen(n) => format!("MaxLen({})", n),
+            Constraint::Min(b) => format!("Min({:?})", b.parse::<f64>().unwrap()),
+            Constraint::Max(b) => format!("Max({:?})", b.parse::<f64>().unwrap()),
+        };
+        format!("::quick_protobuf::validate::Constraint::{}", v)
+    }
+
+    /// Condition violating a constraint on a value, `v` being a reference to it or the field itself
+    fn violates(&self, c: &Constraint, v: &str) -> String {
+        let deref = if v == "v" { "*" } else { "" };
+        match *c {
+            Constraint::Required => unreachable!("required is checked on the field"),
+            Constraint::NonEmpty => format!("{}.is_empty()", v),
+            Constraint::MaxLen(n) if self.typ == "string" => format!("{}.chars().count() > {}", v, n),
+            Constraint::MaxLen(n) => format!("{}.len() > {}", v, n),
+            Constraint::Min(b) => format!("{}{} < {}", deref, v, self.bound_literal(b)),
+            Constraint::Max(b) => format!("{}{} > {}", deref, v, self.bound_literal(b)),
+        }
+    }
+
+    /// Writes the checks of the constraints of the field in `validate_into`, then the validation
+    /// of its messages, with their path
+    fn write_validate<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        let (n, name) = (&self.rust_name, self.name);
+        let error = |field: &str, c: &Constraint| {
+            format!("errors.push(ValidationError::new(path, {}, {}));", field, self.constraint_value(c))
+        };
+        let quoted = format!("\"{}\"", name);
+        // checks of the whole field, then of each value
+        let repeated = matches!(self.frequency, Frequency::Repeated);
+        let (field_checks, value_checks): (Vec<&Constraint>, Vec<_>) = self.constraints.iter()
+            .partition(|c| **c == Constraint::Required || (**c == Constraint::NonEmpty && repeated));
+        for c in field_checks {
+            let check = if *c == Constraint::Required { "is_none" } else { "is_empty" };
+            writeln!(w, "        if self.{}.{}() {{ {} }}", n, check, error(&quoted, c))?;
+        }
+        if !value_checks.is_empty() {
+            let (v, field, indent) = match self.frequency {
+                Frequency::Repeated => {
+                    writeln!(w, "        for (i, v) in self.{}.iter().enumerate() {{", n)?;
+                    ("v".to_string(), format!("&format!(\"{}[{{}}]\", i)", name), "    ")
+                }
+                _ if self.is_option() => {
+                    writeln!(w, "        if let Some(ref v) = self.{} {{", n)?;
+                    ("v".to_string(), quoted.clone(), "    ")
+                }
+                _ => (format!("self.{}", n), quoted.clone(), ""),
+            };
+            for c in value_checks {
+                writeln!(w, "        {}if {} {{ {} }}", indent, self.violates(c, &v), error(&field, c))?;
+            }
+            if !indent.is_empty() {
+                writeln!(w, "        }}")?;
+            }
+        }
+        if self.read_fn(enums) != "message" {
+            return Ok(());
+        }
+        match self.frequency {
+            Frequency::Repeated => {
+                writeln!(w, "        for (i, m) in self.{}.iter().enumerate() {{", n)?;
+                writeln!(w, "            m.validate_into(&format!(\"{{}}{}[{{}}].\", path, i), errors);", name)?;
+                writeln!(w, "        }}")
+            }
+            _ if self.is_option() => {
+                writeln!(w, "        if let Some(ref m) = self.{} {{", n)?;
+                writeln!(w, "            m.validate_into(&format!(\"{{}}{}.\", path), errors);", name)?;
+                writeln!(w, "        }}")
+            }
+            _ => writeln!(w, "        self.{}.validate_into(&format!(\"{{}}{}.\", path), errors);", n, name),
+        }
+    }
+
+    /// Writes the `#[cfg]` attribute of a feature gated field, before an item, a `let` or a match arm
+    fn write_cfg<W: Write>(&self, w: &mut W, indent: &str) -> IoResult<()> {
+        match self.feature {
+            Some(feature) => writeln!(w, "{}#[cfg(feature = \"{}\")]", indent, feature),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the statements written by `f`, in a `#[cfg]` block if the field is feature gated
+    fn write_gated<W, F>(&self, w: &mut W, f: F) -> IoResult<()>
+        where W: Write, F: FnOnce(&mut Vec<u8>) -> IoResult<()>
+    {
+        let mut buf = Vec::new();
+        f(&mut buf)?;
+        if self.feature.is_none() {
+            return w.write_all(&buf);
+        }
+        self.write_cfg(w, "        ")?;
+        writeln!(w, "        {{")?;
+        for line in String::from_utf8_lossy(&buf).lines() {
+            writeln!(w, "    {}", line)?;
+        }
+        writeln!(w, "        }}")
+    }
+
+    /// Name in the JSON mapping: the `json_name` option or the lowerCamelCase field name
+    fn json_name(&self) -> String {
+        if let Some(n) = self.option("json_name") {
+            return n.to_string();
+        }
+        let mut name = String::with_capacity(self.name.len());
+        let mut upper = false;
+        for c in self.name.chars() {
+            match c {
+                '_' => upper = true,
+                c if upper => {
+                    name.extend(c.to_uppercase());
+                    upper = false;
+                }
+                c => name.push(c),
+            }
+        }
+        name
+    }
+
+    /// Predicate skipping the field when serializing, where the JSON mapping omits it:
+    /// unset optional fields, empty repeated fields and proto3 scalars equal to their default
+    ///
+    /// Proto2 fields with a default have no presence and are always serialized. Proto3 `string`,
+    /// `bytes` and enum fields without a label are `Option`s but have no presence either, so they
+    /// are skipped when set to their default too
+    fn serde_skip(&self, proto3: bool, enums: &[&str]) -> Option<&'static str> {
+        match self.frequency {
+            Frequency::Required => None,
+            Frequency::Repeated => Some("is_default"),
+            Frequency::Optional if proto3 && !self.labeled && self.default.is_none() && self.user_type.is_none()
+                && self.read_fn(enums) != "message" => Some("is_none_or_default"),
+            Frequency::Optional if self.boxed || self.default.is_none() => Some("Option::is_none"),
+            Frequency::Optional if proto3 => Some("is_default"),
+            Frequency::Optional => None,
+        }
+    }
+
     fn is_numeric(&self) -> bool {
         match self.typ {
             "int32" | "sint32" | "sfixed32" |
@@ -65,7 +526,20 @@ impl<'a> Field<'a> {
             "double" => "f64",
             "string" => "String",
             "bytes" => "Vec<u8>",
-            t => t,
+            t => self.extern_type.as_deref().unwrap_or(t),
+        }
+    }
+
+    /// Type of the struct field (or of its items if repeated)
+    fn field_type(&self) -> String {
+        match self.user_type {
+            Some(ref t) => t.clone(),
+            None if self.borrow && self.typ == "string" => "Cow<'a, str>".to_string(),
+            None if self.borrow => "Cow<'a, [u8]>".to_string(),
+            None if self.intern => "Arc<str>".to_string(),
+            None if self.lazy => format!("LazyMessage<{}>", self.rust_type()),
+            None if self.lifetime => format!("{}<'a>", self.typ),
+            None => self.rust_type().to_string(),
         }
     }
 
@@ -83,146 +557,389 @@ impl<'a> Field<'a> {
                 "uint32" | "uint64" | "bool" | "enum" => 0,
             "fixed64" | "sfixed64" | "double" => 1,
             "fixed32" | "sfixed32" | "float" => 5,
-            t if enums.contains(&t) => 0,
+            t if enums.contains(&t) || self.extern_enum => 0,
             _ => 2,
         }
     }
 
+    /// Encoded length of fixed size types
+    fn fixed_len(&self) -> Option<usize> {
+        match self.typ {
+            "fixed64" | "sfixed64" | "double" => Some(8),
+            "fixed32" | "sfixed32" | "float" => Some(4),
+            _ => None,
+        }
+    }
+
     fn read_fn(&self, enums: &[&str]) -> &str {
         match self.typ {
             "int32" | "sint32" | "int64" | "sint64" | 
                 "uint32" | "uint64" | "bool" | "fixed64" | 
                 "sfixed64" | "double" | "fixed32" | "sfixed32" | 
                 "float" | "string" | "bytes" => self.typ,
-            t if enums.contains(&t) => "enum",
+            t if enums.contains(&t) || self.extern_enum => "enum",
             _ => "message",
         }
     }
 
+    /// Expression of the value as expected by `Writer` and `sizeof_*` functions
+    fn wire_value(&self, enums: &[&str], v: &str) -> String {
+        if self.read_fn(enums) == "enum" {
+            format!("i32::from({})", v)
+        } else if self.user_type.is_some() {
+            match self.typ {
+                "string" | "bytes" => format!("&({}).to_proto()", v),
+                _ => format!("({}).to_proto()", v),
+            }
+        } else {
+            v.to_string()
+        }
+    }
+
+    /// Expression of a `string` or `bytes` value, converted from its user type if any
+    fn proto_value(&self, v: &str) -> String {
+        match self.user_type {
+            Some(_) => format!("{}.to_proto()", v),
+            None => v.to_string(),
+        }
+    }
+
+    /// Expression converting `other` field of a compatible message, read from `m`
+    ///
+    /// `None` if the fields do not line up (different type or representation)
+    fn convert_from(&self, other: &Field, enums: &[&str], other_enums: &[&str]) -> Option<String> {
+        let read_fn = self.read_fn(enums);
+        if self.typ != other.typ || read_fn != other.read_fn(other_enums) || self.user_type != other.user_type
+            || self.intern != other.intern || self.lazy || other.lazy
+            || self.default.is_none() != other.default.is_none()
+            || !matches!((&self.frequency, &other.frequency),
+                         (&Frequency::Optional, &Frequency::Optional) |
+                         (&Frequency::Repeated, &Frequency::Repeated) |
+                         (&Frequency::Required, &Frequency::Required)) {
+            return None;
+        }
+        let value = match (read_fn, other.boxed) {
+            ("enum", _) => "i32::from(v).into()",
+            ("message", true) => "(*v).into()",
+            ("message", false) => "v.into()",
+            _ => "v",
+        };
+        let value = if self.boxed { format!("Box::new({})", value) } else { value.to_string() };
+        let src = format!("m.{}", other.rust_name);
+        Some(match self.frequency {
+            Frequency::Repeated => {
+                if value == "v" && self.container == other.container {
+                    src
+                } else {
+                    let iter = if other.container == Container::BoxSlice { ".into_vec().into_iter()" } else { ".into_iter()" };
+                    let map = if value == "v" { String::new() } else { format!(".map(|v| {})", value) };
+                    format!("{}{}{}.collect()", src, iter, map)
+                }
+            }
+            _ if value == "v" => src,
+            Frequency::Optional if self.default.is_none() => format!("{}.map(|v| {})", src, value),
+            _ => format!("{{ let v = {}; {} }}", src, value),
+        })
+    }
+
+    /// Expression of the default value of the field
+    fn default_value(&self, enums: &[&str]) -> String {
+        match self.default_literal(enums) {
+            None => "Default::default()".to_string(),
+            Some(d) => match self.rust_type() {
+                _ if self.borrow => format!("Cow::Borrowed({})", d),
+                "String" => format!("{}.to_string()", d),
+                "Vec<u8>" => format!("{}.to_vec()", d),
+                _ => d,
+            },
+        }
+    }
+
+    /// Literal of the `[default = ...]` value, which the field can be compared with
+    fn default_literal(&self, enums: &[&str]) -> Option<String> {
+        self.default.map(|d| match self.rust_type() {
+            "Vec<u8>" if self.borrow => format!("&b{}[..]", d),
+            "Vec<u8>" => format!("b{}", d),
+            "f32" | "f64" => match d {
+                "inf" => format!("::std::{}::INFINITY", self.rust_type()),
+                "-inf" => format!("::std::{}::NEG_INFINITY", self.rust_type()),
+                "nan" => format!("::std::{}::NAN", self.rust_type()),
+                d => format!("{}{}", d, self.rust_type()),
+            },
+            t if enums.contains(&self.typ) || self.extern_enum => format!("{}::{}", t, d),
+            _ => d.to_string(),
+        })
+    }
+
     fn tag(&self, enums: &[&str]) -> u32 {
         (self.number as u32) << 3 | self.wire_type_num(enums)
     }
 
     fn write_definition<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        for a in &self.attributes {
+            writeln!(w, "    {}", a)?;
+        }
         match self.frequency {
             Frequency::Optional => {
                 if self.boxed {
-                    writeln!(w, "    pub {}: Option<Box<{}>>,", self.name, self.rust_type())
+                    writeln!(w, "    pub {}: Option<Box<{}>>,", self.rust_name, self.field_type())
                 } else {
                     if self.default.is_none() {
-                        writeln!(w, "    pub {}: Option<{}>,", self.name, self.rust_type())
+                        writeln!(w, "    pub {}: Option<{}>,", self.rust_name, self.field_type())
                     } else {
-                        writeln!(w, "    pub {}: {},", self.name, self.rust_type())
+                        writeln!(w, "    pub {}: {},", self.rust_name, self.field_type())
                     }
                 }
             }
-            Frequency::Repeated => writeln!(w, "    pub {}: Vec<{}>,", self.name, self.rust_type()),
-            Frequency::Required => writeln!(w, "    pub {}: {},", self.name, self.rust_type()),
+            Frequency::Repeated => writeln!(w, "    pub {}: {},", self.rust_name, self.container.rust_type(&self.field_type())),
+            Frequency::Required => writeln!(w, "    pub {}: {},", self.rust_name, self.field_type()),
+        }
+    }
+
+    /// Expression reading the value of the field from `r`, converted to its user type if any
+    fn read_value(&self, enums: &[&str]) -> String {
+        match self.user_type {
+            Some(_) => format!("ProtoConvert::from_proto(r.read_{}()?)?", self.read_fn(enums)),
+            None if self.borrow => format!("Cow::Borrowed(r.read_{}_borrowed()?)", self.read_fn(enums)),
+            None if self.intern => "r.read_string_interned()?".to_string(),
+            None if self.lifetime => "r.read_message_borrowed()?".to_string(),
+            None => format!("r.read_{}()?", self.read_fn(enums)),
         }
     }
 
     fn write_match_tag<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        let value = self.read_value(enums);
+        if self.read_fn(enums) == "message" && !matches!(self.frequency, Frequency::Repeated) {
+            // occurrences of a singular message field are merged
+            let target = match (&self.frequency, self.boxed) {
+                (&Frequency::Optional, true) => format!("&mut **msg.{}.get_or_insert_with(Default::default)", self.rust_name),
+                (&Frequency::Optional, false) => format!("msg.{}.get_or_insert_with(Default::default)", self.rust_name),
+                (_, true) => format!("&mut *msg.{}", self.rust_name),
+                (_, false) => format!("&mut msg.{}", self.rust_name),
+            };
+            let merge = if self.lifetime { "merge_message_borrowed" } else { "merge_message" };
+            return writeln!(w, "Ok({}) => r.{}({}){}?,", self.tag(enums), merge, target, self.in_field(None));
+        }
         match self.frequency {
             Frequency::Optional => {
                 if self.boxed {
-                    writeln!(w, "Ok({}) => msg.{} = Some(Box::new(r.read_{}()?)),",
-                             self.tag(enums), self.name, self.read_fn(enums))
+                    writeln!(w, "Ok({}) => msg.{} = Some(Box::new({})),", self.tag(enums), self.rust_name, value)
                 } else {
                     if self.default.is_none() {
-                        writeln!(w, "Ok({}) => msg.{} = Some(r.read_{}()?),",
-                                 self.tag(enums), self.name, self.read_fn(enums))
+                        writeln!(w, "Ok({}) => msg.{} = Some({}),", self.tag(enums), self.rust_name, value)
                     } else {
-                        writeln!(w, "Ok({}) => msg.{} = r.read_{}()?,",
-                                 self.tag(enums), self.name, self.read_fn(enums))
+                        writeln!(w, "Ok({}) => msg.{} = {},", self.tag(enums), self.rust_name, value)
                     }
                 }
             }
             Frequency::Repeated => {
                 if self.packed() {
-                    writeln!(w, "Ok({}) => msg.{} = r.read_packed_repeated_field(|r| r.read_{}())?,",
-                             self.tag(enums), self.name, self.read_fn(enums))
+                    self.write_match_packed(w, enums)
+                } else if self.read_fn(enums) == "message" && !self.is_inline_container() && !self.lifetime {
+                    let read = if self.container == Container::Pool { "read_message_to_pooled_vec" } else { "read_message_to_vec" };
+                    if self.nested_required {
+                        let target = self.repeated_target();
+                        writeln!(w, "Ok({}) => {{ let i = {}.len(); r.{}(&mut {}){}?; }}",
+                                 self.tag(enums), target, read, target, self.in_field(Some("i")))
+                    } else {
+                        writeln!(w, "Ok({}) => r.{}(&mut {})?,", self.tag(enums), read, self.repeated_target())
+                    }
                 } else {
-                    writeln!(w, "Ok({}) => msg.{}.push(r.read_{}()?),",
-                             self.tag(enums), self.name, self.read_fn(enums))
+                    self.write_match_unpacked(w, enums)
                 }
             }
             Frequency::Required => {
                 if self.boxed {
-                    writeln!(w, "Ok({}) => msg.{} = Box::new(r.read_{}()?),",
-                             self.tag(enums), self.name, self.read_fn(enums))
+                    writeln!(w, "Ok({}) => msg.{} = Box::new({}),", self.tag(enums), self.rust_name, value)
                 } else {
-                    writeln!(w, "Ok({}) => msg.{} = r.read_{}()?,",
-                             self.tag(enums), self.name, self.read_fn(enums))
+                    writeln!(w, "Ok({}) => msg.{} = {},", self.tag(enums), self.rust_name, value)
                 }
             }
         }
     }
 
+    /// Prefixes the path of the missing required fields of a nested message with the field name,
+    /// and the `index` of the item for repeated fields
+    fn in_field(&self, index: Option<&str>) -> String {
+        match index {
+            _ if !self.nested_required => String::new(),
+            Some(i) => format!(".map_err(|e| e.in_field(&format!(\"{}[{{}}]\", {})))", self.name, i),
+            None => format!(".map_err(|e| e.in_field(\"{}\"))", self.name),
+        }
+    }
+
+    /// Accumulator of the values of a repeated field while reading
+    fn repeated_target(&self) -> String {
+        if self.needs_buffer() {
+            format!("{}_buf", self.rust_name)
+        } else {
+            format!("msg.{}", self.rust_name)
+        }
+    }
+
+    /// A repeated scalar or enum field can be written packed or not, whatever its `packed` option
+    fn is_packable(&self, enums: &[&str]) -> bool {
+        matches!(self.frequency, Frequency::Repeated) && self.wire_type_num_non_packed(enums) != 2
+    }
+
+    /// Writes the match arm appending the values of a packed occurrence of a repeated field
+    fn write_match_packed<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        let tag = (self.number as u32) << 3 | 2;
+        let target = self.repeated_target();
+        let convert = if self.user_type.is_some() { ".and_then(ProtoConvert::from_proto)" } else { "" };
+        match self.fixed_len() {
+            // values are copied in bulk, unless they are converted one by one
+            Some(_) if self.user_type.is_none() => writeln!(w, "Ok({}) => {}.extend(r.read_packed_fixed::<{}>()?),",
+                                                             tag, target, self.rust_type()),
+            Some(len) => writeln!(w, "Ok({}) => {}.extend(r.read_packed_fixed_size::<{}, _>({}, |r| r.read_{}(){})?),",
+                                  tag, target, self.field_type(), len, self.read_fn(enums), convert),
+            None => writeln!(w, "Ok({}) => {}.extend(r.read_packed_repeated_field::<{}, _>(|r| r.read_{}(){})?),",
+                             tag, target, self.field_type(), self.read_fn(enums), convert),
+        }
+    }
+
+    /// Writes the match arm appending the value of an unpacked occurrence of a repeated field
+    fn write_match_unpacked<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        let tag = (self.number as u32) << 3 | self.wire_type_num_non_packed(enums);
+        if self.nested_required {
+            let read = if self.lifetime { "read_message_borrowed" } else { "read_message" };
+            return writeln!(w, "Ok({0}) => {{ let i = {1}.len(); r.check_repeated_len(i)?; {1}.push(r.{2}(){3}?); }}",
+                            tag, self.repeated_target(), read, self.in_field(Some("i")));
+        }
+        writeln!(w, "Ok({0}) => {{ r.check_repeated_len({1}.len())?; {1}.push({2}); }}",
+                 tag, self.repeated_target(), self.read_value(enums))
+    }
+
+    /// Statement checking a value read by `r` without building it, for `validate_fields`
+    ///
+    /// Values of `closed_enums` must be defined, nested messages are checked recursively, except
+    /// imported ones which must only fit in their length, and user types are not converted
+    fn validate_value(&self, enums: &[&str], closed_enums: &[&str]) -> String {
+        match self.read_fn(enums) {
+            "message" if self.extern_type.is_some() => "r.read_bytes_borrowed()?;".to_string(),
+            "message" => format!("r.validate_message({}::validate_fields)?;", self.rust_type()),
+            "string" => "r.read_string_borrowed()?;".to_string(),
+            "bytes" => "r.read_bytes_borrowed()?;".to_string(),
+            "enum" if closed_enums.contains(&self.typ) => {
+                format!("let v = r.read_int32()?; if {}::from_i32(v).is_none() {{ \
+                         return Err(::quick_protobuf::errors::ErrorKind::UnknownEnumValue({}, v).into()); }}",
+                        self.rust_type(), self.number)
+            }
+            "enum" => "r.read_int32()?;".to_string(),
+            read_fn => format!("r.read_{}()?;", read_fn),
+        }
+    }
+
+    /// Writes the match arms of `validate_fields` checking the occurrences of the field, with the
+    /// tags accepted by `merge_from`
+    fn write_validate_arms<W: Write>(&self, w: &mut W, enums: &[&str], closed_enums: &[&str]) -> IoResult<()> {
+        let value = self.validate_value(enums, closed_enums);
+        let packed = format!("{} => r.read_packed_for_each(|r| {{ {} Ok(()) }})?,", (self.number as u32) << 3 | 2, value);
+        let unpacked = format!("{} => {{ {} }}", (self.number as u32) << 3 | self.wire_type_num_non_packed(enums), value);
+        let mut arms = vec![if self.packed() { &packed } else { &unpacked }];
+        if self.is_packable(enums) {
+            arms.push(if self.packed() { &unpacked } else { &packed });
+        }
+        for arm in arms {
+            self.write_cfg(w, "                ")?;
+            writeln!(w, "                {}", arm)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the match arm of the encoding of a packable field other than the one it is written
+    /// with: parsers must accept both, concatenating the values of all the occurrences
+    fn write_match_other_encoding<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        if self.packed() {
+            self.write_match_unpacked(w, enums)
+        } else {
+            self.write_match_packed(w, enums)
+        }
+    }
+
     /// searches if the message must be boxed
     fn is_leaf(&self, leaf_messages: &[&str], enums: &[&str]) -> bool {
+        if self.boxed {
+            // already boxed with `(rust.box_it)`
+            return true;
+        }
         match self.frequency {
-            Frequency::Repeated | Frequency::Required => return true,
+            Frequency::Required => return true,
+            Frequency::Repeated if !self.is_inline_container() => true,
+            Frequency::Repeated |
             Frequency::Optional => {
-                if self.read_fn(enums) != "message" { return true; }
+                // imported messages cannot refer to the messages of this file
+                if self.read_fn(enums) != "message" || self.extern_type.is_some() { return true; }
                 leaf_messages.iter().any(|m| m == &self.typ)
             },
         }
     }
 
-    fn write_get_size<W: Write>(&self, w: &mut W, enums: &[&str], is_first: bool) -> IoResult<()> {
-        if is_first { 
-            write!(w, "        ")?;
-        } else { 
-            write!(w, "        + ")?;
-        }
+    /// Name of the method returning the size of the field once written
+    fn encoded_len_fn(&self) -> String {
+        format!("{}_encoded_len", self.rust_name.trim_start_matches("r#"))
+    }
+
+    /// Writes the method returning the size of the field once written, summed by `get_size`
+    fn write_encoded_len<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        writeln!(w, "    /// Size of the `{}` field once written, tag included, 0 if it is not written", self.name)?;
+        self.write_cfg(w, "    ")?;
+        writeln!(w, "    pub fn {}(&self) -> usize {{", self.encoded_len_fn())?;
+        self.write_get_size(w, enums)?;
+        writeln!(w, "    }}")
+    }
+
+    fn write_get_size<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        write!(w, "        ")?;
         match self.frequency {
             Frequency::Required => {
-                self.write_inner_get_size(w, enums, &format!("self.{}", self.name), "")?;
+                self.write_inner_get_size(w, enums, &format!("self.{}", self.rust_name), "")?;
                 writeln!(w, "")?;
             }
             Frequency::Optional => {
                 match self.default {
                     None => {
-                        write!(w, "self.{}.as_ref().map_or(0, |m| ", self.name)?;
+                        write!(w, "self.{}.as_ref().map_or(0, |m| ", self.rust_name)?;
                         self.write_inner_get_size(w, enums, "m", "*")?;
                         writeln!(w, ")")?;
                     }
-                    Some(d) => {
-                        write!(w, "if self.{} == {} {{ 0 }} else {{", self.name, d)?;
-                        self.write_inner_get_size(w, enums, &format!("self.{}", self.name), "")?;
-                        writeln!(w, "}}")?;
+                    Some(_) => {
+                        let d = self.default_literal(enums).unwrap();
+                        write!(w, "if self.{} == {} {{ 0 }} else {{ ", self.rust_name, d)?;
+                        self.write_inner_get_size(w, enums, &format!("self.{}", self.rust_name), "")?;
+                        writeln!(w, " }}")?;
                     }
                 }
             }
             Frequency::Repeated => {
                 let tag_size = sizeof_varint(self.tag(enums));
                 let read_fn = self.read_fn(enums);
-                let as_enum = if read_fn == "enum" { " as i32" } else { "" };
+                let value = self.wire_value(enums, "*s");
                 if self.packed() {
-                    write!(w, "if self.{}.is_empty() {{ 0 }} else {{ ", self.name)?;
+                    write!(w, "if self.{}.is_empty() {{ 0 }} else {{ ", self.rust_name)?;
                     match self.wire_type_num_non_packed(enums) {
-                        0 => write!(w, "{} + sizeof_var_length(self.{}.iter().map(|s| sizeof_{}(*s{})).sum::<usize>())", 
-                                    tag_size, self.name, read_fn, as_enum)?,
-                        1 => write!(w, "{} + sizeof_var_length(self.{}.len() * 8)", tag_size, self.name)?,
-                        5 => write!(w, "{} + sizeof_var_length(self.{}.len() * 4)", tag_size, self.name)?,
+                        0 => write!(w, "{} + sizeof_var_length(self.{}.iter().map(|s| sizeof_{}({})).sum::<usize>())", 
+                                    tag_size, self.rust_name, read_fn, value)?,
+                        1 => write!(w, "{} + sizeof_var_length(self.{}.len() * 8)", tag_size, self.rust_name)?,
+                        5 => write!(w, "{} + sizeof_var_length(self.{}.len() * 4)", tag_size, self.rust_name)?,
                         2 => {
                             let len = if self.read_fn(enums) == "message" { "get_size" } else { "len" };
-                            write!(w, "{} + sizeof_var_length(self.{}.iter().map(|s| sizeof_var_length(s.{}())).sum::<usize>())", 
-                                   tag_size, self.name, len)?;
+                            write!(w, "{} + sizeof_var_length(self.{}.iter().map(|s| sizeof_var_length({}.{}())).sum::<usize>())", 
+                                   tag_size, self.rust_name, self.proto_value("s"), len)?;
                         }
                         e => panic!("expecting wire type number, got: {}", e),
                     }
                     writeln!(w, " }}")?;
                 } else {
                     match self.wire_type_num_non_packed(enums) {
-                        0 => writeln!(w, "self.{}.iter().map(|s| {} + sizeof_{}(*s{})).sum::<usize>()", 
-                                      self.name, tag_size, read_fn, as_enum)?,
-                        1 => writeln!(w, "({} + 8) * self.{}.len()", tag_size, self.name)?,
-                        5 => writeln!(w, "({} + 4) * self.{}.len()", tag_size, self.name)?,
+                        0 => writeln!(w, "self.{}.iter().map(|s| {} + sizeof_{}({})).sum::<usize>()", 
+                                      self.rust_name, tag_size, read_fn, value)?,
+                        1 => writeln!(w, "({} + 8) * self.{}.len()", tag_size, self.rust_name)?,
+                        5 => writeln!(w, "({} + 4) * self.{}.len()", tag_size, self.rust_name)?,
                         2 => {
                             let len = if self.read_fn(enums) == "message" { "get_size" } else { "len" };
-                            writeln!(w, "self.{}.iter().map(|s| {} + sizeof_var_length(s.{}())).sum::<usize>()", 
-                                     self.name, tag_size, len)?;
+                            writeln!(w, "self.{}.iter().map(|s| {} + sizeof_var_length({}.{}())).sum::<usize>()", 
+                                     self.rust_name, tag_size, self.proto_value("s"), len)?;
                         }
                         e => panic!("expecting wire type number, got: {}", e),
                     }
@@ -237,13 +954,14 @@ impl<'a> Field<'a> {
         match self.wire_type_num_non_packed(enums) {
             0 => {
                 let read_fn = self.read_fn(enums);
-                let as_enum = if read_fn == "enum" { " as i32" } else { "" };
-                write!(w, "{} + sizeof_{}({}{}{})", tag_size, read_fn, as_ref, s, as_enum)?
+                let value = self.wire_value(enums, &format!("{}{}", as_ref, s));
+                write!(w, "{} + sizeof_{}({})", tag_size, read_fn, value)?
             },
             1 => write!(w, "{} + 8", tag_size)?,
             5 => write!(w, "{} + 4", tag_size)?,
             2 => {
                 let len = if self.read_fn(enums) == "message" { "get_size" } else { "len" };
+                let s = self.proto_value(s);
                 if self.packed() {
                     write!(w, "if s.is_empty() {{ 0 }} else {{ {} + sizeof_var_length({}.{}()) }}", tag_size, s, len)?;
                 } else {
@@ -259,14 +977,15 @@ impl<'a> Field<'a> {
         let tag = self.tag(enums);
         let use_ref = match self.rust_type() {
             "i32" | "i64" | "u32" | "u64" | "f32" | "f64" | "bool" => false,
-            t => !enums.contains(&t),
+            _ => self.read_fn(enums) != "enum",
         };
         let read_fn = self.read_fn(enums);
-        let as_enum = if read_fn == "enum" { " as i32" } else { "" };
+        let deref = if use_ref { "" } else { "*" };
         match self.frequency {
             Frequency::Required => {
                 let r = if use_ref { "&" } else { "" };
-                writeln!(w, "        r.write_{}_with_tag({}, {}self.{}{})?;", read_fn, tag, r, self.name, as_enum)?;
+                let value = self.wire_value(enums, &format!("{}self.{}", r, self.rust_name));
+                writeln!(w, "        r.write_{}_with_tag({}, {})?;", read_fn, tag, value)?;
             },
             Frequency::Optional => {
                 let r = if use_ref { 
@@ -276,52 +995,157 @@ impl<'a> Field<'a> {
                 };
                 match self.default {
                     None => {
-                        writeln!(w, "        if let Some(ref s) = self.{} {{ r.write_{}_with_tag({}, {}s{})?; }}", 
-                                 self.name, read_fn, tag, r, as_enum)?;
+                        let value = self.wire_value(enums, &format!("{}s", r));
+                        writeln!(w, "        if let Some(ref s) = self.{} {{ r.write_{}_with_tag({}, {})?; }}", 
+                                 self.rust_name, read_fn, tag, value)?;
                     },
-                    Some(d) => {
-                        writeln!(w, "        if self.{} != {} {{ r.write_{}_with_tag({}, self.{0}{})?; }}", 
-                                 self.name, d, read_fn, tag, as_enum)?;
+                    Some(_) => {
+                        let d = self.default_literal(enums).unwrap();
+                        let value = self.wire_value(enums, &format!("{}self.{}", if use_ref { "&" } else { "" }, self.rust_name));
+                        writeln!(w, "        if self.{} != {} {{ r.write_{}_with_tag({}, {})?; }}", 
+                                 self.rust_name, d, read_fn, tag, value)?;
                     }
                 }
             }
             Frequency::Repeated => {
                 if self.packed() {
+                    let value = self.wire_value(enums, &format!("{}m", deref));
                     match read_fn {
                         "message" => {
-                            writeln!(w, "        r.write_packed_repeated_field_with_tag({}, &self.{}, |r, m| r.write_{}({}m{}), \
+                            writeln!(w, "        r.write_packed_repeated_field_with_tag({}, &self.{}, |r, m| r.write_{}({}), \
                                         &|m| sizeof_var_length(m.get_size()))?;", 
-                                     tag, self.name, read_fn, if use_ref { "" } else { "*" }, as_enum)?
+                                     tag, self.rust_name, read_fn, value)?
                         },
                         "bytes" | "string" => {
-                            writeln!(w, "        r.write_packed_repeated_field_with_tag({}, &self.{}, |r, m| r.write_{}({}m{}), \
-                                        &|m| sizeof_var_length(m.len()))?;", 
-                                     tag, self.name, read_fn, if use_ref { "" } else { "*" }, as_enum)?
+                            writeln!(w, "        r.write_packed_repeated_field_with_tag({}, &self.{}, |r, m| r.write_{}({}), \
+                                        &|m| sizeof_var_length({}.len()))?;", 
+                                     tag, self.rust_name, read_fn, value, self.proto_value("m"))?
                         },
-                        t => {
-                            writeln!(w, "        r.write_packed_repeated_field_with_tag({}, &self.{}, |r, m| r.write_{}({}m{}), \
-                                        &|m| sizeof_{}(*m))?;", 
-                                     tag, self.name, read_fn, if use_ref { "" } else { "*" }, as_enum, t)?
+                        t => match self.fixed_len() {
+                            Some(_) if self.user_type.is_none() && (t == "float" || t == "double") => {
+                                writeln!(w, "        r.write_packed_{}_with_tag({}, &self.{})?;", t, tag, self.rust_name)?
+                            }
+                            Some(len) if self.user_type.is_none() => {
+                                writeln!(w, "        r.write_packed_fixed_size_with_tag({}, &self.{}, {})?;",
+                                         tag, self.rust_name, len)?
+                            }
+                            Some(len) => writeln!(w, "        r.write_packed_repeated_field_with_tag({}, &self.{}, |r, m| r.write_{}({}), \
+                                                  &|_| {})?;", tag, self.rust_name, read_fn, value, len)?,
+                            None => writeln!(w, "        r.write_packed_repeated_field_with_tag({}, &self.{}, |r, m| r.write_{}({}), \
+                                              &|m| sizeof_{}({}))?;", 
+                                             tag, self.rust_name, read_fn, value, t, self.wire_value(enums, "*m"))?,
                         },
                     }
                 } else {
-                    writeln!(w, "        for s in &self.{} {{ r.write_{}_with_tag({}, {}s{})? }}", 
-                             self.name, read_fn, tag, if use_ref { "" } else { "*" }, as_enum)?;
+                    let iter = if self.container == Container::BoxSlice { ".iter()" } else { "" };
+                    let r = if iter.is_empty() { "&" } else { "" };
+                    let value = self.wire_value(enums, &format!("{}s", deref));
+                    writeln!(w, "        for s in {}self.{}{} {{ r.write_{}_with_tag({}, {})? }}", 
+                             r, self.rust_name, iter, read_fn, tag, value)?;
                 }
             }
         }
         Ok(())
     }
 
+    /// Prints the field in the text format with `t`, if `write_write` would write it
+    fn write_text<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        let print = |v: &str| {
+            let v = if self.user_type.is_some() { format!("&({}).to_proto()", v) } else { v.to_string() };
+            let method = match self.read_fn(enums) {
+                "message" => "message",
+                "string" => "string",
+                "bytes" => "bytes",
+                "float" | "double" => "float",
+                _ => "scalar",
+            };
+            format!("t.{}(\"{}\", {})?;", method, self.name, v)
+        };
+        let deref = if self.boxed { "&**" } else { "" };
+        match self.frequency {
+            Frequency::Required => {
+                writeln!(w, "        {}", print(&format!("{}self.{}", if self.boxed { "&*" } else { "&" }, self.rust_name)))
+            }
+            Frequency::Optional => match self.default_literal(enums) {
+                None => writeln!(w, "        if let Some(ref v) = self.{} {{ {} }}", self.rust_name, print(&format!("{}v", deref))),
+                Some(d) => writeln!(w, "        if self.{} != {} {{ {} }}", self.rust_name, d, print(&format!("&self.{}", self.rust_name))),
+            },
+            Frequency::Repeated => writeln!(w, "        for v in self.{}.iter() {{ {} }}", self.rust_name, print("v")),
+        }
+    }
+
+    /// Expression of the `FieldValue` of a value of the field, `r` being a reference to it
+    fn field_value(&self, enums: &[&str], r: &str) -> String {
+        let owned = self.user_type.is_some();
+        let v = match self.user_type {
+            Some(_) => format!("ProtoConvert::<{}>::to_proto({})", self.rust_type(), r),
+            None if r.starts_with('&') => r[1..].to_string(),
+            None => format!("*{}", r),
+        };
+        match self.read_fn(enums) {
+            "int32" | "sint32" | "sfixed32" | "int64" | "sint64" | "sfixed64" => format!("FieldValue::Int64(i64::from({}))", v),
+            "uint32" | "fixed32" | "uint64" | "fixed64" => format!("FieldValue::Uint64(u64::from({}))", v),
+            "float" | "double" => format!("FieldValue::Double(f64::from({}))", v),
+            "bool" => format!("FieldValue::Bool({})", v),
+            "enum" => format!("FieldValue::Enum(i32::from({}))", v),
+            "string" | "bytes" => {
+                let variant = if self.typ == "string" { "String" } else { "Bytes" };
+                match owned {
+                    true => format!("FieldValue::{}(::std::borrow::Cow::Owned({}))", variant, v),
+                    false => format!("FieldValue::{}(::std::borrow::Cow::Borrowed(&*{}))", variant, v),
+                }
+            }
+            _ if self.boxed => format!("FieldValue::Message(&*{})", v),
+            _ => format!("FieldValue::Message({})", r),
+        }
+    }
+
+    /// Writes the match arm of the field in `field_by_number`
+    fn write_field_by_number<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        let n = &self.rust_name;
+        match self.frequency {
+            Frequency::Repeated => writeln!(w, "            {} => Some(FieldValue::Repeated(self.{}.iter().map(|v| {}).collect())),",
+                                            self.number, n, self.field_value(enums, "v")),
+            _ if self.is_option() => writeln!(w, "            {} => self.{}.as_ref().map(|v| {}),",
+                                              self.number, n, self.field_value(enums, "v")),
+            _ => writeln!(w, "            {} => Some({}),", self.number, self.field_value(enums, &format!("&self.{}", n))),
+        }
+    }
+
+    /// Writes the comparison of the field in `eq_bitwise`, floats with `eq_float` and messages
+    /// with their own `eq_bitwise`
+    fn write_eq_bitwise<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        let n = &self.rust_name;
+        let (single, each) = match self.read_fn(enums) {
+            _ if self.user_type.is_some() => return writeln!(w, "        let eq = eq && self.{0} == other.{0};", n),
+            "float" | "double" => (format!("eq_float(&self.{0}, &other.{0})", n), "eq_float(a, b)"),
+            "message" => (format!("self.{0}.eq_bitwise(&other.{0})", n), "a.eq_bitwise(b)"),
+            _ => return writeln!(w, "        let eq = eq && self.{0} == other.{0};", n),
+        };
+        match self.frequency {
+            Frequency::Optional if self.default.is_none() => {
+                writeln!(w, "        let eq = eq && self.{0}.is_some() == other.{0}.is_some()", n)?;
+                writeln!(w, "            && self.{0}.iter().zip(other.{0}.iter()).all(|(a, b)| {1});", n, each)
+            }
+            Frequency::Repeated => {
+                writeln!(w, "        let eq = eq && self.{0}.len() == other.{0}.len()", n)?;
+                writeln!(w, "            && self.{0}.iter().zip(other.{0}.iter()).all(|(a, b)| {1});", n, each)
+            }
+            _ => writeln!(w, "        let eq = eq && {};", single),
+        }
+    }
+
     fn has_unregular_default(&self, enums: &[Enumerator]) -> bool {
         match self.default {
             None => false,
             Some(ref d) => match self.rust_type() {
-                "i32" | "i64" | "u32" | "u64" | "f32" | "f64" => d.parse::<f32>().unwrap() != 0.,
+                "i32" | "i64" | "u32" | "u64" | "f32" | "f64" => d.parse::<f64>() != Ok(0.),
                 "bool" => *d != "false",
                 "String" | "Vec<u8>" => *d != "\"\"",
-                t => match enums.iter().find(|e| e.name == self.typ) {
-                    Some(e) => t != e.fields[0].0,
+                _ => match enums.iter().find(|e| e.name == self.typ) {
+                    Some(e) => *d != e.fields[0].0,
+                    // the first variant of an imported enum is unknown
+                    None if self.extern_enum => true,
                     None => false, // Messages are regular defaults
                 }
             } 
@@ -333,77 +1157,686 @@ impl<'a> Field<'a> {
 pub struct Message<'a> {
     pub name: &'a str,
     pub fields: Vec<Field<'a>>,
+    pub options: Vec<(&'a str, &'a str)>,
+    /// Names of the `oneof`s, which are not supported yet
+    pub oneofs: Vec<&'a str>,
+    /// Extra attributes emitted on the struct
+    pub attributes: Vec<String>,
+    /// Implements `Eq`, `Hash` and `Ord`
+    pub ord: bool,
+    /// Keeps unknown fields in an `unknown_fields` member, with these extra attributes
+    pub unknown_fields: Option<Vec<String>>,
+    /// Generates a `{Name}Builder` checking that required fields are set
+    pub builder: bool,
+    /// Generates compact code (`option optimize_for = CODE_SIZE`), relying on the default trait methods
+    pub code_size: bool,
+    /// Borrows from the input, with a `'a` lifetime, because of one of its fields
+    pub lifetime: bool,
+    /// Converts from and to a tuple of its fields, `(rust.tuple)` option
+    pub tuple: bool,
+    /// Derives `Copy`, all its fields being scalars or enums
+    pub copy: bool,
 }
 
 impl<'a> Message<'a> {
+    /// Generic parameters of the impl blocks of the message: its lifetime if any
+    fn generics(&self) -> &'static str {
+        if self.lifetime { "<'a>" } else { "" }
+    }
+
+    /// Type of the message, with its lifetime if any
+    fn rust_type(&self) -> String {
+        format!("{}{}", self.name, self.generics())
+    }
+
     fn write_definition<W: Write>(&self, w: &mut W, enums: &[Enumerator]) -> IoResult<()> {
-        if self.can_derive_default(enums) {
-            writeln!(w, "#[derive(Debug, Default, PartialEq, Clone)]")?;
-        } else {
-            writeln!(w, "#[derive(Debug, PartialEq, Clone)]")?;
+        let default = if self.can_derive_default(enums) { "Default, " } else { "" };
+        // unknown fields are ignored by the `PartialEq` and `Hash` of `write_impl_eq`
+        let partial_eq = if self.unknown_fields.is_some() { "" } else { "PartialEq, " };
+        let eq = match (self.ord, self.unknown_fields.is_some()) {
+            (true, false) => "Eq, Hash, ",
+            (true, true) => "Eq, ",
+            (false, _) => "",
+        };
+        let copy = if self.copy { ", Copy" } else { "" };
+        writeln!(w, "#[derive(Debug, {}{}{}Clone{})]", default, partial_eq, eq, copy)?;
+        for a in &self.attributes {
+            writeln!(w, "{}", a)?;
         }
-        writeln!(w, "pub struct {} {{", self.name)?;
+        writeln!(w, "pub struct {} {{", self.rust_type())?;
         for f in self.fields.iter().filter(|f| !f.deprecated) {
             f.write_definition(w)?;
         }
+        if let Some(ref attributes) = self.unknown_fields {
+            for a in attributes {
+                writeln!(w, "    {}", a)?;
+            }
+            writeln!(w, "    pub unknown_fields: UnknownFields,")?;
+        }
         writeln!(w, "}}")
     }
 
-    fn can_derive_default(&self, enums: &[Enumerator]) -> bool {
-        self.fields.iter().all(|f| f.deprecated || !f.has_unregular_default(enums))
+    /// Implements `TextFormat`, and `Display` through it
+    fn write_impl_display<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        let fields = self.fields.iter().filter(|f| !f.deprecated).collect::<Vec<_>>();
+        writeln!(w, "impl{} TextFormat for {} {{", self.generics(), self.rust_type())?;
+        writeln!(w, "    fn fmt_text(&self, {}: &mut TextFormatter) -> ::std::fmt::Result {{",
+                 if fields.is_empty() { "_t" } else { "t" })?;
+        for f in fields {
+            f.write_gated(w, |w| f.write_text(w, enums))?;
+        }
+        writeln!(w, "        Ok(())")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")?;
+        writeln!(w)?;
+        writeln!(w, "impl{} ::std::fmt::Display for {} {{", self.generics(), self.rust_type())?;
+        writeln!(w, "    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {{")?;
+        writeln!(w, "        TextFormatter::new(f).write(self)")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
     }
 
-    fn write_impl_message_read<W: Write>(&self, w: &mut W, enums: &[Enumerator]) -> IoResult<()> {
-        writeln!(w, "impl MessageRead for {} {{", self.name)?;
-        let enums_str = enums.iter().map(|e| e.name).collect::<Vec<_>>();
-        self.write_from_reader(w, &enums_str)?;
+    /// Implements `IntoIterator` (owned and borrowed), `FromIterator` and `Index`, with `len` and
+    /// `is_empty`, over the items of the `(rust.collection)` field, if any
+    fn write_impl_collection<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        let f = match self.fields.iter().find(|f| f.collection && !f.deprecated) {
+            Some(f) => f,
+            None => return Ok(()),
+        };
+        let typ = &*f.field_type();
+        let (into_iter, into_iter_expr) = f.container.owned_iter(typ, &format!("self.{}", f.rust_name));
+        // the lifetime of references is 'a, unless it is already the one of the message
+        let (r, generics) = if self.lifetime { ("'r", "<'a, 'r>") } else { ("'a", "<'a>") };
+        writeln!(w)?;
+        f.write_cfg(w, "")?;
+        writeln!(w, "impl{} IntoIterator for {} {{", self.generics(), self.rust_type())?;
+        writeln!(w, "    type Item = {};", typ)?;
+        writeln!(w, "    type IntoIter = {};", into_iter)?;
+        writeln!(w)?;
+        writeln!(w, "    fn into_iter(self) -> Self::IntoIter {{")?;
+        writeln!(w, "        {}", into_iter_expr)?;
+        writeln!(w, "    }}")?;
         writeln!(w, "}}")?;
-
-        if !self.can_derive_default(enums) {
-            writeln!(w, "")?;
-            self.write_impl_default(w, &enums_str)?;
+        writeln!(w)?;
+        f.write_cfg(w, "")?;
+        writeln!(w, "impl{} IntoIterator for &{} {} {{", generics, r, self.rust_type())?;
+        writeln!(w, "    type Item = &{} {};", r, typ)?;
+        writeln!(w, "    type IntoIter = ::std::slice::Iter<{}, {}>;", r, typ)?;
+        writeln!(w)?;
+        writeln!(w, "    fn into_iter(self) -> Self::IntoIter {{")?;
+        writeln!(w, "        self.{}.iter()", f.rust_name)?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")?;
+        writeln!(w)?;
+        f.write_cfg(w, "")?;
+        writeln!(w, "impl{} ::std::iter::FromIterator<{}> for {} {{", self.generics(), typ, self.rust_type())?;
+        writeln!(w, "    fn from_iter<I: IntoIterator<Item = {}>>(iter: I) -> Self {{", typ)?;
+        writeln!(w, "        {} {{", self.name)?;
+        writeln!(w, "            {}: iter.into_iter().collect(),", f.rust_name)?;
+        if self.fields.iter().filter(|f| !f.deprecated).count() > 1 || self.unknown_fields.is_some() {
+            writeln!(w, "            ..Self::default()")?;
         }
-        Ok(())
+        writeln!(w, "        }}")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")?;
+        writeln!(w)?;
+        f.write_cfg(w, "")?;
+        writeln!(w, "impl{} ::std::ops::Index<usize> for {} {{", self.generics(), self.rust_type())?;
+        writeln!(w, "    type Output = {};", typ)?;
+        writeln!(w)?;
+        writeln!(w, "    fn index(&self, i: usize) -> &{} {{", typ)?;
+        writeln!(w, "        &self.{}[i]", f.rust_name)?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")?;
+        writeln!(w)?;
+        f.write_cfg(w, "")?;
+        writeln!(w, "impl{} {} {{", self.generics(), self.rust_type())?;
+        writeln!(w, "    /// Number of items of `{}`", f.rust_name)?;
+        writeln!(w, "    pub fn len(&self) -> usize {{")?;
+        writeln!(w, "        self.{}.len()", f.rust_name)?;
+        writeln!(w, "    }}")?;
+        writeln!(w)?;
+        writeln!(w, "    /// `{}` has no items", f.rust_name)?;
+        writeln!(w, "    pub fn is_empty(&self) -> bool {{")?;
+        writeln!(w, "        self.{}.is_empty()", f.rust_name)?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
     }
 
-    fn write_impl_message_write<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
-        writeln!(w, "impl MessageWrite for {} {{", self.name)?;
-        self.write_get_size(w, enums)?;
+    /// Implements `PartialEq` (and `Hash` for `--ord` messages) on the known fields of a message
+    /// with unknown fields, with `eq_including_unknown` comparing them as well
+    fn write_impl_eq<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        let fields = self.fields.iter().filter(|f| !f.deprecated).collect::<Vec<_>>();
+        writeln!(w, "impl{} PartialEq for {} {{", self.generics(), self.rust_type())?;
+        writeln!(w, "    /// Compares the known fields, unknown fields are ignored (see `eq_including_unknown`)")?;
+        writeln!(w, "    fn eq(&self, {}: &Self) -> bool {{", if fields.is_empty() { "_other" } else { "other" })?;
+        writeln!(w, "        let eq = true;")?;
+        for f in &fields {
+            f.write_cfg(w, "        ")?;
+            writeln!(w, "        let eq = eq && self.{0} == other.{0};", f.rust_name)?;
+        }
+        writeln!(w, "        eq")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")?;
+        if self.ord {
+            writeln!(w)?;
+            writeln!(w, "impl{} ::std::hash::Hash for {} {{", self.generics(), self.rust_type())?;
+            writeln!(w, "    fn hash<H: ::std::hash::Hasher>(&self, {}: &mut H) {{",
+                     if fields.is_empty() { "_state" } else { "state" })?;
+            for f in &fields {
+                f.write_cfg(w, "        ")?;
+                writeln!(w, "        ::std::hash::Hash::hash(&self.{}, state);", f.rust_name)?;
+            }
+            writeln!(w, "    }}")?;
+            writeln!(w, "}}")?;
+        }
+        writeln!(w)?;
+        writeln!(w, "impl{} {} {{", self.generics(), self.rust_type())?;
+        writeln!(w, "    /// Compares with `other` like `==`, unknown fields included")?;
+        writeln!(w, "    pub fn eq_including_unknown(&self, other: &Self) -> bool {{")?;
+        writeln!(w, "        self == other && self.unknown_fields == other.unknown_fields")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
+    }
+
+    /// Writes `eq_bitwise`, a `PartialEq` where NaN floats are equal to each other
+    fn write_eq_bitwise<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        let fields = self.fields.iter().filter(|f| !f.deprecated).collect::<Vec<_>>();
+        let other = if fields.is_empty() { "_other" } else { "other" };
+        writeln!(w, "impl{} {} {{", self.generics(), self.rust_type())?;
+        writeln!(w, "    /// Compares with `other` like `==`, except that NaN floats are equal to each other")?;
+        writeln!(w, "    pub fn eq_bitwise(&self, {}: &Self) -> bool {{", other)?;
+        // shadowed `let`s rather than a `&&` chain, which cannot skip feature gated fields
+        writeln!(w, "        let eq = true;")?;
+        for f in fields {
+            f.write_cfg(w, "        ")?;
+            f.write_eq_bitwise(w, enums)?;
+        }
+        writeln!(w, "        eq")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
+    }
+
+    /// Implements `Validate`, checking the constraints of the fields and validating nested messages
+    fn write_impl_validate<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        let fields = self.fields.iter()
+            .filter(|f| !f.deprecated && (!f.constraints.is_empty() || f.read_fn(enums) == "message"))
+            .collect::<Vec<_>>();
+        let (path, errors) = if fields.is_empty() { ("_path", "_errors") } else { ("path", "errors") };
+        writeln!(w, "impl{} Validate for {} {{", self.generics(), self.rust_type())?;
+        if !fields.is_empty() && fields.iter().all(|f| f.feature.is_some()) {
+            writeln!(w, "    #[allow(unused_variables)]")?;
+        }
+        writeln!(w, "    fn validate_into(&self, {}: &str, {}: &mut Vec<ValidationError>) {{", path, errors)?;
+        for f in fields {
+            f.write_gated(w, |w| f.write_validate(w, enums))?;
+        }
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
+    }
+
+    /// Implements `FieldByNumber`, matching the number of each field
+    ///
+    /// Message fields of a user type cannot be borrowed as a `FieldValue` and are not returned
+    fn write_impl_field_by_number<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        let fields = self.fields.iter()
+            .filter(|f| !f.deprecated && (f.user_type.is_none() || f.read_fn(enums) != "message"))
+            .collect::<Vec<_>>();
+        writeln!(w, "impl{} FieldByNumber for {} {{", self.generics(), self.rust_type())?;
+        if fields.is_empty() {
+            writeln!(w, "    fn field_by_number(&self, _n: u32) -> Option<FieldValue<'_>> {{")?;
+            writeln!(w, "        None")?;
+        } else {
+            writeln!(w, "    fn field_by_number(&self, n: u32) -> Option<FieldValue<'_>> {{")?;
+            writeln!(w, "        match n {{")?;
+            for f in fields {
+                f.write_cfg(w, "            ")?;
+                f.write_field_by_number(w, enums)?;
+            }
+            writeln!(w, "            _ => None,")?;
+            writeln!(w, "        }}")?;
+        }
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
+    }
+
+    /// Writes `validate_bytes`, checking that bytes are a valid encoding of the message without
+    /// building it, and `validate_fields`, walking its fields for the messages it is nested in
+    fn write_validate_bytes<W: Write>(&self, w: &mut W, enums: &[&str], closed_enums: &[&str]) -> IoResult<()> {
+        writeln!(w, "impl{} {} {{", self.generics(), self.rust_type())?;
+        writeln!(w, "    /// Checks that `bytes` are a valid `{}` without building it: lengths, utf8 strings and values", self.name)?;
+        writeln!(w, "    /// of closed enums, of the nested messages too, the required fields excepted")?;
+        writeln!(w, "    pub fn validate_bytes(bytes: &[u8]) -> Result<()> {{")?;
+        writeln!(w, "        Self::validate_fields(&mut Reader::from_reader(bytes, bytes.len()))")?;
+        writeln!(w, "    }}")?;
+        writeln!(w)?;
+        writeln!(w, "    /// Checks the fields read by `r`, see `validate_bytes`")?;
+        writeln!(w, "    pub fn validate_fields(r: &mut Reader<&[u8]>) -> Result<()> {{")?;
+        writeln!(w, "        while !r.is_eof() {{")?;
+        writeln!(w, "            match r.next_tag()? {{")?;
+        for f in self.fields.iter().filter(|f| !f.deprecated) {
+            f.write_validate_arms(w, enums, closed_enums)?;
+        }
+        writeln!(w, "                t => r.read_unknown(t)?,")?;
+        writeln!(w, "            }}")?;
+        writeln!(w, "        }}")?;
+        writeln!(w, "        Ok(())")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
+    }
+
+    /// Newtype of the field numbers of the message, so that numbers of another message cannot be
+    /// matched by mistake, with a constant per field
+    fn write_tag_type<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        writeln!(w, "/// Number of a field of `{}`", self.name)?;
+        writeln!(w, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]")?;
+        writeln!(w, "pub struct {}Tag(pub u32);", self.name)?;
+        writeln!(w)?;
+        writeln!(w, "impl {}Tag {{", self.name)?;
+        for f in &self.fields {
+            writeln!(w, "    pub const {}: {}Tag = {}Tag({});",
+                     snake_case(f.rust_name.trim_start_matches("r#")).to_uppercase(), self.name, self.name, f.number)?;
+        }
+        if !self.fields.is_empty() {
+            writeln!(w)?;
+        }
+        writeln!(w, "    /// Reads the next field of a `{}`, `None` if all bytes have been read", self.name)?;
+        writeln!(w, "    pub fn next_field<R: Read>(r: &mut Reader<R>) -> Result<Option<({}Tag, WireType)>> {{", self.name)?;
+        writeln!(w, "        Ok(r.next_field()?.map(|(n, t)| ({}Tag(n), t)))", self.name)?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
+    }
+
+    /// Converts to and from a `serde_json::Value` through the serde impls, without going through
+    /// JSON text, when the `serde_json` feature is enabled
+    fn write_impl_json_value<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        writeln!(w, "#[cfg(feature = \"serde_json\")]")?;
+        writeln!(w, "impl{} {} {{", self.generics(), self.rust_type())?;
+        writeln!(w, "    /// Converts into a JSON value, following the protobuf JSON mapping like `Serialize`")?;
+        writeln!(w, "    pub fn to_value(&self) -> ::serde_json::Value {{")?;
+        writeln!(w, "        ::serde_json::to_value(self).expect(\"message cannot be converted to a JSON value\")")?;
+        writeln!(w, "    }}")?;
+        writeln!(w)?;
+        writeln!(w, "    /// Converts from a JSON value, following the protobuf JSON mapping like `Deserialize`")?;
+        writeln!(w, "    pub fn from_value(v: ::serde_json::Value) -> ::serde_json::Result<Self> {{")?;
+        writeln!(w, "        ::serde_json::from_value(v)")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
+    }
+
+    /// Converts from a tuple of the values of the fields, by increasing number, and back: with
+    /// `TryFrom`, failing with `MissingField`, if an optional field may not be set
+    fn write_impl_tuple<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        let mut fields = self.fields.iter().filter(|f| !f.deprecated).collect::<Vec<_>>();
+        fields.sort_by_key(|f| f.number);
+        let types = fields.iter().map(|f| match f.frequency {
+            Frequency::Repeated => f.container.rust_type(&f.field_type()),
+            _ => f.field_type(),
+        }).collect::<Vec<_>>();
+        let tuple = format!("({})", types.join(", "));
+        writeln!(w, "impl{} From<{}> for {} {{", self.generics(), tuple, self.rust_type())?;
+        writeln!(w, "    fn from(t: {}) -> Self {{", tuple)?;
+        writeln!(w, "        {} {{", self.name)?;
+        for (i, f) in fields.iter().enumerate() {
+            let v = match (f.is_option(), f.boxed) {
+                _ if matches!(f.frequency, Frequency::Repeated) => format!("t.{}", i),
+                (true, true) => format!("Some(Box::new(t.{}))", i),
+                (true, false) => format!("Some(t.{})", i),
+                (false, true) => format!("Box::new(t.{})", i),
+                (false, false) => format!("t.{}", i),
+            };
+            writeln!(w, "            {}: {},", f.rust_name, v)?;
+        }
+        if self.unknown_fields.is_some() {
+            writeln!(w, "            ..Self::default()")?;
+        }
+        writeln!(w, "        }}")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")?;
+        writeln!(w)?;
+        let values = fields.iter().map(|f| match (f.is_option(), f.boxed) {
+            _ if matches!(f.frequency, Frequency::Repeated) => format!("m.{}", f.rust_name),
+            (true, boxed) => format!("{}m.{}.ok_or(::quick_protobuf::errors::ErrorKind::MissingField(\"{}\"))?",
+                                     if boxed { "*" } else { "" }, f.rust_name, f.name),
+            (false, true) => format!("*m.{}", f.rust_name),
+            (false, false) => format!("m.{}", f.rust_name),
+        }).collect::<Vec<_>>();
+        if fields.iter().any(|f| f.is_option()) {
+            writeln!(w, "impl{} ::std::convert::TryFrom<{}> for {} {{", self.generics(), self.rust_type(), tuple)?;
+            writeln!(w, "    type Error = ::quick_protobuf::errors::Error;")?;
+            writeln!(w, "    fn try_from(m: {}) -> Result<Self> {{", self.rust_type())?;
+            writeln!(w, "        Ok(({}))", values.join(", "))?;
+        } else {
+            writeln!(w, "impl{} From<{}> for {} {{", self.generics(), self.rust_type(), tuple)?;
+            writeln!(w, "    fn from(m: {}) -> Self {{", self.rust_type())?;
+            writeln!(w, "        ({})", values.join(", "))?;
+        }
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
+    }
+
+    /// Orders messages field by field, by increasing field number
+    fn write_impl_ord<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        let mut fields = self.fields.iter().filter(|f| !f.deprecated).collect::<Vec<_>>();
+        fields.sort_by_key(|f| f.number);
+        writeln!(w, "impl{} PartialOrd for {} {{", self.generics(), self.rust_type())?;
+        writeln!(w, "    fn partial_cmp(&self, other: &Self) -> Option<::std::cmp::Ordering> {{")?;
+        writeln!(w, "        Some(self.cmp(other))")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")?;
+        writeln!(w)?;
+        writeln!(w, "impl{} Ord for {} {{", self.generics(), self.rust_type())?;
+        // unknown fields are ignored, like by `PartialEq`
+        let names = fields.iter().map(|f| &*f.rust_name).collect::<Vec<_>>();
+        writeln!(w, "    fn cmp(&self, {}: &Self) -> ::std::cmp::Ordering {{", if names.is_empty() { "_" } else { "other" })?;
+        if names.is_empty() {
+            writeln!(w, "        ::std::cmp::Ordering::Equal")?;
+        }
+        for (i, name) in names.iter().enumerate() {
+            if i == 0 {
+                writeln!(w, "        self.{0}.cmp(&other.{0})", name)?;
+            } else {
+                writeln!(w, "            .then_with(|| self.{0}.cmp(&other.{0}))", name)?;
+            }
+        }
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
+    }
+
+    /// Writes a type-state builder: each required field is a type parameter, `Unset` then `Set`
+    /// by its setter, and `build` is only implemented once they are all `Set`
+    fn write_builder<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        let fields = self.fields.iter().filter(|f| !f.deprecated).collect::<Vec<_>>();
+        let required = fields.iter().filter(|f| matches!(f.frequency, Frequency::Required)).collect::<Vec<_>>();
+        // generic parameters: the lifetime of the message, if any, then the states
+        let states = |f: &dyn Fn(usize) -> String| {
+            let lifetime = if self.lifetime { Some("'a".to_string()) } else { None };
+            let params = lifetime.into_iter().chain((0..required.len()).map(f)).collect::<Vec<_>>();
+            if params.is_empty() {
+                String::new()
+            } else {
+                format!("<{}>", params.join(", "))
+            }
+        };
+        let params = states(&|i| format!("S{}", i));
+        let builder = format!("{}Builder", self.name);
+
+        writeln!(w, "/// Builder of `{}`, `build` is only available once all the required fields are set", self.name)?;
+        writeln!(w, "#[derive(Debug)]")?;
+        writeln!(w, "pub struct {}{} {{", builder, params)?;
+        writeln!(w, "    msg: {},", self.rust_type())?;
+        let state = match required.len() {
+            0 => "()".to_string(),
+            1 => "S0".to_string(),
+            n => format!("({})", (0..n).map(|i| format!("S{}", i)).collect::<Vec<_>>().join(", ")),
+        };
+        writeln!(w, "    state: ::std::marker::PhantomData<{}>,", state)?;
+        writeln!(w, "}}")?;
+        writeln!(w)?;
+        writeln!(w, "impl{} {} {{", self.generics(), self.rust_type())?;
+        writeln!(w, "    pub fn builder() -> {}{} {{", builder, states(&|_| "::quick_protobuf::message::Unset".to_string()))?;
+        writeln!(w, "        {} {{ msg: Self::default(), state: ::std::marker::PhantomData }}", builder)?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")?;
+        writeln!(w)?;
+        writeln!(w, "impl{} {}{} {{", params, builder, params)?;
+        for (i, f) in fields.iter().enumerate() {
+            if i > 0 {
+                writeln!(w)?;
+            }
+            let typ = match f.frequency {
+                Frequency::Repeated => f.container.rust_type(&f.field_type()),
+                _ => f.field_type(),
+            };
+            let value = match f.frequency {
+                Frequency::Optional if f.boxed => "Some(Box::new(v))",
+                Frequency::Optional if f.default.is_none() => "Some(v)",
+                Frequency::Required if f.boxed => "Box::new(v)",
+                _ => "v",
+            };
+            f.write_cfg(w, "    ")?;
+            match required.iter().position(|r| r.number == f.number) {
+                Some(n) => {
+                    let set = states(&|i| if i == n { "::quick_protobuf::message::Set".to_string() } else { format!("S{}", i) });
+                    writeln!(w, "    pub fn {}(self, v: {}) -> {}{} {{", f.rust_name, typ, builder, set)?;
+                    writeln!(w, "        let mut msg = self.msg;")?;
+                    writeln!(w, "        msg.{} = {};", f.rust_name, value)?;
+                    writeln!(w, "        {} {{ msg, state: ::std::marker::PhantomData }}", builder)?;
+                }
+                None => {
+                    writeln!(w, "    pub fn {}(mut self, v: {}) -> Self {{", f.rust_name, typ)?;
+                    writeln!(w, "        self.msg.{} = {};", f.rust_name, value)?;
+                    writeln!(w, "        self")?;
+                }
+            }
+            writeln!(w, "    }}")?;
+        }
+        writeln!(w, "}}")?;
+        writeln!(w)?;
+        writeln!(w, "impl{} {}{} {{", self.generics(), builder, states(&|_| "::quick_protobuf::message::Set".to_string()))?;
+        writeln!(w, "    pub fn build(self) -> {} {{", self.rust_type())?;
+        writeln!(w, "        self.msg")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
+    }
+
+    fn can_derive_default(&self, enums: &[Enumerator]) -> bool {
+        self.fields.iter().all(|f| f.deprecated || !f.has_unregular_default(enums))
+    }
+
+    fn write_impl_message_read<W: Write>(&self, w: &mut W, enums: &[Enumerator]) -> IoResult<()> {
+        if self.lifetime {
+            writeln!(w, "impl<'a> MessageReadBorrowed<'a> for {} {{", self.rust_type())?;
+        } else {
+            writeln!(w, "impl MessageRead for {} {{", self.name)?;
+        }
+        let enums_str = enums.iter().map(|e| e.name).collect::<Vec<_>>();
+        self.write_from_reader(w)?;
+        writeln!(w)?;
+        self.write_merge_from(w, &enums_str)?;
+        if !self.code_size {
+            writeln!(w)?;
+            self.write_clear(w, &enums_str)?;
+        }
+        if self.unknown_fields.is_some() && !self.lifetime {
+            writeln!(w)?;
+            writeln!(w, "    fn unknown_fields_mut(&mut self) -> Option<&mut UnknownFields> {{")?;
+            writeln!(w, "        Some(&mut self.unknown_fields)")?;
+            writeln!(w, "    }}")?;
+        }
+        writeln!(w, "}}")?;
+
+        if !self.can_derive_default(enums) {
+            writeln!(w, "")?;
+            self.write_impl_default(w, &enums_str)?;
+        }
+        Ok(())
+    }
+
+    fn write_impl_message_write<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        writeln!(w, "impl{} MessageWrite for {} {{", self.generics(), self.rust_type())?;
+        self.write_get_size(w)?;
         writeln!(w, "")?;
         self.write_write_message(w, enums)?;
         writeln!(w, "}}")
     }
 
-    fn write_from_reader<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
-        writeln!(w, "    fn from_reader<R: Read>(mut r: &mut Reader<R>) -> Result<Self> {{")?;
+    /// Generic parameters of the read methods, messages with a lifetime are read from a slice only
+    fn reader_generics(&self) -> &'static str {
+        if self.lifetime { "" } else { "<R: Read>" }
+    }
+
+    fn reader_type(&self) -> &'static str {
+        if self.lifetime { "Reader<&'a [u8]>" } else { "Reader<R>" }
+    }
+
+    fn write_from_reader<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        writeln!(w, "    fn from_reader{}(r: &mut {}) -> Result<Self> {{", self.reader_generics(), self.reader_type())?;
         writeln!(w, "        let mut msg = Self::default();")?;
+        writeln!(w, "        msg.merge_from(r)?;")?;
+        writeln!(w, "        Ok(msg)")?;
+        writeln!(w, "    }}")
+    }
+
+    fn write_merge_from<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        writeln!(w, "    fn merge_from{}(&mut self, r: &mut {}) -> Result<()> {{", self.reader_generics(), self.reader_type())?;
+        writeln!(w, "        let msg = self;")?;
+        for f in self.fields.iter().filter(|f| !f.deprecated && f.needs_buffer()) {
+            f.write_cfg(w, "        ")?;
+            writeln!(w, "        let mut {0}_buf = ::std::mem::replace(&mut msg.{0}, Default::default()).into_vec();", f.rust_name)?;
+        }
+        let required = self.required_fields();
+        if !required.is_empty() {
+            writeln!(w, "        let mut required = [false; {}];", required.len())?;
+        }
         writeln!(w, "        while !r.is_eof() {{")?;
         writeln!(w, "            match r.next_tag() {{")?;
         for f in self.fields.iter().filter(|f| !f.deprecated) {
+            // without its feature, a gated field is read as an unknown field
+            f.write_cfg(w, "                ")?;
             write!(w, "                ")?;
-            f.write_match_tag(w, enums)?;
+            match required.iter().position(|r| r.number == f.number) {
+                Some(i) => {
+                    // `Ok(tag) => read,` becomes `Ok(tag) => { read; required[i] = true; }`
+                    let mut arm = Vec::new();
+                    f.write_match_tag(&mut arm, enums)?;
+                    let arm = String::from_utf8(arm).expect("generated code is utf8");
+                    let (tag, read) = arm.trim_end().trim_end_matches(',').split_at(arm.find(" => ").expect("match arm"));
+                    writeln!(w, "{} => {{ {}; required[{}] = true; }}", tag, &read[4..], i)?;
+                }
+                None => f.write_match_tag(w, enums)?,
+            }
+            if f.is_packable(enums) {
+                f.write_cfg(w, "                ")?;
+                write!(w, "                ")?;
+                f.write_match_other_encoding(w, enums)?;
+            }
+        }
+        // tags include the wire type: a known field number with an unexpected wire type
+        // ends up here and is skipped according to its actual wire type, without desync
+        if self.unknown_fields.is_some() {
+            writeln!(w, "                Ok(t) => msg.unknown_fields.read_field(r, t)?,")?;
+        } else {
+            writeln!(w, "                Ok(t) => {{ r.read_unknown(t)?; }}")?;
         }
-        writeln!(w, "                Ok(t) => {{ r.read_unknown(t)?; }}")?;
         writeln!(w, "                Err(e) => return Err(e),")?;
         writeln!(w, "            }}")?;
         writeln!(w, "        }}")?;
-        writeln!(w, "        Ok(msg)")?;
+        for f in self.fields.iter().filter(|f| !f.deprecated && f.needs_buffer()) {
+            f.write_gated(w, |w| writeln!(w, "        msg.{0} = {0}_buf.into_boxed_slice();", f.rust_name))?;
+        }
+        if !required.is_empty() {
+            let names = required.iter().map(|f| format!("\"{}\"", f.name)).collect::<Vec<_>>();
+            writeln!(w, "        r.check_required(&required, &[{}])?;", names.join(", "))?;
+        }
+        writeln!(w, "        Ok(())")?;
         writeln!(w, "    }}")
     }
 
-    fn write_get_size<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+    /// Required fields checked by `Reader::check_required`
+    ///
+    /// Feature gated fields cannot be required
+    fn required_fields(&self) -> Vec<&Field<'a>> {
+        self.fields.iter().filter(|f| !f.deprecated && matches!(f.frequency, Frequency::Required)).collect()
+    }
+
+    /// Resets all fields to their default, keeping `Vec` allocations
+    fn write_clear<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        writeln!(w, "    fn clear(&mut self) {{")?;
+        for f in self.fields.iter().filter(|f| !f.deprecated) {
+            f.write_gated(w, |w| match (&f.frequency, &f.container) {
+                (&Frequency::Repeated, &Container::BoxSlice) => writeln!(w, "        self.{} = Default::default();", f.rust_name),
+                (&Frequency::Repeated, _) => writeln!(w, "        self.{}.clear();", f.rust_name),
+                _ => writeln!(w, "        self.{} = {};", f.rust_name, f.default_value(enums)),
+            })?;
+        }
+        if self.unknown_fields.is_some() {
+            writeln!(w, "        self.unknown_fields.clear();")?;
+        }
+        writeln!(w, "    }}")
+    }
+
+    fn write_get_size<W: Write>(&self, w: &mut W) -> IoResult<()> {
         writeln!(w, "    fn get_size(&self) -> usize {{")?;
-        for (i, f) in self.fields.iter().filter(|f| !f.deprecated).enumerate() {
-            f.write_get_size(w, enums, i == 0)?;
+        let fields = self.fields.iter().filter(|f| !f.deprecated).collect::<Vec<_>>();
+        if fields.iter().any(|f| f.feature.is_some()) {
+            // a sum cannot skip terms with `#[cfg]`, shadowed `let`s can
+            writeln!(w, "        let size = 0;")?;
+            for f in fields {
+                f.write_cfg(w, "        ")?;
+                writeln!(w, "        let size = size + self.{}();", f.encoded_len_fn())?;
+            }
+            if self.unknown_fields.is_some() {
+                writeln!(w, "        let size = size + self.unknown_fields.get_size();")?;
+            }
+            writeln!(w, "        size")?;
+            return writeln!(w, "    }}");
+        }
+        for (i, f) in fields.iter().enumerate() {
+            writeln!(w, "        {}self.{}()", if i == 0 { "" } else { "+ " }, f.encoded_len_fn())?;
+        }
+        match (fields.is_empty(), self.unknown_fields.is_some()) {
+            (true, true) => writeln!(w, "        self.unknown_fields.get_size()")?,
+            (false, true) => writeln!(w, "        + self.unknown_fields.get_size()")?,
+            (true, false) => writeln!(w, "        0")?,
+            (false, false) => (),
         }
         writeln!(w, "    }}")
     }
 
+    /// Writes the `{field}_encoded_len` methods of the fields
+    fn write_encoded_lens<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        writeln!(w, "impl{} {} {{", self.generics(), self.rust_type())?;
+        for f in self.fields.iter().filter(|f| !f.deprecated) {
+            f.write_encoded_len(w, enums)?;
+            writeln!(w)?;
+        }
+        self.write_size_hint(w, enums)?;
+        writeln!(w, "}}")
+    }
+
+    /// Writes `size_hint`, a lower bound of `get_size` known without a message: the minimum size of
+    /// the required fields, which are always written, e.g. to reserve a buffer
+    ///
+    /// Equal to `get_size` for messages whose fields are all required and fixed size
+    fn write_size_hint<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
+        let mut len = 0;
+        let mut nested = Vec::new();
+        for f in self.fields.iter().filter(|f| !f.deprecated && matches!(f.frequency, Frequency::Required)) {
+            len += sizeof_varint(f.tag(enums)) + match f.wire_type_num_non_packed(enums) {
+                1 => 8,
+                5 => 4,
+                // the length prefix of a message, then at least the size hint of its own fields
+                // (boxed messages of a recursive cycle, imported and lazy messages excepted)
+                2 if f.read_fn(enums) == "message" && !f.boxed && f.extern_type.is_none() && !f.lazy => {
+                    nested.push(format!("{}::size_hint()", f.typ));
+                    1
+                }
+                _ => 1,
+            };
+        }
+        writeln!(w, "    /// Lower bound of `get_size`: the minimum size of the required fields, which are always written")?;
+        writeln!(w, "    pub const fn size_hint() -> usize {{")?;
+        write!(w, "        {}", len)?;
+        for n in nested {
+            write!(w, " + {}", n)?;
+        }
+        writeln!(w)?;
+        writeln!(w, "    }}")
+    }
+
     fn write_write_message<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
         writeln!(w, "    fn write_message<W: Write>(&self, r: &mut Writer<W>) -> Result<()> {{")?;
+        writeln!(w, "        let start = r.written();")?;
         for f in self.fields.iter().filter(|f| !f.deprecated) {
-            f.write_write(w, enums)?;
+            f.write_gated(w, |w| f.write_write(w, enums))?;
         }
+        if self.unknown_fields.is_some() {
+            writeln!(w, "        self.unknown_fields.write(r)?;")?;
+        }
+        // catches get_size drifting from what is actually written, in debug builds only
+        writeln!(w, "        debug_assert_eq!(r.written() - start, self.get_size(), \"{}: written size differs from get_size\");",
+                 self.name)?;
         writeln!(w, "        Ok(())")?;
         writeln!(w, "    }}")
     }
@@ -412,19 +1845,49 @@ impl<'a> Message<'a> {
         self.fields.iter().all(|f| f.is_leaf(leaf_messages, enums) || f.deprecated)
     }
 
+    /// Writes `From<module::Other>`, fields are matched by number, unmatched ones are defaulted
+    fn write_impl_from<W: Write>(&self, w: &mut W, other: &Message, module: &str,
+                                 enums: &[&str], other_enums: &[&str]) -> IoResult<()> {
+        let mut fields = Vec::new();
+        let mut complete = true;
+        for f in self.fields.iter().filter(|f| !f.deprecated) {
+            match other.fields.iter()
+                .find(|o| o.number == f.number && !o.deprecated && o.feature.is_none() && f.feature.is_none())
+                .and_then(|o| f.convert_from(o, enums, other_enums)) {
+                Some(c) => fields.push((&*f.rust_name, c)),
+                None => complete = false,
+            }
+        }
+        match (&self.unknown_fields, &other.unknown_fields) {
+            (&Some(_), &Some(_)) => fields.push(("unknown_fields", "m.unknown_fields".to_string())),
+            (&Some(_), &None) => complete = false,
+            _ => (),
+        }
+        writeln!(w, "impl From<{}::{}> for {} {{", module, other.name, self.name)?;
+        let m = if fields.is_empty() { "_" } else { "m" };
+        writeln!(w, "    fn from({}: {}::{}) -> Self {{", m, module, other.name)?;
+        writeln!(w, "        {} {{", self.name)?;
+        for (name, c) in fields {
+            writeln!(w, "            {}: {},", name, c)?;
+        }
+        if !complete {
+            writeln!(w, "            ..Self::default()")?;
+        }
+        writeln!(w, "        }}")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
+    }
+
     fn write_impl_default<W: Write>(&self, w: &mut W, enums: &[&str]) -> IoResult<()> {
-        writeln!(w, "impl Default for {} {{", self.name)?;
+        writeln!(w, "impl{} Default for {} {{", self.generics(), self.rust_type())?;
         writeln!(w, "    fn default() -> Self {{")?;
         writeln!(w, "        {} {{", self.name)?;
         for f in self.fields.iter().filter(|f| !f.deprecated) {
-            match f.default {
-                None => writeln!(w, "            {}::default(),", f.rust_type())?,
-                Some(ref d) => if enums.contains(&f.typ) {
-                    writeln!(w, "            {}: {}::{},", f.name, f.typ, d)?
-                } else {
-                    writeln!(w, "            {}: {},", f.name, d)?
-                }
-            }
+            f.write_cfg(w, "            ")?;
+            writeln!(w, "            {}: {},", f.rust_name, f.default_value(enums))?;
+        }
+        if self.unknown_fields.is_some() {
+            writeln!(w, "            unknown_fields: Default::default(),")?;
         }
         writeln!(w, "        }}")?;
         writeln!(w, "    }}")?;
@@ -436,14 +1899,77 @@ impl<'a> Message<'a> {
 pub struct Enumerator<'a> {
     pub name: &'a str,
     pub fields: Vec<(&'a str, i32)>,
+    pub options: Vec<(&'a str, &'a str)>,
+    /// Open (proto3) enums keep unknown values in an extra variant
+    pub open: bool,
+    /// Integer type of the variants in memory, `(rust.repr)` option, `i32` by default
+    pub repr: Option<&'a str>,
+    /// Extra attributes emitted on the enum
+    pub attributes: Vec<String>,
+    /// Extra attributes emitted on the variant holding unknown values of open enums
+    pub unknown_attributes: Vec<String>,
 }
 
 impl<'a> Enumerator<'a> {
+    /// Sets `repr` from the `(rust.repr)` option, checking that all the numbers fit in it
+    fn set_repr(&mut self) -> Result<(), String> {
+        let repr = match self.options.iter().find(|o| o.0 == "(rust.repr)") {
+            Some(o) => o.1.trim_matches('"'),
+            None => return Ok(()),
+        };
+        let (min, max) = match repr {
+            "i8" => (i8::MIN as i32, i8::MAX as i32),
+            "u8" => (0, u8::MAX as i32),
+            "i16" => (i16::MIN as i32, i16::MAX as i32),
+            "u16" => (0, u16::MAX as i32),
+            "i32" => (i32::MIN, i32::MAX),
+            r => return Err(format!("enum '{}': unsupported (rust.repr) '{}', expecting i8, u8, i16, u16 or i32",
+                                    self.name, r)),
+        };
+        if self.open {
+            return Err(format!("enum '{}': open enums cannot have a (rust.repr), \
+                                they are generated as closed enums with --exhaustive-enums", self.name));
+        }
+        if let Some(&(f, n)) = self.fields.iter().find(|&&(_, n)| n < min || n > max) {
+            return Err(format!("enum '{}': value {} of '{}' does not fit in a {}", self.name, n, f, repr));
+        }
+        self.repr = Some(repr);
+        Ok(())
+    }
+
+    /// Name of the variant holding unknown values of open enums
+    fn unknown_variant(&self) -> &'static str {
+        if self.fields.iter().any(|&(f, _)| f == "Unknown") {
+            "UnknownValue"
+        } else {
+            "Unknown"
+        }
+    }
+
     fn write_definition<W: Write>(&self, w: &mut W) -> IoResult<()> {
-        writeln!(w, "#[derive(Debug, PartialEq, Eq, Clone, Copy)]")?;
+        writeln!(w, "#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]")?;
+        for a in &self.attributes {
+            writeln!(w, "{}", a)?;
+        }
+        if self.open {
+            writeln!(w, "#[non_exhaustive]")?;
+        } else {
+            writeln!(w, "#[repr({})]", self.repr.unwrap_or("i32"))?;
+        }
         writeln!(w, "pub enum {} {{", self.name)?;
-        for &(f, number) in &self.fields {
-            writeln!(w, "    {} = {},", f, number)?;
+        if self.open {
+            for &(f, _) in &self.fields {
+                writeln!(w, "    {},", f)?;
+            }
+            writeln!(w, "    /// Value not defined in the .proto file")?;
+            for a in &self.unknown_attributes {
+                writeln!(w, "    {}", a)?;
+            }
+            writeln!(w, "    {}(i32),", self.unknown_variant())?;
+        } else {
+            for &(f, number) in &self.fields {
+                writeln!(w, "    {} = {},", f, number)?;
+            }
         }
         writeln!(w, "}}")
     }
@@ -464,37 +1990,429 @@ impl<'a> Enumerator<'a> {
         for &(f, number) in &self.fields {
             writeln!(w, "            {} => {}::{},", number, self.name, f)?;
         }
-        writeln!(w, "            _ => Self::default(),")?;
+        if self.open {
+            writeln!(w, "            i => {}::{}(i),", self.name, self.unknown_variant())?;
+        } else {
+            writeln!(w, "            _ => Self::default(),")?;
+        }
+        writeln!(w, "        }}")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
+    }
+
+    /// Writes `value` and `from_i32`, `const` conversions without `transmute`
+    fn write_impl_values<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        writeln!(w, "impl {} {{", self.name)?;
+        writeln!(w, "    /// Number of the variant")?;
+        writeln!(w, "    pub const fn value(self) -> i32 {{")?;
+        if self.open {
+            writeln!(w, "        match self {{")?;
+            for &(f, number) in &self.fields {
+                writeln!(w, "            {}::{} => {},", self.name, f, number)?;
+            }
+            writeln!(w, "            {}::{}(i) => i,", self.name, self.unknown_variant())?;
+            writeln!(w, "        }}")?;
+        } else {
+            writeln!(w, "        self as i32")?;
+        }
+        writeln!(w, "    }}")?;
+        writeln!(w)?;
+        writeln!(w, "    /// Variant of a number defined in the .proto file, `None` for other numbers")?;
+        writeln!(w, "    pub const fn from_i32(i: i32) -> Option<Self> {{")?;
+        writeln!(w, "        match i {{")?;
+        for &(f, number) in &self.fields {
+            writeln!(w, "            {} => Some({}::{}),", number, self.name, f)?;
+        }
+        writeln!(w, "            _ => None,")?;
         writeln!(w, "        }}")?;
         writeln!(w, "    }}")?;
         writeln!(w, "}}")
     }
+
+    /// Displays the variant name, or the number of unknown values
+    fn write_impl_display<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        writeln!(w, "impl ::std::fmt::Display for {} {{", self.name)?;
+        writeln!(w, "    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {{")?;
+        writeln!(w, "        match *self {{")?;
+        for &(f, _) in &self.fields {
+            writeln!(w, "            {}::{} => f.write_str(\"{}\"),", self.name, f, f)?;
+        }
+        if self.open {
+            writeln!(w, "            {}::{}(i) => write!(f, \"{{}}\", i),", self.name, self.unknown_variant())?;
+        }
+        writeln!(w, "        }}")?;
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
+    }
+
+    fn write_into_i32<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        writeln!(w, "impl From<{}> for i32 {{", self.name)?;
+        writeln!(w, "    fn from(v: {}) -> i32 {{", self.name)?;
+        if self.open {
+            writeln!(w, "        match v {{")?;
+            for &(f, number) in &self.fields {
+                writeln!(w, "            {}::{} => {},", self.name, f, number)?;
+            }
+            writeln!(w, "            {}::{}(i) => i,", self.name, self.unknown_variant())?;
+            writeln!(w, "        }}")?;
+        } else {
+            writeln!(w, "        v as i32")?;
+        }
+        writeln!(w, "    }}")?;
+        writeln!(w, "}}")
+    }
+}
+
+/// A remote procedure call of a service
+#[derive(Debug)]
+pub struct Rpc<'a> {
+    pub name: &'a str,
+    pub input: &'a str,
+    pub input_stream: bool,
+    pub output: &'a str,
+    pub output_stream: bool,
+}
+
+impl<'a> Rpc<'a> {
+    /// Type of a request or a response, boxed in an `RpcStream` if streamed
+    fn rust_type(typ: &str, stream: bool) -> String {
+        if stream {
+            format!("RpcStream<{}, Self::Error>", typ)
+        } else {
+            typ.to_string()
+        }
+    }
+
+    fn write_definition<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        let stream = |s| if s { "stream " } else { "" };
+        writeln!(w, "    /// `rpc {}({}{}) returns ({}{})`", self.name,
+                 stream(self.input_stream), self.input, stream(self.output_stream), self.output)?;
+        let param = if self.input_stream { "requests" } else { "request" };
+        writeln!(w, "    fn {}(&self, {}: {}) -> impl ::std::future::Future<Output = \
+                     ::std::result::Result<{}, Self::Error>> + Send;",
+                 snake_case(self.name), param, Rpc::rust_type(self.input, self.input_stream),
+                 Rpc::rust_type(self.output, self.output_stream))
+    }
+}
+
+/// A service, generated as an async trait to be implemented by servers
+#[derive(Debug)]
+pub struct Service<'a> {
+    pub name: &'a str,
+    pub rpcs: Vec<Rpc<'a>>,
+}
+
+impl<'a> Service<'a> {
+    fn write_definition<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        writeln!(w, "/// Server side of the `{}` service", self.name)?;
+        writeln!(w, "pub trait {} {{", self.name)?;
+        writeln!(w, "    /// Error returned by the methods, or yielded by the streams")?;
+        writeln!(w, "    type Error;")?;
+        for rpc in &self.rpcs {
+            writeln!(w)?;
+            rpc.write_definition(w)?;
+        }
+        writeln!(w, "}}")
+    }
 }
 
 #[derive(Debug)]
 pub enum MessageOrEnum<'a> {
     Msg(Message<'a>),
     Enum(Enumerator<'a>),
+    Service(Service<'a>),
+    Package(&'a str),
+    Option((&'a str, &'a str)),
+    Import(&'a str),
     Ignore,
 }
 
 #[derive(Debug)]
 pub struct FileDescriptor<'a> {
     pub syntax: Syntax,
+    pub package: Option<&'a str>,
+    /// File options, e.g. `optimize_for`
+    pub options: Vec<(&'a str, &'a str)>,
     pub message_and_enums: Vec<MessageOrEnum<'a>>,
     pub messages: Vec<Message<'a>>,
     pub enums: Vec<Enumerator<'a>>,
+    pub services: Vec<Service<'a>>,
+    /// Paths of the imported .proto files
+    pub imports: Vec<&'a str>,
 }
 
 impl<'a> FileDescriptor<'a> {
 
-    pub fn from_bytes(b: &'a [u8]) -> Result<FileDescriptor<'a>, IError<u32>> {
-        let mut f = file_descriptor(b).to_full_result()?;
+    pub fn from_bytes(b: &'a [u8], config: &Config) -> Result<FileDescriptor<'a>, String> {
+        FileDescriptor::from_bytes_with_imports(b, config, &[])
+    }
+
+    /// Parses a file importing other files, as (import path, parsed file, rust module) triples,
+    /// whose types are used from the module they are generated in
+    pub fn from_bytes_with_imports(b: &'a [u8], config: &Config, imports: &[(&str, FileDescriptor, &str)])
+        -> Result<FileDescriptor<'a>, String>
+    {
+        let mut f = match file_descriptor(b) {
+            IResult::Done(rest, f) => {
+                check_fully_parsed(b, rest)?;
+                f
+            }
+            r => return Err(format!("{:?}", r.to_full_result().err())),
+        };
+        f.split_messages_and_enums();
+        f.check_oneofs()?;
+        f.check_field_numbers()?;
+        for &(path, ref other, module) in imports {
+            if !f.imports.contains(&path) {
+                return Err(format!("--import: '{}' is not imported", path));
+            }
+            f.set_import(other, module);
+        }
+        f.set_config(config)?;
+        f.set_lifetimes();
+        f.set_pooled(config)?;
+        f.check_lazy(config)?;
+        f.check_boxed()?;
         f.break_cycles();
+        f.set_copy();
+        f.set_nested_required();
         f.set_defaults();
+        f.set_constraints()?;
+        if config.serde {
+            f.set_serde_skips();
+        }
         Ok(f)
     }
 
+    /// Rejects `oneof`s, which are not supported yet
+    fn check_oneofs(&self) -> Result<(), String> {
+        for m in &self.messages {
+            if let Some(o) = m.oneofs.first() {
+                return Err(format!("message '{}': oneof '{}' is not supported", m.name, o));
+            }
+        }
+        Ok(())
+    }
+
+    /// Rejects field numbers which would generate a broken dispatch:
+    /// duplicates, 0, out of range or reserved for the protobuf implementation (19000-19999)
+    fn check_field_numbers(&self) -> Result<(), String> {
+        for m in &self.messages {
+            for (i, f) in m.fields.iter().enumerate() {
+                match f.number {
+                    1..=18999 | 20000..=536870911 => (),
+                    19000..=19999 => return Err(format!("message '{}', field '{}': number {} is reserved \
+                                                         for the protobuf implementation", m.name, f.name, f.number)),
+                    n => return Err(format!("message '{}', field '{}': number {} must be between 1 and 536870911",
+                                            m.name, f.name, n)),
+                }
+                if let Some(other) = m.fields[..i].iter().find(|o| o.number == f.number) {
+                    return Err(format!("message '{}': fields '{}' and '{}' have the same number {}",
+                                       m.name, other.name, f.name, f.number));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that all the fields of ordered messages can be ordered
+    fn check_ord(&self) -> Result<(), String> {
+        for m in self.messages.iter().filter(|m| m.ord) {
+            for f in m.fields.iter().filter(|f| !f.deprecated) {
+                if f.feature.is_some() {
+                    return Err(format!("message '{}' cannot be ordered: field '{}' is feature gated", m.name, f.name));
+                }
+                match f.typ {
+                    "float" | "double" => return Err(format!("message '{}' cannot be ordered: field '{}' is a {}",
+                                                             m.name, f.name, f.typ)),
+                    t => if self.messages.iter().any(|o| o.name == t && !o.ord) {
+                        return Err(format!("message '{}' cannot be ordered: message '{}' of field '{}' \
+                                            must be ordered too (--ord={})", m.name, t, f.name, t));
+                    },
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn set_config(&mut self, config: &Config) -> Result<(), String> {
+        let code_size = self.options.contains(&("optimize_for", "CODE_SIZE"));
+        for m in &mut self.messages {
+            let name = m.name;
+            m.attributes = attributes(&m.options, config, name);
+            if config.serde {
+                m.attributes.insert(0, "#[serde(default)]".to_string());
+                m.attributes.insert(0, "#[derive(Serialize, Deserialize)]".to_string());
+            }
+            for f in &mut m.fields {
+                f.set_config(config, name).map_err(|e| format!("message '{}', {}", name, e))?;
+            }
+            for (i, f) in m.fields.iter().enumerate() {
+                if let Some(other) = m.fields[..i].iter().find(|o| o.rust_name == f.rust_name) {
+                    return Err(format!("message '{}': fields '{}' and '{}' are both named '{}' in rust",
+                                       name, other.name, f.name, f.rust_name));
+                }
+            }
+            if m.fields.iter().filter(|f| f.collection).count() > 1 {
+                return Err(format!("message '{}': only one field can be a collection", name));
+            }
+            m.builder = config.builders;
+            m.code_size = code_size;
+            m.tuple = m.options.iter().any(|o| o.0 == "(rust.tuple)" && o.1.trim_matches('"') == "true");
+            if m.tuple {
+                let fields = m.fields.iter().filter(|f| !f.deprecated).collect::<Vec<_>>();
+                if fields.len() < 2 || fields.len() > 3 {
+                    return Err(format!("message '{}': only messages with 2 or 3 fields can be tuples", name));
+                }
+                if let Some(f) = fields.iter().find(|f| f.feature.is_some()) {
+                    return Err(format!("message '{}': field '{}' of a tuple cannot be feature gated", name, f.name));
+                }
+            }
+            if config.unknown_fields {
+                m.unknown_fields = Some(if config.serde_unknown_fields {
+                    // `@` cannot be part of the JSON name of a field
+                    vec!["#[serde(rename = \"@unknown\", default, skip_serializing_if = \"UnknownFields::is_empty\", \
+                          with = \"serde_unknown_fields\")]".to_string()]
+                } else if config.serde {
+                    vec!["#[serde(skip)]".to_string()]
+                } else {
+                    Vec::new()
+                });
+            }
+        }
+        for name in &config.ord {
+            let m = self.messages.iter_mut().find(|m| m.name == name)
+                .ok_or_else(|| format!("--ord: cannot find message '{}'", name))?;
+            m.ord = true;
+        }
+        self.check_ord()?;
+        let proto3 = matches!(self.syntax, Syntax::Proto3);
+        for e in &mut self.enums {
+            e.open = proto3 && !config.exhaustive_enums;
+            e.set_repr()?;
+            if config.serde {
+                e.attributes.push("#[derive(Serialize, Deserialize)]".to_string());
+                if e.open {
+                    // unknown values are written as numbers, like the reference implementation
+                    e.unknown_attributes.push("#[serde(untagged)]".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that lazy fields are of messages without lifetime, and are not used with options
+    /// which need to access the message (`Display`, `Validate` ...)
+    fn check_lazy(&self, config: &Config) -> Result<(), String> {
+        let enums = self.enums.iter().map(|e| e.name).collect::<Vec<_>>();
+        for m in &self.messages {
+            for f in m.fields.iter().filter(|f| f.lazy && !f.deprecated) {
+                let err = |e: &str| Err(format!("message '{}', field '{}': {}", m.name, f.name, e));
+                if f.read_fn(&enums) != "message" {
+                    return err("only message fields can be lazy");
+                }
+                if f.lifetime {
+                    return err("messages with borrowed fields cannot be lazy");
+                }
+                if m.ord {
+                    return err("lazy fields cannot be used with --ord");
+                }
+                if config.display || config.validate || config.eq_bitwise || config.serde || config.field_values {
+                    return err("lazy fields cannot be used with --display, --validate, --eq-bitwise, --serde \
+                                or --field-values");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that `pool` containers are used for message fields which can be read into, those of
+    /// `--repeated pool` falling back to a `Vec` for other fields
+    fn set_pooled(&mut self, config: &Config) -> Result<(), String> {
+        let enums = self.enums.iter().map(|e| e.name).collect::<Vec<_>>();
+        for m in &mut self.messages {
+            for f in m.fields.iter_mut().filter(|f| f.container == Container::Pool) {
+                if f.read_fn(&enums) == "message" && !f.lifetime {
+                    if config.serde {
+                        return Err(format!("message '{}', field '{}': pool containers cannot be used with --serde",
+                                           m.name, f.name));
+                    }
+                } else if f.option("(rust.container)").is_some() {
+                    return Err(format!("message '{}', field '{}': only fields of messages without lifetime \
+                                        can have a pool container", m.name, f.name));
+                } else {
+                    f.container = Container::Vec;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the fields boxed with `(rust.box_it)` are messages
+    fn check_boxed(&self) -> Result<(), String> {
+        let enums = self.enums.iter().map(|e| e.name).collect::<Vec<_>>();
+        for m in &self.messages {
+            if let Some(f) = m.fields.iter().find(|f| f.boxed && !f.deprecated && f.read_fn(&enums) != "message") {
+                return Err(format!("message '{}', field '{}': only message fields can be boxed", m.name, f.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Derives `Copy` on the messages whose fields are all singular scalars or enums
+    ///
+    /// Fields of user types, which may not be `Copy`, and unknown fields prevent it
+    fn set_copy(&mut self) {
+        let enums = self.enums.iter().map(|e| e.name).collect::<Vec<_>>();
+        for m in &mut self.messages {
+            m.copy = m.unknown_fields.is_none() && m.fields.iter().filter(|f| !f.deprecated).all(|f| {
+                !matches!(f.frequency, Frequency::Repeated) && f.user_type.is_none()
+                    && !matches!(f.read_fn(&enums), "message" | "string" | "bytes")
+            });
+        }
+    }
+
+    /// Marks the fields of messages with required fields, then the fields of messages with such
+    /// fields, and so on
+    ///
+    /// Imported messages are not known and are not marked, lazy messages are not read
+    fn set_nested_required(&mut self) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            let nested = self.messages.iter()
+                .filter(|m| m.fields.iter().any(|f| !f.deprecated && (matches!(f.frequency, Frequency::Required) || f.nested_required)))
+                .map(|m| m.name).collect::<Vec<_>>();
+            for m in &mut self.messages {
+                for f in m.fields.iter_mut().filter(|f| !f.deprecated && !f.nested_required && f.extern_type.is_none() && !f.lazy) {
+                    if nested.contains(&f.typ) {
+                        f.nested_required = true;
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Gives a lifetime to the messages with borrowed fields, then to the messages with fields
+    /// of such messages, and so on
+    fn set_lifetimes(&mut self) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            let lifetimes = self.messages.iter().filter(|m| m.lifetime).map(|m| m.name).collect::<Vec<_>>();
+            for m in &mut self.messages {
+                for f in m.fields.iter_mut().filter(|f| !f.deprecated && !f.lifetime) {
+                    f.lifetime = f.borrow || lifetimes.contains(&f.typ);
+                }
+                if !m.lifetime && m.fields.iter().any(|f| f.lifetime) {
+                    m.lifetime = true;
+                    changed = true;
+                }
+            }
+        }
+    }
+
     fn set_defaults(&mut self) {
 
         // if proto3, then changes several defaults
@@ -506,7 +2424,7 @@ impl<'a> FileDescriptor<'a> {
                             f.packed = Some(true); 
                         }
                     }
-                    if f.default.is_none() && f.is_numeric() { 
+                    if f.default.is_none() && f.is_numeric() && f.user_type.is_none() { 
                         f.default = Some("0");
                     }
                 }
@@ -515,7 +2433,49 @@ impl<'a> FileDescriptor<'a> {
 
     }
 
-    pub fn write<W: Write>(&self, w: &mut W, filename: &str) -> IoResult<()> {
+    /// Reads the constraints of the fields, once defaults are known
+    fn set_constraints(&mut self) -> Result<(), String> {
+        for m in &mut self.messages {
+            let name = m.name;
+            for f in m.fields.iter_mut().filter(|f| !f.deprecated) {
+                f.set_constraints().map_err(|e| format!("message '{}', {}", name, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Uses the messages and enums of `other`, an imported file generated in the rust `module`,
+    /// for the fields of their types (`Name`, or `package.Name` when `other` has a package)
+    fn set_import(&mut self, other: &FileDescriptor, module: &str) {
+        let local = self.messages.iter().map(|m| m.name).chain(self.enums.iter().map(|e| e.name)).collect::<Vec<_>>();
+        let package = self.package;
+        for f in self.messages.iter_mut().flat_map(|m| m.fields.iter_mut()) {
+            let typ = f.typ.trim_start_matches('.');
+            let name = match other.package {
+                Some(p) if typ.starts_with(p) && typ[p.len()..].starts_with('.') => &typ[p.len() + 1..],
+                Some(p) if package != Some(p) => continue,
+                _ if local.contains(&typ) => continue,
+                _ => typ,
+            };
+            f.extern_enum = other.enums.iter().any(|e| e.name == name);
+            if f.extern_enum || other.messages.iter().any(|m| m.name == name) {
+                f.extern_type = Some(format!("{}::{}", module, name));
+            }
+        }
+    }
+
+    /// Omits fields from JSON like the reference implementation, once defaults are known
+    fn set_serde_skips(&mut self) {
+        let proto3 = matches!(self.syntax, Syntax::Proto3);
+        let enums = self.enums.iter().map(|e| e.name).collect::<Vec<_>>();
+        for f in self.messages.iter_mut().flat_map(|m| m.fields.iter_mut()) {
+            if let Some(skip) = f.serde_skip(proto3, &enums) {
+                f.attributes.push(format!("#[serde(skip_serializing_if = \"{}\")]", skip));
+            }
+        }
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W, filename: &str, config: &Config) -> IoResult<()> {
         
         println!("Found {} messages, and {} enums", self.messages.len(), self.enums.len());
 
@@ -527,8 +2487,89 @@ impl<'a> FileDescriptor<'a> {
         writeln!(w, "use std::io::{{Read, Write}};")?;
         writeln!(w, "use quick_protobuf::{{MessageRead, MessageWrite, Reader, Writer, Result}};")?;
         writeln!(w, "use quick_protobuf::sizeofs::*;")?;
+        if self.messages.iter().flat_map(|m| m.fields.iter()).any(|f| f.is_inline_container()) {
+            writeln!(w, "use smallvec::SmallVec;")?;
+        }
+        if self.messages.iter().flat_map(|m| m.fields.iter()).any(|f| f.container == Container::Pool) {
+            writeln!(w, "use quick_protobuf::pool::PooledVec;")?;
+        }
+        if config.unknown_fields {
+            writeln!(w, "use quick_protobuf::UnknownFields;")?;
+        }
+        if self.messages.iter().flat_map(|m| m.fields.iter()).any(|f| f.user_type.is_some()) {
+            writeln!(w, "use quick_protobuf::ProtoConvert;")?;
+        }
+        if self.messages.iter().any(|m| m.lifetime) {
+            writeln!(w, "use quick_protobuf::MessageReadBorrowed;")?;
+        }
+        if self.messages.iter().flat_map(|m| m.fields.iter()).any(|f| f.borrow && !f.deprecated) {
+            writeln!(w, "use std::borrow::Cow;")?;
+        }
+        if self.messages.iter().flat_map(|m| m.fields.iter()).any(|f| f.intern && !f.deprecated) {
+            writeln!(w, "use std::sync::Arc;")?;
+        }
+        if self.messages.iter().flat_map(|m| m.fields.iter()).any(|f| f.lazy && !f.deprecated) {
+            writeln!(w, "use quick_protobuf::lazy::LazyMessage;")?;
+        }
+        if config.display {
+            writeln!(w, "use quick_protobuf::text::{{TextFormat, TextFormatter}};")?;
+        }
+        if config.validate {
+            writeln!(w, "use quick_protobuf::validate::{{Validate, ValidationError}};")?;
+        }
+        if config.field_values {
+            writeln!(w, "use quick_protobuf::field_value::{{FieldByNumber, FieldValue}};")?;
+        }
+        if config.typed_tags {
+            writeln!(w, "use quick_protobuf::reader::WireType;")?;
+        }
+        if config.serde {
+            writeln!(w, "use serde::{{Serialize, Deserialize}};")?;
+            let proto3 = matches!(self.syntax, Syntax::Proto3);
+            let enums = self.enums.iter().map(|e| e.name).collect::<Vec<_>>();
+            let skips = self.messages.iter().flat_map(|m| m.fields.iter())
+                .filter_map(|f| f.serde_skip(proto3, &enums)).collect::<Vec<_>>();
+            if skips.contains(&"is_default") {
+                writeln!(w)?;
+                writeln!(w, "fn is_default<T: Default + PartialEq>(v: &T) -> bool {{")?;
+                writeln!(w, "    *v == T::default()")?;
+                writeln!(w, "}}")?;
+            }
+            if skips.contains(&"is_none_or_default") {
+                writeln!(w)?;
+                writeln!(w, "fn is_none_or_default<T: Default + PartialEq>(v: &Option<T>) -> bool {{")?;
+                writeln!(w, "    v.as_ref().map_or(true, |v| *v == T::default())")?;
+                writeln!(w, "}}")?;
+            }
+            if config.serde_unknown_fields {
+                writeln!(w)?;
+                writeln!(w, "/// Serde (de)serialization of unknown fields, as the base64 of their encoded bytes")?;
+                writeln!(w, "mod serde_unknown_fields {{")?;
+                writeln!(w, "    use quick_protobuf::UnknownFields;")?;
+                writeln!(w, "    use serde::{{Serialize, Deserialize, Serializer, Deserializer}};")?;
+                writeln!(w)?;
+                writeln!(w, "    pub fn serialize<S: Serializer>(fields: &UnknownFields, s: S) -> Result<S::Ok, S::Error> {{")?;
+                writeln!(w, "        fields.to_base64().serialize(s)")?;
+                writeln!(w, "    }}")?;
+                writeln!(w)?;
+                writeln!(w, "    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<UnknownFields, D::Error> {{")?;
+                writeln!(w, "        UnknownFields::from_base64(&String::deserialize(d)?).map_err(::serde::de::Error::custom)")?;
+                writeln!(w, "    }}")?;
+                writeln!(w, "}}")?;
+            }
+        }
+        if config.eq_bitwise && self.messages.iter().flat_map(|m| m.fields.iter())
+            .any(|f| matches!(f.typ, "float" | "double") && f.user_type.is_none()) {
+            writeln!(w)?;
+            writeln!(w, "/// Compares floats with `==`, except that NaN is equal to NaN")?;
+            writeln!(w, "fn eq_float<T: Copy + Into<f64>>(a: &T, b: &T) -> bool {{")?;
+            writeln!(w, "    let (a, b): (f64, f64) = ((*a).into(), (*b).into());")?;
+            writeln!(w, "    a == b || a.is_nan() && b.is_nan()")?;
+            writeln!(w, "}}")?;
+        }
 
         let enums = self.enums.iter().map(|e| e.name).collect::<Vec<_>>();
+        let closed_enums = self.enums.iter().filter(|e| !e.open).map(|e| e.name).collect::<Vec<_>>();
         for m in &self.enums {
             writeln!(w, "")?;
             m.write_definition(w)?;
@@ -536,31 +2577,143 @@ impl<'a> FileDescriptor<'a> {
             m.write_impl_default(w)?;
             writeln!(w, "")?;
             m.write_from_i32(w)?;
+            writeln!(w)?;
+            m.write_into_i32(w)?;
+            writeln!(w)?;
+            m.write_impl_values(w)?;
+            if config.display {
+                writeln!(w)?;
+                m.write_impl_display(w)?;
+            }
         }
         for m in &self.messages {
             writeln!(w, "")?;
             m.write_definition(w, &self.enums)?;
+            if m.unknown_fields.is_some() {
+                writeln!(w)?;
+                m.write_impl_eq(w)?;
+            }
             writeln!(w, "")?;
             m.write_impl_message_read(w, &self.enums)?;
             writeln!(w, "")?;
             m.write_impl_message_write(w, &enums)?;
+            writeln!(w)?;
+            m.write_encoded_lens(w, &enums)?;
+            if config.prost {
+                writeln!(w)?;
+                writeln!(w, "impl ::quick_protobuf::ProstMessage for {} {{}}", m.name)?;
+            }
+            m.write_impl_collection(w)?;
+            if m.tuple {
+                writeln!(w)?;
+                m.write_impl_tuple(w)?;
+            }
+            if m.ord {
+                writeln!(w)?;
+                m.write_impl_ord(w)?;
+            }
+            if m.builder {
+                writeln!(w)?;
+                m.write_builder(w)?;
+            }
+            if config.display {
+                writeln!(w)?;
+                m.write_impl_display(w, &enums)?;
+            }
+            if config.eq_bitwise {
+                writeln!(w)?;
+                m.write_eq_bitwise(w, &enums)?;
+            }
+            if config.validate {
+                writeln!(w)?;
+                m.write_impl_validate(w, &enums)?;
+            }
+            if config.field_values {
+                writeln!(w)?;
+                m.write_impl_field_by_number(w, &enums)?;
+            }
+            if config.typed_tags {
+                writeln!(w)?;
+                m.write_tag_type(w)?;
+            }
+            if config.validate_bytes {
+                writeln!(w)?;
+                m.write_validate_bytes(w, &enums, &closed_enums)?;
+            }
+            if config.serde_json {
+                writeln!(w)?;
+                m.write_impl_json_value(w)?;
+            }
+        }
+        if config.services && !self.services.is_empty() {
+            writeln!(w)?;
+            writeln!(w, "/// Boxed stream of the requests or responses of a streaming rpc")?;
+            writeln!(w, "pub type RpcStream<T, E> = ::std::pin::Pin<Box<dyn ::futures_core::Stream<\
+                         Item = ::std::result::Result<T, E>> + Send>>;")?;
+            for s in &self.services {
+                writeln!(w)?;
+                s.write_definition(w)?;
+            }
+        }
+        if config.descriptor {
+            self.write_file_descriptor(w, filename)?;
         }
         Ok(())
     }
 
-    fn break_cycles(&mut self) {
+    /// Writes `From` conversions from messages of `other` file, generated in rust `module`
+    ///
+    /// Messages are paired with `config.conversions`
+    pub fn write_conversions<W: Write>(&self, w: &mut W, other: &FileDescriptor,
+                                       module: &str, config: &Config) -> IoResult<()> {
+        let enums = self.enums.iter().map(|e| e.name).collect::<Vec<_>>();
+        let other_enums = other.enums.iter().map(|e| e.name).collect::<Vec<_>>();
+        for (name, other_name) in &config.conversions {
+            let m = self.messages.iter().find(|m| m.name == name)
+                .ok_or_else(|| Error::other(format!("Cannot find message '{}'", name)))?;
+            let o = other.messages.iter().find(|m| m.name == other_name)
+                .ok_or_else(|| Error::other(format!("Cannot find message '{}' in {}", other_name, module)))?;
+            if m.lifetime || o.lifetime {
+                return Err(Error::other(format!("Cannot convert message '{}': it has borrowed fields", name)));
+            }
+            writeln!(w)?;
+            m.write_impl_from(w, o, module, &enums, &other_enums)?;
+        }
+        Ok(())
+    }
+
+    fn write_file_descriptor<W: Write>(&self, w: &mut W, filename: &str) -> IoResult<()> {
+        let bytes = encode_file_descriptor_set(self, filename)
+            .map_err(|e| Error::other(e.to_string()))?;
+        writeln!(w)?;
+        writeln!(w, "/// Serialized `FileDescriptorSet` of '{}' (e.g. for gRPC server reflection)", filename)?;
+        writeln!(w, "pub const FILE_DESCRIPTOR: &[u8] = &[")?;
+        for chunk in bytes.chunks(16) {
+            let line = chunk.iter().map(|b| b.to_string()).collect::<Vec<_>>();
+            writeln!(w, "    {},", line.join(", "))?;
+        }
+        writeln!(w, "];")
+    }
+
+    fn split_messages_and_enums(&mut self) {
         let mut messages = Vec::new();
         let mut enums = Vec::new();
         for m in self.message_and_enums.drain(..) {
             match m {
                 MessageOrEnum::Msg(m) => messages.push(m),
                 MessageOrEnum::Enum(e) => enums.push(e),
-                _ => (),
+                MessageOrEnum::Service(s) => self.services.push(s),
+                MessageOrEnum::Package(p) => self.package = Some(p),
+                MessageOrEnum::Option(o) => self.options.push(o),
+                MessageOrEnum::Import(i) => self.imports.push(i),
+                MessageOrEnum::Ignore => (),
             }
         }
         self.messages = messages;
         self.enums = enums;
+    }
 
+    fn break_cycles(&mut self) {
         let message_names = self.messages.iter().map(|m| m.name.to_string()).collect::<Vec<_>>();
         let enum_names = self.enums.iter().map(|m| m.name.to_string()).collect::<Vec<_>>();
         let enums = enum_names.iter().map(|n| &**n).collect::<Vec<_>>();
@@ -585,7 +2738,12 @@ impl<'a> FileDescriptor<'a> {
                     let m = self.messages.get_mut(k).unwrap();
                     for f in m.fields.iter_mut() {
                         if !f.is_leaf(&leaf_messages, &enums) {
-                            f.boxed = true;
+                            if f.is_inline_container() {
+                                // fall back to a heap allocated container
+                                f.container = Container::Vec;
+                            } else {
+                                f.boxed = true;
+                            }
                         }
                     }
                 }
@@ -593,3 +2751,782 @@ impl<'a> FileDescriptor<'a> {
         }
     }
 }
+
+#[test]
+fn test_enum_conversions() {
+    for &(open, into) in &[(false, "        v as i32\n"), (true, "            Kind::Unknown(i) => i,\n")] {
+        let e = Enumerator { name: "Kind", fields: vec![("A", 0), ("B", 2)], options: Vec::new(), open, repr: None,
+                             attributes: Vec::new(), unknown_attributes: Vec::new() };
+        let mut from = Vec::new();
+        e.write_from_i32(&mut from).unwrap();
+        let mut to = Vec::new();
+        e.write_into_i32(&mut to).unwrap();
+        let (from, to) = (String::from_utf8(from).unwrap(), String::from_utf8(to).unwrap());
+        assert!(from.contains("impl From<i32> for Kind {") && from.contains("            2 => Kind::B,\n"));
+        assert!(to.contains("impl From<Kind> for i32 {") && to.contains(into));
+        let mut values = Vec::new();
+        e.write_impl_values(&mut values).unwrap();
+        let values = String::from_utf8(values).unwrap();
+        assert!(values.contains("    pub const fn value(self) -> i32 {"));
+        assert!(values.contains("            2 => Some(Kind::B),\n            _ => None,\n"));
+        let mut definition = Vec::new();
+        e.write_definition(&mut definition).unwrap();
+        assert_eq!(!open, String::from_utf8(definition).unwrap().contains("#[repr(i32)]\npub enum Kind {"));
+    }
+}
+
+#[test]
+fn test_pool_container() {
+    let proto = b"message Item { optional int32 x = 1; }
+        message M { repeated Item items = 1; repeated Item others = 2; repeated int32 ids = 3; }";
+    let config = Config { container: Some(Container::Pool), ..Default::default() };
+    let desc = FileDescriptor::from_bytes(proto, &config).unwrap();
+    let containers = desc.messages[1].fields.iter().map(|f| f.container).collect::<Vec<_>>();
+    assert_eq!(vec![Container::Pool, Container::Pool, Container::Vec], containers);
+    let mut w = Vec::new();
+    desc.messages[1].fields[0].write_match_tag(&mut w, &[]).unwrap();
+    assert_eq!("Ok(10) => r.read_message_to_pooled_vec(&mut msg.items)?,\n", String::from_utf8(w).unwrap());
+
+    let proto = b"message M { repeated int32 ids = 1 [(rust.container) = \"pool\"]; }";
+    assert!(FileDescriptor::from_bytes(proto, &Default::default()).is_err());
+    let proto = b"message Item { } message M { repeated Item items = 1 [(rust.container) = \"pool\"]; }";
+    assert!(FileDescriptor::from_bytes(proto, &Config { serde: true, ..Default::default() }).is_err());
+}
+
+#[test]
+fn test_enum_repr() {
+    let proto = |repr: &str, value: i32| format!("enum E {{ option (rust.repr) = \"{}\"; A = 0; B = {}; }}
+        message M {{ optional E e = 1; }}", repr, value);
+    let u8_enum = proto("u8", 255);
+    let desc = FileDescriptor::from_bytes(u8_enum.as_bytes(), &Default::default()).unwrap();
+    let mut definition = Vec::new();
+    desc.enums[0].write_definition(&mut definition).unwrap();
+    assert!(String::from_utf8(definition).unwrap().contains("#[repr(u8)]\npub enum E {\n    A = 0,\n    B = 255,\n"));
+
+    let err = FileDescriptor::from_bytes(proto("u8", 256).as_bytes(), &Default::default()).unwrap_err();
+    assert_eq!("enum 'E': value 256 of 'B' does not fit in a u8", err);
+    let i16_enum = proto("i16", -300);
+    let desc = FileDescriptor::from_bytes(i16_enum.as_bytes(), &Default::default()).unwrap();
+    assert_eq!(vec![("A", 0), ("B", -300)], desc.enums[0].fields);
+    assert!(FileDescriptor::from_bytes(proto("u64", 1).as_bytes(), &Default::default()).is_err());
+    let proto3 = format!("syntax = \"proto3\"; {}", proto("u8", 1));
+    assert!(FileDescriptor::from_bytes(proto3.as_bytes(), &Default::default()).is_err());
+}
+
+#[test]
+fn test_impl_from() {
+    let v1 = FileDescriptor::from_bytes(b"message M { optional int32 a = 1; optional string b = 2; }",
+                                        &Default::default()).unwrap();
+    let v2 = FileDescriptor::from_bytes(b"message M { optional int32 c = 1; optional int64 b = 2; }",
+                                        &Default::default()).unwrap();
+    let mut w = Vec::new();
+    v2.messages[0].write_impl_from(&mut w, &v1.messages[0], "v1", &[], &[]).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("impl From<v1::M> for M {"));
+    assert!(w.contains("            c: m.a,\n            ..Self::default()\n"));
+}
+
+#[test]
+fn test_tuple() {
+    let desc = FileDescriptor::from_bytes(br#"message Entry {
+        option (rust.tuple) = true;
+        optional string value = 2;
+        required int32 key = 1;
+    }
+    message Point {
+        option (rust.tuple) = true;
+        required double x = 1;
+        required double y = 2;
+        repeated string tags = 3;
+    }"#, &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.write(&mut w, "tuple.proto", &Default::default()).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("impl From<(i32, String)> for Entry {\n    fn from(t: (i32, String)) -> Self {\n        \
+                        Entry {\n            key: t.0,\n            value: Some(t.1),\n        }\n"));
+    assert!(w.contains("impl ::std::convert::TryFrom<Entry> for (i32, String) {\n"));
+    assert!(w.contains("        Ok((m.key, m.value.ok_or(::quick_protobuf::errors::ErrorKind::MissingField(\"value\"))?))\n"));
+    assert!(w.contains("impl From<Point> for (f64, f64, Vec<String>) {\n    fn from(m: Point) -> Self {\n        \
+                        (m.x, m.y, m.tags)\n"));
+
+    let parse = |p: &str| FileDescriptor::from_bytes(p.as_bytes(), &Default::default()).map(|_| ());
+    assert!(parse("message M { option (rust.tuple) = true; optional int32 a = 1; }").is_err());
+    assert!(parse(r#"message M { option (rust.tuple) = true; optional int32 a = 1;
+                     optional int32 b = 2 [(rust.feature) = "x"]; }"#).is_err());
+}
+
+#[test]
+fn test_copy() {
+    let desc = FileDescriptor::from_bytes(br#"enum E { A = 0; }
+    message Point { required double x = 1; optional int32 y = 2 [default = 3]; optional E e = 3; optional bool b = 4; }
+    message Name { optional string name = 1; }
+    message Ids { repeated int32 ids = 1; }
+    message Line { optional Point from = 1; }
+    message Id { optional bytes id = 1 [(rust.type) = "Uuid"]; }"#, &Default::default()).unwrap();
+    assert_eq!(vec![true, false, false, false, false], desc.messages.iter().map(|m| m.copy).collect::<Vec<_>>());
+    let mut w = Vec::new();
+    desc.messages[0].write_definition(&mut w, &desc.enums).unwrap();
+    assert!(String::from_utf8(w).unwrap().starts_with("#[derive(Debug, PartialEq, Clone, Copy)]\n"));
+
+    let config = Config { unknown_fields: true, ..Default::default() };
+    let desc = FileDescriptor::from_bytes(b"message P { required int32 x = 1; }", &config).unwrap();
+    assert!(!desc.messages[0].copy);
+}
+
+#[test]
+fn test_check_field_numbers() {
+    let parse = |p: &'static str| FileDescriptor::from_bytes(p.as_bytes(), &Default::default()).map(|_| ());
+    assert!(parse("message M { optional int32 a = 1; optional int32 b = 536870911; }").is_ok());
+    assert!(parse("message M { optional int32 a = 1; optional int32 b = 1; }").is_err());
+    assert!(parse("message M { optional int32 a = 0; }").is_err());
+    assert!(parse("message M { optional int32 a = 19500; }").is_err());
+    assert!(parse("message M { optional int32 a = 536870912; }").is_err());
+}
+
+#[test]
+fn test_unparsed_input() {
+    let parse = |p: &'static str| FileDescriptor::from_bytes(p.as_bytes(), &Default::default()).map(|f| f.messages.len());
+    assert_eq!(Ok(2), parse("message A { }\nmessage B { }\n// end"));
+    assert_eq!(Err("message 'B': oneof 'o' is not supported".to_string()),
+               parse("message A { }\nmessage B {\n  oneof o { int32 x = 1; }\n}\nmessage C { }"));
+    assert_eq!(Err("message 'B': oneof 'o' is not supported".to_string()),
+               parse("message A { }\nmessage B {oneof o {int32 x = 1;}}\nmessage C { }"));
+    assert_eq!(Err("line 2: cannot parse 'message B { optional int32 x; }'".to_string()),
+               parse("message A { }\nmessage B { optional int32 x; }\n// oneof o\nmessage C { }"));
+}
+
+#[test]
+fn test_check_ord() {
+    let ord = |names: &[&str]| Config { ord: names.iter().map(|n| n.to_string()).collect(), ..Default::default() };
+    let proto = b"message A { optional B b = 1; } message B { optional string s = 1; } message F { optional float f = 1; }";
+    assert!(FileDescriptor::from_bytes(proto, &ord(&["A", "B"])).is_ok());
+    assert!(FileDescriptor::from_bytes(proto, &ord(&["A"])).is_err());
+    assert!(FileDescriptor::from_bytes(proto, &ord(&["F"])).is_err());
+    assert!(FileDescriptor::from_bytes(proto, &ord(&["C"])).is_err());
+}
+
+#[test]
+fn test_serde_json_name() {
+    let config = Config { serde: true, ..Default::default() };
+    let desc = FileDescriptor::from_bytes(br#"message M {
+        optional int32 foo_bar = 1;
+        optional int32 baz = 2 [json_name = "qux"];
+        optional int32 id = 3;
+    }"#, &config).unwrap();
+    let fields = &desc.messages[0].fields;
+    let skip = "#[serde(skip_serializing_if = \"Option::is_none\")]".to_string();
+    assert_eq!(vec!["#[serde(rename = \"fooBar\")]".to_string(), skip.clone()], fields[0].attributes);
+    assert_eq!(vec!["#[serde(rename = \"qux\")]".to_string(), skip.clone()], fields[1].attributes);
+    assert_eq!(vec![skip], fields[2].attributes);
+}
+
+#[test]
+fn test_serde_skip() {
+    let config = Config { serde: true, ..Default::default() };
+    let desc = FileDescriptor::from_bytes(br#"message M {
+        required int32 a = 1;
+        optional int32 b = 2 [default = 3];
+        optional M m = 3;
+        repeated int32 r = 4;
+    }"#, &config).unwrap();
+    let skips = desc.messages[0].fields.iter().map(|f| f.serde_skip(false, &[])).collect::<Vec<_>>();
+    assert_eq!(vec![None, None, Some("Option::is_none"), Some("is_default")], skips);
+
+    let desc = FileDescriptor::from_bytes(br#"syntax = "proto3";
+        enum E { ZERO = 0; }
+        message M { int64 x = 1; string s = 2; E e = 3; bytes b = 4; optional string o = 5; M m = 6; }"#,
+        &config).unwrap();
+    let skips = desc.messages[0].fields.iter().map(|f| f.serde_skip(true, &["E"])).collect::<Vec<_>>();
+    assert_eq!(vec![Some("is_default"), Some("is_none_or_default"), Some("is_none_or_default"),
+                    Some("is_none_or_default"), Some("Option::is_none"), Some("Option::is_none")], skips);
+    assert_eq!(vec!["#[serde(untagged)]".to_string()], desc.enums[0].unknown_attributes);
+    let mut w = Vec::new();
+    desc.write(&mut w, "m.proto", &config).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("fn is_default<T: Default + PartialEq>(v: &T) -> bool {"));
+    assert!(w.contains("    #[serde(skip_serializing_if = \"is_default\")]\n    pub x: i64,\n"));
+    // `Some("")` and `Some(E::ZERO)` are omitted like the reference implementation does
+    assert!(w.contains("fn is_none_or_default<T: Default + PartialEq>(v: &Option<T>) -> bool {\n    \
+                        v.as_ref().map_or(true, |v| *v == T::default())\n}"));
+    assert!(w.contains("    #[serde(skip_serializing_if = \"is_none_or_default\")]\n    pub s: Option<String>,\n"));
+    assert!(w.contains("    #[serde(skip_serializing_if = \"is_none_or_default\")]\n    pub e: Option<E>,\n"));
+    assert!(w.contains("    #[serde(skip_serializing_if = \"Option::is_none\")]\n    pub o: Option<String>,\n"));
+}
+
+#[test]
+fn test_serde_json_value() {
+    let config = Config { serde: true, serde_json: true, ..Default::default() };
+    let desc = FileDescriptor::from_bytes(br#"message M { optional string s = 1 [(rust.borrow) = true]; }"#,
+                                          &config).unwrap();
+    let mut w = Vec::new();
+    desc.write(&mut w, "m.proto", &config).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("#[cfg(feature = \"serde_json\")]\nimpl<'a> M<'a> {\n"));
+    assert!(w.contains("    pub fn to_value(&self) -> ::serde_json::Value {\n        ::serde_json::to_value(self)"));
+    assert!(w.contains("    pub fn from_value(v: ::serde_json::Value) -> ::serde_json::Result<Self> {\n"));
+}
+
+#[test]
+fn test_impl_default() {
+    let desc = FileDescriptor::from_bytes(br#"enum E { X = 0; Y = 1; }
+        message M { optional int32 x = 1 [default = 42]; optional E e = 2 [default = Y]; optional string s = 3; }"#,
+        &Default::default()).unwrap();
+    assert!(!desc.messages[0].can_derive_default(&desc.enums));
+    let mut w = Vec::new();
+    desc.messages[0].write_impl_default(&mut w, &["E"]).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("            x: 42,\n            e: E::Y,\n            s: Default::default(),\n"));
+}
+
+#[test]
+fn test_snake_case() {
+    assert_eq!("scenario_set", snake_case("scenarioSet"));
+    assert_eq!("validation_set", snake_case("ValidationSet"));
+    assert_eq!("xml_http_request_count", snake_case("XMLHttpRequest_count"));
+    assert_eq!("field2_name", snake_case("field2Name"));
+    assert_eq!("already_snake", snake_case("already_snake"));
+}
+
+#[test]
+fn test_prost_message() {
+    let config = Config { prost: true, ..Default::default() };
+    let desc = FileDescriptor::from_bytes(b"message M { optional int32 fooBar = 1; }", &config).unwrap();
+    let mut w = Vec::new();
+    desc.write(&mut w, "m.proto", &config).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("    pub foo_bar: Option<i32>,\n"));
+    assert!(w.contains("impl ::quick_protobuf::ProstMessage for M {}\n"));
+}
+
+#[test]
+fn test_user_type() {
+    let config = Config { user_types: vec![("M.at".to_string(), "Millis".to_string())], ..Default::default() };
+    let desc = FileDescriptor::from_bytes(br#"syntax = "proto3";
+        message M { bytes id = 1 [(rust.type) = "Uuid"]; int64 at = 2; }"#, &config).unwrap();
+    let fields = &desc.messages[0].fields;
+    assert_eq!(Some("Uuid"), fields[0].user_type.as_deref());
+    assert_eq!("Millis", fields[1].field_type());
+    assert!(fields[1].default.is_none());
+    let mut w = Vec::new();
+    fields[1].write_match_tag(&mut w, &[]).unwrap();
+    assert_eq!("Ok(16) => msg.at = Some(ProtoConvert::from_proto(r.read_int64()?)?),\n", String::from_utf8(w).unwrap());
+
+    assert!(FileDescriptor::from_bytes(br#"message M { optional M m = 1 [(rust.type) = "Box<M>"]; }"#,
+                                       &Default::default()).is_err());
+    assert!(FileDescriptor::from_bytes(br#"message M { optional int32 x = 1 [default = 1, (rust.type) = "X"]; }"#,
+                                       &Default::default()).is_err());
+}
+
+#[test]
+fn test_unknown_fields() {
+    let config = Config { unknown_fields: true, serde: true, ..Default::default() };
+    let desc = FileDescriptor::from_bytes(b"message Empty { } message M { optional int32 x = 1; }", &config).unwrap();
+    assert_eq!(Some(vec!["#[serde(skip)]".to_string()]), desc.messages[1].unknown_fields);
+    let mut w = Vec::new();
+    desc.messages[0].write_get_size(&mut w).unwrap();
+    assert!(String::from_utf8(w).unwrap().contains("        self.unknown_fields.get_size()\n"));
+    let mut w = Vec::new();
+    desc.messages[1].write_get_size(&mut w).unwrap();
+    assert!(String::from_utf8(w).unwrap().contains("        + self.unknown_fields.get_size()\n"));
+
+    let desc = FileDescriptor::from_bytes(b"message Empty { }", &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.messages[0].write_get_size(&mut w).unwrap();
+    assert!(String::from_utf8(w).unwrap().contains("        0\n"));
+}
+
+#[test]
+fn test_serde_unknown_fields() {
+    let config = Config { unknown_fields: true, serde: true, serde_unknown_fields: true, ..Default::default() };
+    let desc = FileDescriptor::from_bytes(b"message M { optional int32 x = 1; }", &config).unwrap();
+    let mut w = Vec::new();
+    desc.write(&mut w, "m.proto", &config).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("    #[serde(rename = \"@unknown\", default, skip_serializing_if = \"UnknownFields::is_empty\", \
+                        with = \"serde_unknown_fields\")]\n    pub unknown_fields: UnknownFields,\n"));
+    assert!(w.contains("mod serde_unknown_fields {\n"));
+    assert!(w.contains("        UnknownFields::from_base64(&String::deserialize(d)?).map_err(::serde::de::Error::custom)\n"));
+}
+
+#[test]
+fn test_builder() {
+    let config = Config { builders: true, ..Default::default() };
+    let desc = FileDescriptor::from_bytes(b"message M { required int32 x = 1; optional int32 o = 2; required string y = 3; }",
+                                          &config).unwrap();
+    assert!(desc.messages[0].builder);
+    let mut w = Vec::new();
+    desc.messages[0].write_builder(&mut w).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("pub struct MBuilder<S0, S1> {"));
+    assert!(w.contains("    pub fn y(self, v: String) -> MBuilder<S0, ::quick_protobuf::message::Set> {"));
+    assert!(w.contains("    pub fn o(mut self, v: i32) -> Self {\n        self.msg.o = Some(v);"));
+    assert!(w.contains("impl MBuilder<::quick_protobuf::message::Set, ::quick_protobuf::message::Set> {"));
+}
+
+#[test]
+fn test_display() {
+    let desc = FileDescriptor::from_bytes(br#"enum K { A = 0; B = 1; } message N { }
+        message M { required N r = 1; optional K k = 2 [default = B]; repeated bytes b = 3; optional M o = 4; }"#,
+        &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.messages[1].write_impl_display(&mut w, &["K"]).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("        t.message(\"r\", &self.r)?;\n\
+                        \x20       if self.k != K::B { t.scalar(\"k\", &self.k)?; }\n\
+                        \x20       for v in self.b.iter() { t.bytes(\"b\", v)?; }\n\
+                        \x20       if let Some(ref v) = self.o { t.message(\"o\", &**v)?; }\n"));
+    let mut w = Vec::new();
+    desc.enums[0].write_impl_display(&mut w).unwrap();
+    assert!(String::from_utf8(w).unwrap().contains("            K::B => f.write_str(\"B\"),\n"));
+}
+
+#[test]
+fn test_optimize_for() {
+    let read_impl = |proto: &[u8]| {
+        let desc = FileDescriptor::from_bytes(proto, &Default::default()).unwrap();
+        let mut w = Vec::new();
+        desc.messages[0].write_impl_message_read(&mut w, &[]).unwrap();
+        String::from_utf8(w).unwrap()
+    };
+    assert!(read_impl(b"option optimize_for = SPEED; message M { repeated int32 x = 1; }").contains("fn clear"));
+    let code_size = read_impl(br#"option java_package = "a.b";
+        option optimize_for = CODE_SIZE;
+        message M { repeated int32 x = 1; }"#);
+    assert!(code_size.contains("fn merge_from") && !code_size.contains("fn clear"));
+}
+
+#[test]
+fn test_write_size_assertion() {
+    let desc = FileDescriptor::from_bytes(b"message M { optional int32 x = 1; }", &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.messages[0].write_write_message(&mut w, &[]).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("        let start = r.written();\n"));
+    assert!(w.contains("        debug_assert_eq!(r.written() - start, self.get_size(), \
+                        \"M: written size differs from get_size\");\n"));
+}
+
+#[test]
+fn test_rename() {
+    let config = Config { renames: vec![("M.XMLHttpRequest_count".to_string(), "request_count".to_string())],
+                          serde: true, ..Default::default() };
+    let desc = FileDescriptor::from_bytes(br#"message M {
+        optional int32 XMLHttpRequest_count = 1;
+        optional int32 x = 2 [(rust.name) = "type"];
+    }"#, &config).unwrap();
+    let fields = &desc.messages[0].fields;
+    assert_eq!("request_count", fields[0].rust_name);
+    assert_eq!("#[serde(rename = \"XMLHttpRequestCount\")]", fields[0].attributes[0]);
+    assert_eq!("r#type", fields[1].rust_name);
+
+    let config = Config { renames: vec![("M.a".to_string(), "b".to_string())], ..Default::default() };
+    assert!(FileDescriptor::from_bytes(b"message M { optional int32 a = 1; optional int32 b = 2; }", &config).is_err());
+    assert!(FileDescriptor::from_bytes(br#"message M { optional int32 a = 1 [(rust.name) = "a-b"]; }"#,
+                                       &Default::default()).is_err());
+}
+
+#[test]
+fn test_services() {
+    let config = Config { services: true, ..Default::default() };
+    let desc = FileDescriptor::from_bytes(br#"message P { } service S {
+        rpc Get(P) returns (P);
+        rpc Chat(stream P) returns (stream P) {}
+    }"#, &config).unwrap();
+    let mut w = Vec::new();
+    desc.write(&mut w, "s.proto", &config).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("pub trait S {\n    /// Error returned by the methods, or yielded by the streams\n    type Error;\n"));
+    assert!(w.contains("    fn get(&self, request: P) -> impl ::std::future::Future<Output = \
+                        ::std::result::Result<P, Self::Error>> + Send;\n"));
+    assert!(w.contains("    fn chat(&self, requests: RpcStream<P, Self::Error>) -> impl ::std::future::Future<Output = \
+                        ::std::result::Result<RpcStream<P, Self::Error>, Self::Error>> + Send;\n"));
+}
+
+#[test]
+fn test_feature() {
+    let desc = FileDescriptor::from_bytes(br#"message M {
+        optional int32 a = 1;
+        repeated int32 b = 2 [(rust.feature) = "exp"];
+    }"#, &Default::default()).unwrap();
+    assert_eq!(Some("exp"), desc.messages[0].fields[1].feature);
+    let mut w = Vec::new();
+    desc.write(&mut w, "m.proto", &Default::default()).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("    #[cfg(feature = \"exp\")]\n    pub b: Vec<i32>,\n"));
+    assert!(w.contains("                #[cfg(feature = \"exp\")]\n                Ok(16) => { r.check_repeated_len(msg.b.len())?; msg.b.push(r.read_int32()?); }\n"));
+    assert!(w.contains("        #[cfg(feature = \"exp\")]\n        let size = size + self.b_encoded_len();\n"));
+    assert!(w.contains("    #[cfg(feature = \"exp\")]\n    pub fn b_encoded_len(&self) -> usize {\n"));
+    assert!(w.contains("        #[cfg(feature = \"exp\")]\n        {\n            for s in &self.b {"));
+
+    assert!(FileDescriptor::from_bytes(br#"message M { required int32 a = 1 [(rust.feature) = "exp"]; }"#,
+                                       &Default::default()).is_err());
+    let config = Config { ord: vec!["M".to_string()], ..Default::default() };
+    assert!(FileDescriptor::from_bytes(br#"message M { optional int32 a = 1 [(rust.feature) = "exp"]; }"#,
+                                       &config).is_err());
+}
+
+#[test]
+fn test_eq_bitwise() {
+    let config = Config { eq_bitwise: true, ..Default::default() };
+    let desc = FileDescriptor::from_bytes(br#"message M {
+        required float a = 1;
+        repeated double b = 2;
+        optional M m = 3;
+        optional int32 c = 4;
+    }"#, &config).unwrap();
+    let mut w = Vec::new();
+    desc.write(&mut w, "m.proto", &config).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("fn eq_float<T: Copy + Into<f64>>(a: &T, b: &T) -> bool {"));
+    assert!(w.contains("        let eq = eq && eq_float(&self.a, &other.a);\n"));
+    assert!(w.contains("            && self.b.iter().zip(other.b.iter()).all(|(a, b)| eq_float(a, b));\n"));
+    assert!(w.contains("            && self.m.iter().zip(other.m.iter()).all(|(a, b)| a.eq_bitwise(b));\n"));
+    assert!(w.contains("        let eq = eq && self.c == other.c;\n"));
+}
+
+#[test]
+fn test_encoded_len() {
+    let desc = FileDescriptor::from_bytes(b"message M { optional int32 a = 1; required int64 type = 100000; }",
+                                          &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.write(&mut w, "m.proto", &Default::default()).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("    fn get_size(&self) -> usize {\n        self.a_encoded_len()\n        + self.type_encoded_len()\n    }\n"));
+    assert!(w.contains("    pub fn a_encoded_len(&self) -> usize {\n        self.a.as_ref().map_or(0, |m| 1 + sizeof_int32(*m))\n"));
+    assert!(w.contains("    pub fn type_encoded_len(&self) -> usize {\n        3 + sizeof_int64(self.r#type)\n"));
+}
+
+#[test]
+fn test_size_hint() {
+    let desc = FileDescriptor::from_bytes(b"message P { required fixed32 x = 1; required double y = 2; }
+        message L { required P from = 1; optional P to = 2; required string name = 20; repeated int32 ids = 3; }
+        message E { optional int32 a = 1; }", &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.write(&mut w, "m.proto", &Default::default()).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("    pub const fn size_hint() -> usize {\n        14\n    }\n"));
+    assert!(w.contains("    pub const fn size_hint() -> usize {\n        5 + P::size_hint()\n    }\n"));
+    assert!(w.contains("    pub const fn size_hint() -> usize {\n        0\n    }\n"));
+}
+
+#[test]
+fn test_check_required() {
+    let desc = FileDescriptor::from_bytes(b"message P { required int32 x = 1; optional int32 y = 2; }
+        message L { optional P p = 1; repeated P ps = 2; required string name = 3; }
+        message E { optional L l = 1; optional int32 a = 2; }", &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.write(&mut w, "m.proto", &Default::default()).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("        let mut required = [false; 1];\n"));
+    assert!(w.contains("                Ok(8) => { msg.x = r.read_int32()?; required[0] = true; }\n"));
+    assert!(w.contains("                Ok(16) => msg.y = Some(r.read_int32()?),\n"));
+    assert!(w.contains("        r.check_required(&required, &[\"x\"])?;\n"));
+    assert!(w.contains("Ok(10) => r.merge_message(msg.p.get_or_insert_with(Default::default)).map_err(|e| e.in_field(\"p\"))?,"));
+    assert!(w.contains("Ok(18) => { let i = msg.ps.len(); r.read_message_to_vec(&mut msg.ps)\
+                        .map_err(|e| e.in_field(&format!(\"ps[{}]\", i)))?; }"));
+    assert!(w.contains("Ok(10) => r.merge_message(msg.l.get_or_insert_with(Default::default)).map_err(|e| e.in_field(\"l\"))?,"));
+    assert_eq!(2, w.matches("let mut required").count());
+}
+
+#[test]
+fn test_collection() {
+    let desc = FileDescriptor::from_bytes(br#"message L {
+        repeated int32 v = 1 [(rust.collection) = true, (rust.container) = "smallvec:4"];
+    }"#, &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.messages[0].write_impl_collection(&mut w).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("impl IntoIterator for L {\n    type Item = i32;\n    type IntoIter = ::smallvec::IntoIter<[i32; 4]>;\n"));
+    assert!(w.contains("impl<'a> IntoIterator for &'a L {\n"));
+    assert!(w.contains("        L {\n            v: iter.into_iter().collect(),\n        }\n"));
+    assert!(w.contains("impl ::std::ops::Index<usize> for L {\n    type Output = i32;\n"));
+    assert!(w.contains("        &self.v[i]\n"));
+    assert!(w.contains("    pub fn len(&self) -> usize {\n        self.v.len()\n"));
+
+    assert!(FileDescriptor::from_bytes(br#"message L { optional int32 v = 1 [(rust.collection) = true]; }"#,
+                                       &Default::default()).is_err());
+    assert!(FileDescriptor::from_bytes(br#"message L {
+        repeated int32 a = 1 [(rust.collection) = true];
+        repeated int32 b = 2 [(rust.collection) = true];
+    }"#, &Default::default()).is_err());
+}
+
+#[test]
+fn test_packed_fixed() {
+    let desc = FileDescriptor::from_bytes(br#"message M {
+        repeated double d = 1 [packed = true];
+        repeated fixed32 f = 2 [packed = true, (rust.container) = "smallvec:4"];
+    }"#, &Default::default()).unwrap();
+    let fields = &desc.messages[0].fields;
+    let mut w = Vec::new();
+    fields[0].write_match_tag(&mut w, &[]).unwrap();
+    fields[1].write_match_tag(&mut w, &[]).unwrap();
+    fields[0].write_write(&mut w, &[]).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("Ok(10) => msg.d.extend(r.read_packed_fixed::<f64>()?),\n"));
+    assert!(w.contains("Ok(18) => msg.f.extend(r.read_packed_fixed::<u32>()?),\n"));
+    assert!(w.contains("        r.write_packed_double_with_tag(10, &self.d)?;\n"));
+
+    // the other encoding is accepted as well
+    let desc = FileDescriptor::from_bytes(br#"message M {
+        repeated double d = 1 [packed = true];
+        repeated int32 i = 2;
+        repeated string s = 3;
+    }"#, &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.messages[0].write_merge_from(&mut w, &[]).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("Ok(10) => msg.d.extend(r.read_packed_fixed::<f64>()?),\n                \
+                        Ok(9) => { r.check_repeated_len(msg.d.len())?; msg.d.push(r.read_double()?); }\n"));
+    assert!(w.contains("Ok(16) => { r.check_repeated_len(msg.i.len())?; msg.i.push(r.read_int32()?); }\n                \
+                        Ok(18) => msg.i.extend(r.read_packed_repeated_field::<i32, _>(|r| r.read_int32())?),\n"));
+    assert!(!w.contains("Ok(26) => msg.s.extend"));
+}
+
+#[test]
+fn test_borrow() {
+    let desc = FileDescriptor::from_bytes(br#"message Line {
+        repeated string labels = 1 [(rust.borrow) = true];
+        optional bytes id = 2 [(rust.borrow) = true, default = "ab"];
+    }
+    message Log {
+        optional Line first = 1;
+        repeated Line lines = 2;
+        optional string host = 3;
+    }"#, &Default::default()).unwrap();
+    assert!(desc.messages.iter().all(|m| m.lifetime));
+    let mut w = Vec::new();
+    desc.messages[0].write_definition(&mut w, &[]).unwrap();
+    desc.messages[0].write_impl_message_read(&mut w, &[]).unwrap();
+    desc.messages[1].write_definition(&mut w, &[]).unwrap();
+    desc.messages[1].write_impl_message_read(&mut w, &[]).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("pub struct Line<'a> {\n    pub labels: Vec<Cow<'a, str>>,\n    pub id: Cow<'a, [u8]>,\n}\n"));
+    assert!(w.contains("impl<'a> MessageReadBorrowed<'a> for Line<'a> {\n    fn from_reader(r: &mut Reader<&'a [u8]>)"));
+    assert!(w.contains("Ok(10) => { r.check_repeated_len(msg.labels.len())?; msg.labels.push(Cow::Borrowed(r.read_string_borrowed()?)); }\n"));
+    assert!(w.contains("            id: Cow::Borrowed(&b\"ab\"[..]),\n"));
+    assert!(w.contains("    pub first: Option<Line<'a>>,\n    pub lines: Vec<Line<'a>>,\n    pub host: Option<String>,\n"));
+    assert!(w.contains("Ok(10) => r.merge_message_borrowed(msg.first.get_or_insert_with(Default::default))?,\n"));
+    assert!(w.contains("Ok(18) => { r.check_repeated_len(msg.lines.len())?; msg.lines.push(r.read_message_borrowed()?); }\n"));
+
+    assert!(FileDescriptor::from_bytes(br#"message M { optional int32 v = 1 [(rust.borrow) = true]; }"#,
+                                       &Default::default()).is_err());
+}
+
+#[test]
+fn test_intern() {
+    let desc = FileDescriptor::from_bytes(br#"message Event {
+        repeated string labels = 1 [(rust.intern) = true];
+        optional string category = 2 [(rust.intern) = true];
+    }"#, &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.write(&mut w, "intern.proto", &Default::default()).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("use std::sync::Arc;\n"));
+    assert!(w.contains("    pub labels: Vec<Arc<str>>,\n    pub category: Option<Arc<str>>,\n"));
+    assert!(w.contains("Ok(10) => { r.check_repeated_len(msg.labels.len())?; msg.labels.push(r.read_string_interned()?); }\n"));
+    assert!(w.contains("Ok(18) => msg.category = Some(r.read_string_interned()?),\n"));
+
+    for field in &["optional int32 v = 1 [(rust.intern) = true];",
+                   "optional string v = 1 [(rust.intern) = true, default = \"a\"];",
+                   "optional string v = 1 [(rust.intern) = true, (rust.borrow) = true];"] {
+        let proto = format!("message M {{ {} }}", field);
+        assert!(FileDescriptor::from_bytes(proto.as_bytes(), &Default::default()).is_err());
+    }
+}
+
+#[test]
+fn test_lazy() {
+    let desc = FileDescriptor::from_bytes(br#"message Big { required int32 id = 1; }
+    message M {
+        optional Big big = 1 [(rust.lazy) = true];
+        required Big req = 2 [(rust.lazy) = true];
+    }"#, &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.write(&mut w, "lazy.proto", &Default::default()).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("use quick_protobuf::lazy::LazyMessage;\n"));
+    assert!(w.contains("    pub big: Option<LazyMessage<Big>>,\n    pub req: LazyMessage<Big>,\n"));
+    assert!(w.contains("Ok(10) => r.merge_message(msg.big.get_or_insert_with(Default::default))?,\n"));
+    assert!(w.contains("    pub const fn size_hint() -> usize {\n        2\n    }\n"));
+
+    for field in &["optional int32 v = 1 [(rust.lazy) = true];",
+                   "repeated M v = 1 [(rust.lazy) = true];",
+                   "optional bytes v = 1 [(rust.lazy) = true];"] {
+        let proto = format!("message M {{ {} }}", field);
+        assert!(FileDescriptor::from_bytes(proto.as_bytes(), &Default::default()).is_err());
+    }
+    let config = Config { display: true, ..Default::default() };
+    assert!(FileDescriptor::from_bytes(b"message M { optional M m = 1 [(rust.lazy) = true]; }", &config).is_err());
+}
+
+#[test]
+fn test_box_it() {
+    let desc = FileDescriptor::from_bytes(br#"message Big { repeated int64 v = 1; }
+    message M {
+        optional Big big = 1 [(rust.box_it) = true];
+        optional M next = 2 [(rust.box_it) = true];
+        optional M other = 3;
+    }"#, &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.write(&mut w, "box_it.proto", &Default::default()).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("    pub big: Option<Box<Big>>,\n    pub next: Option<Box<M>>,\n    pub other: Option<Box<M>>,\n"));
+    assert!(w.contains("Ok(10) => r.merge_message(&mut **msg.big.get_or_insert_with(Default::default))?,\n"));
+
+    for field in &["optional int32 v = 1 [(rust.box_it) = true];",
+                   "required M v = 1 [(rust.box_it) = true];",
+                   "repeated M v = 1 [(rust.box_it) = true];",
+                   "optional M v = 1 [(rust.box_it) = true, (rust.lazy) = true];"] {
+        let proto = format!("message M {{ {} }}", field);
+        assert!(FileDescriptor::from_bytes(proto.as_bytes(), &Default::default()).is_err());
+    }
+}
+
+#[test]
+fn test_eq_unknown_fields() {
+    let config = Config { unknown_fields: true, ord: vec!["K".to_string()], ..Default::default() };
+    let desc = FileDescriptor::from_bytes(b"message M { optional int32 a = 1; } message K { optional int32 k = 1; }",
+                                          &config).unwrap();
+    let mut w = Vec::new();
+    desc.write(&mut w, "eq.proto", &config).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("#[derive(Debug, Default, Clone)]\npub struct M {"));
+    assert!(w.contains("#[derive(Debug, Default, Eq, Clone)]\npub struct K {"));
+    assert!(w.contains("        let eq = eq && self.a == other.a;\n        eq\n"));
+    assert!(w.contains("        ::std::hash::Hash::hash(&self.k, state);\n"));
+    assert!(w.contains("        self == other && self.unknown_fields == other.unknown_fields\n"));
+    assert!(!w.contains("self.unknown_fields.cmp"));
+}
+
+#[test]
+fn test_validate_bytes() {
+    let desc = FileDescriptor::from_bytes(br#"enum E { A = 0; B = 1; }
+    message M {
+        optional string s = 1;
+        repeated E e = 2 [packed = true];
+        optional M m = 3;
+        repeated fixed32 f = 4;
+    }"#, &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.messages[0].write_validate_bytes(&mut w, &["E"], &["E"]).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("                10 => { r.read_string_borrowed()?; }\n"));
+    assert!(w.contains("                18 => r.read_packed_for_each(|r| { let v = r.read_int32()?; \
+                        if E::from_i32(v).is_none() { return Err(::quick_protobuf::errors::ErrorKind::UnknownEnumValue(2, v)\
+                        .into()); } Ok(()) })?,\n"));
+    assert!(w.contains("                16 => { let v = r.read_int32()?;"));
+    assert!(w.contains("                26 => { r.validate_message(M::validate_fields)?; }\n"));
+    assert!(w.contains("                37 => { r.read_fixed32()?; }\n                34 => r.read_packed_for_each("));
+    assert!(w.contains("                t => r.read_unknown(t)?,\n"));
+}
+
+#[test]
+fn test_field_by_number() {
+    let desc = FileDescriptor::from_bytes(br#"enum E { A = 0; B = 1; }
+    message M {
+        required int32 a = 1;
+        optional string s = 2;
+        repeated fixed64 f = 3;
+        optional E e = 4 [default = B];
+        optional M m = 5;
+        optional bytes b = 6 [(rust.feature) = "b"];
+    }
+    message N {}"#, &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.messages[0].write_impl_field_by_number(&mut w, &["E"]).unwrap();
+    desc.messages[1].write_impl_field_by_number(&mut w, &["E"]).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("            1 => Some(FieldValue::Int64(i64::from(self.a))),\n"));
+    assert!(w.contains("            2 => self.s.as_ref().map(|v| FieldValue::String(::std::borrow::Cow::Borrowed(&**v))),\n"));
+    assert!(w.contains("            3 => Some(FieldValue::Repeated(self.f.iter().map(|v| FieldValue::Uint64(u64::from(*v))).collect())),\n"));
+    assert!(w.contains("            4 => Some(FieldValue::Enum(i32::from(self.e))),\n"));
+    assert!(w.contains("            5 => self.m.as_ref().map(|v| FieldValue::Message(&**v)),\n"));
+    assert!(w.contains("            #[cfg(feature = \"b\")]\n            6 => "));
+    assert!(w.contains("    fn field_by_number(&self, _n: u32) -> Option<FieldValue<'_>> {\n        None\n"));
+}
+
+#[test]
+fn test_tag_type() {
+    let desc = FileDescriptor::from_bytes(br#"message M {
+        required int32 id = 1;
+        optional string firstName = 2;
+        optional M next = 15;
+    }"#, &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.messages[0].write_tag_type(&mut w).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("pub struct MTag(pub u32);\n"));
+    assert!(w.contains("    pub const ID: MTag = MTag(1);\n"));
+    assert!(w.contains("    pub const FIRST_NAME: MTag = MTag(2);\n"));
+    assert!(w.contains("    pub const NEXT: MTag = MTag(15);\n"));
+    assert!(w.contains("        Ok(r.next_field()?.map(|(n, t)| (MTag(n), t)))\n"));
+}
+
+#[test]
+fn test_tag_type_keyword() {
+    let config = Config { typed_tags: true, ..Default::default() };
+    let desc = FileDescriptor::from_bytes(b"message M { optional int32 type = 1; optional int32 self = 2; }",
+                                          &config).unwrap();
+    let mut w = Vec::new();
+    desc.write(&mut w, "m.proto", &config).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("    pub r#type: Option<i32>,\n"));
+    assert!(w.contains("    pub const TYPE: MTag = MTag(1);\n"));
+    assert!(w.contains("    pub const SELF_: MTag = MTag(2);\n"));
+}
+
+#[test]
+fn test_validate() {
+    let desc = FileDescriptor::from_bytes(br#"message Item {
+        required string name = 1 [(rust.max_len) = 4, (rust.non_empty) = true];
+        optional int32 count = 2 [(rust.min) = 1, (rust.max) = 10];
+        optional float ratio = 3 [default = 0.5, (rust.max) = 1];
+    }
+    message Order {
+        optional string id = 1 [(rust.required) = true];
+        repeated Item items = 2 [(rust.non_empty) = true];
+        repeated uint64 codes = 3 [(rust.max) = 99];
+    }"#, &Default::default()).unwrap();
+    let mut w = Vec::new();
+    desc.messages[0].write_impl_validate(&mut w, &[]).unwrap();
+    desc.messages[1].write_impl_validate(&mut w, &[]).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    let c = "::quick_protobuf::validate::Constraint";
+    assert!(w.contains(&format!("        if self.name.chars().count() > 4 {{ \
+                                 errors.push(ValidationError::new(path, \"name\", {}::MaxLen(4))); }}\n", c)));
+    assert!(w.contains(&format!("        if let Some(ref v) = self.count {{\n            if *v < 1i32 {{ \
+                                 errors.push(ValidationError::new(path, \"count\", {}::Min(1.0))); }}\n", c)));
+    assert!(w.contains("        if self.ratio > 1.0f32 {"));
+    assert!(w.contains(&format!("        if self.id.is_none() {{ \
+                                 errors.push(ValidationError::new(path, \"id\", {}::Required)); }}\n", c)));
+    assert!(w.contains("            m.validate_into(&format!(\"{}items[{}].\", path, i), errors);\n"));
+    assert!(w.contains(&format!("            if *v > 99u64 {{ errors.push(ValidationError::new(path, \
+                                 &format!(\"codes[{{}}]\", i), {}::Max(99.0))); }}\n", c)));
+
+    let err = |proto: &[u8]| FileDescriptor::from_bytes(proto, &Default::default()).unwrap_err();
+    assert!(err(b"message M { optional string s = 1 [(rust.min) = 1]; }").contains("only numeric fields"));
+    assert!(err(b"message M { optional uint32 u = 1 [(rust.min) = -1]; }").contains("'-1' is not a valid uint32"));
+    assert!(err(b"message M { repeated int32 r = 1 [(rust.required) = true]; }").contains("can be required"));
+    assert!(err(b"message M { optional int32 i = 1 [(rust.max_len) = 2]; }").contains("max length"));
+}
+
+#[test]
+fn test_import() {
+    let common = || FileDescriptor::from_bytes(b"package common; enum Currency { EUR = 0; USD = 1; }
+        message Money { optional int64 cents = 1; }", &Default::default()).unwrap();
+    let desc = FileDescriptor::from_bytes_with_imports(br#"package shop; import public "common.proto";
+        message Order { optional common.Money total = 1; optional .common.Currency currency = 2 [default = USD]; }"#,
+        &Default::default(), &[("common.proto", common(), "::common")]).unwrap();
+    assert_eq!(vec!["common.proto"], desc.imports);
+    let mut w = Vec::new();
+    desc.messages[0].write_definition(&mut w, &[]).unwrap();
+    desc.messages[0].write_impl_message_read(&mut w, &[]).unwrap();
+    let w = String::from_utf8(w).unwrap();
+    assert!(w.contains("    pub total: Option<::common::Money>,\n    pub currency: ::common::Currency,\n"));
+    assert!(w.contains("Ok(16) => msg.currency = r.read_enum()?,"));
+    assert!(w.contains("            currency: ::common::Currency::USD,\n"));
+
+    assert!(FileDescriptor::from_bytes_with_imports(b"message M { optional common.Money m = 1; }", &Default::default(),
+                                                    &[("common.proto", common(), "::common")]).is_err());
+}
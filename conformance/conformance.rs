@@ -0,0 +1,377 @@
+//! Automatically generated rust module for 'conformance.proto' file
+
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use std::io::{Read, Write};
+use quick_protobuf::{MessageRead, MessageWrite, Reader, Writer, Result};
+use quick_protobuf::sizeofs::*;
+
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+#[repr(i32)]
+pub enum WireFormat {
+    UNSPECIFIED = 0,
+    PROTOBUF = 1,
+    JSON = 2,
+    JSPB = 3,
+    TEXT_FORMAT = 4,
+}
+
+impl Default for WireFormat {
+    fn default() -> Self {
+        WireFormat::UNSPECIFIED
+    }
+}
+
+impl From<i32> for WireFormat {
+    fn from(i: i32) -> Self {
+        match i {
+            0 => WireFormat::UNSPECIFIED,
+            1 => WireFormat::PROTOBUF,
+            2 => WireFormat::JSON,
+            3 => WireFormat::JSPB,
+            4 => WireFormat::TEXT_FORMAT,
+            _ => Self::default(),
+        }
+    }
+}
+
+impl From<WireFormat> for i32 {
+    fn from(v: WireFormat) -> i32 {
+        v as i32
+    }
+}
+
+impl WireFormat {
+    /// Number of the variant
+    pub const fn value(self) -> i32 {
+        self as i32
+    }
+
+    /// Variant of a number defined in the .proto file, `None` for other numbers
+    pub const fn from_i32(i: i32) -> Option<Self> {
+        match i {
+            0 => Some(WireFormat::UNSPECIFIED),
+            1 => Some(WireFormat::PROTOBUF),
+            2 => Some(WireFormat::JSON),
+            3 => Some(WireFormat::JSPB),
+            4 => Some(WireFormat::TEXT_FORMAT),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+#[repr(i32)]
+pub enum TestCategory {
+    UNSPECIFIED_TEST = 0,
+    BINARY_TEST = 1,
+    JSON_TEST = 2,
+    JSON_IGNORE_UNKNOWN_PARSING_TEST = 3,
+    JSPB_TEST = 4,
+    TEXT_FORMAT_TEST = 5,
+}
+
+impl Default for TestCategory {
+    fn default() -> Self {
+        TestCategory::UNSPECIFIED_TEST
+    }
+}
+
+impl From<i32> for TestCategory {
+    fn from(i: i32) -> Self {
+        match i {
+            0 => TestCategory::UNSPECIFIED_TEST,
+            1 => TestCategory::BINARY_TEST,
+            2 => TestCategory::JSON_TEST,
+            3 => TestCategory::JSON_IGNORE_UNKNOWN_PARSING_TEST,
+            4 => TestCategory::JSPB_TEST,
+            5 => TestCategory::TEXT_FORMAT_TEST,
+            _ => Self::default(),
+        }
+    }
+}
+
+impl From<TestCategory> for i32 {
+    fn from(v: TestCategory) -> i32 {
+        v as i32
+    }
+}
+
+impl TestCategory {
+    /// Number of the variant
+    pub const fn value(self) -> i32 {
+        self as i32
+    }
+
+    /// Variant of a number defined in the .proto file, `None` for other numbers
+    pub const fn from_i32(i: i32) -> Option<Self> {
+        match i {
+            0 => Some(TestCategory::UNSPECIFIED_TEST),
+            1 => Some(TestCategory::BINARY_TEST),
+            2 => Some(TestCategory::JSON_TEST),
+            3 => Some(TestCategory::JSON_IGNORE_UNKNOWN_PARSING_TEST),
+            4 => Some(TestCategory::JSPB_TEST),
+            5 => Some(TestCategory::TEXT_FORMAT_TEST),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ConformanceRequest {
+    pub protobuf_payload: Option<Vec<u8>>,
+    pub json_payload: Option<String>,
+    pub jspb_payload: Option<String>,
+    pub text_payload: Option<String>,
+    pub requested_output_format: Option<WireFormat>,
+    pub message_type: Option<String>,
+    pub test_category: Option<TestCategory>,
+    pub print_unknown_fields: Option<bool>,
+}
+
+impl MessageRead for ConformanceRequest {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
+        let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        let msg = self;
+        while !r.is_eof() {
+            match r.next_tag() {
+                Ok(10) => msg.protobuf_payload = Some(r.read_bytes()?),
+                Ok(18) => msg.json_payload = Some(r.read_string()?),
+                Ok(58) => msg.jspb_payload = Some(r.read_string()?),
+                Ok(66) => msg.text_payload = Some(r.read_string()?),
+                Ok(24) => msg.requested_output_format = Some(r.read_enum()?),
+                Ok(34) => msg.message_type = Some(r.read_string()?),
+                Ok(40) => msg.test_category = Some(r.read_enum()?),
+                Ok(72) => msg.print_unknown_fields = Some(r.read_bool()?),
+                Ok(t) => { r.read_unknown(t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.protobuf_payload = Default::default();
+        self.json_payload = Default::default();
+        self.jspb_payload = Default::default();
+        self.text_payload = Default::default();
+        self.requested_output_format = Default::default();
+        self.message_type = Default::default();
+        self.test_category = Default::default();
+        self.print_unknown_fields = Default::default();
+    }
+}
+
+impl MessageWrite for ConformanceRequest {
+    fn get_size(&self) -> usize {
+        self.protobuf_payload_encoded_len()
+        + self.json_payload_encoded_len()
+        + self.jspb_payload_encoded_len()
+        + self.text_payload_encoded_len()
+        + self.requested_output_format_encoded_len()
+        + self.message_type_encoded_len()
+        + self.test_category_encoded_len()
+        + self.print_unknown_fields_encoded_len()
+    }
+
+    fn write_message<W: Write>(&self, r: &mut Writer<W>) -> Result<()> {
+        let start = r.written();
+        if let Some(ref s) = self.protobuf_payload { r.write_bytes_with_tag(10, s)?; }
+        if let Some(ref s) = self.json_payload { r.write_string_with_tag(18, s)?; }
+        if let Some(ref s) = self.jspb_payload { r.write_string_with_tag(58, s)?; }
+        if let Some(ref s) = self.text_payload { r.write_string_with_tag(66, s)?; }
+        if let Some(ref s) = self.requested_output_format { r.write_enum_with_tag(24, i32::from(*s))?; }
+        if let Some(ref s) = self.message_type { r.write_string_with_tag(34, s)?; }
+        if let Some(ref s) = self.test_category { r.write_enum_with_tag(40, i32::from(*s))?; }
+        if let Some(ref s) = self.print_unknown_fields { r.write_bool_with_tag(72, *s)?; }
+        debug_assert_eq!(r.written() - start, self.get_size(), "ConformanceRequest: written size differs from get_size");
+        Ok(())
+    }
+}
+
+impl ConformanceRequest {
+    /// Size of the `protobuf_payload` field once written, tag included, 0 if it is not written
+    pub fn protobuf_payload_encoded_len(&self) -> usize {
+        self.protobuf_payload.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `json_payload` field once written, tag included, 0 if it is not written
+    pub fn json_payload_encoded_len(&self) -> usize {
+        self.json_payload.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `jspb_payload` field once written, tag included, 0 if it is not written
+    pub fn jspb_payload_encoded_len(&self) -> usize {
+        self.jspb_payload.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `text_payload` field once written, tag included, 0 if it is not written
+    pub fn text_payload_encoded_len(&self) -> usize {
+        self.text_payload.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `requested_output_format` field once written, tag included, 0 if it is not written
+    pub fn requested_output_format_encoded_len(&self) -> usize {
+        self.requested_output_format.as_ref().map_or(0, |m| 1 + sizeof_enum(i32::from(*m)))
+    }
+
+    /// Size of the `message_type` field once written, tag included, 0 if it is not written
+    pub fn message_type_encoded_len(&self) -> usize {
+        self.message_type.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `test_category` field once written, tag included, 0 if it is not written
+    pub fn test_category_encoded_len(&self) -> usize {
+        self.test_category.as_ref().map_or(0, |m| 1 + sizeof_enum(i32::from(*m)))
+    }
+
+    /// Size of the `print_unknown_fields` field once written, tag included, 0 if it is not written
+    pub fn print_unknown_fields_encoded_len(&self) -> usize {
+        self.print_unknown_fields.as_ref().map_or(0, |m| 1 + sizeof_bool(*m))
+    }
+
+    /// Lower bound of `get_size`: the minimum size of the required fields, which are always written
+    pub const fn size_hint() -> usize {
+        0
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ConformanceResponse {
+    pub parse_error: Option<String>,
+    pub serialize_error: Option<String>,
+    pub timeout_error: Option<String>,
+    pub runtime_error: Option<String>,
+    pub protobuf_payload: Option<Vec<u8>>,
+    pub json_payload: Option<String>,
+    pub skipped: Option<String>,
+    pub jspb_payload: Option<String>,
+    pub text_payload: Option<String>,
+}
+
+impl MessageRead for ConformanceResponse {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
+        let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        let msg = self;
+        while !r.is_eof() {
+            match r.next_tag() {
+                Ok(10) => msg.parse_error = Some(r.read_string()?),
+                Ok(50) => msg.serialize_error = Some(r.read_string()?),
+                Ok(74) => msg.timeout_error = Some(r.read_string()?),
+                Ok(18) => msg.runtime_error = Some(r.read_string()?),
+                Ok(26) => msg.protobuf_payload = Some(r.read_bytes()?),
+                Ok(34) => msg.json_payload = Some(r.read_string()?),
+                Ok(42) => msg.skipped = Some(r.read_string()?),
+                Ok(58) => msg.jspb_payload = Some(r.read_string()?),
+                Ok(66) => msg.text_payload = Some(r.read_string()?),
+                Ok(t) => { r.read_unknown(t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.parse_error = Default::default();
+        self.serialize_error = Default::default();
+        self.timeout_error = Default::default();
+        self.runtime_error = Default::default();
+        self.protobuf_payload = Default::default();
+        self.json_payload = Default::default();
+        self.skipped = Default::default();
+        self.jspb_payload = Default::default();
+        self.text_payload = Default::default();
+    }
+}
+
+impl MessageWrite for ConformanceResponse {
+    fn get_size(&self) -> usize {
+        self.parse_error_encoded_len()
+        + self.serialize_error_encoded_len()
+        + self.timeout_error_encoded_len()
+        + self.runtime_error_encoded_len()
+        + self.protobuf_payload_encoded_len()
+        + self.json_payload_encoded_len()
+        + self.skipped_encoded_len()
+        + self.jspb_payload_encoded_len()
+        + self.text_payload_encoded_len()
+    }
+
+    fn write_message<W: Write>(&self, r: &mut Writer<W>) -> Result<()> {
+        let start = r.written();
+        if let Some(ref s) = self.parse_error { r.write_string_with_tag(10, s)?; }
+        if let Some(ref s) = self.serialize_error { r.write_string_with_tag(50, s)?; }
+        if let Some(ref s) = self.timeout_error { r.write_string_with_tag(74, s)?; }
+        if let Some(ref s) = self.runtime_error { r.write_string_with_tag(18, s)?; }
+        if let Some(ref s) = self.protobuf_payload { r.write_bytes_with_tag(26, s)?; }
+        if let Some(ref s) = self.json_payload { r.write_string_with_tag(34, s)?; }
+        if let Some(ref s) = self.skipped { r.write_string_with_tag(42, s)?; }
+        if let Some(ref s) = self.jspb_payload { r.write_string_with_tag(58, s)?; }
+        if let Some(ref s) = self.text_payload { r.write_string_with_tag(66, s)?; }
+        debug_assert_eq!(r.written() - start, self.get_size(), "ConformanceResponse: written size differs from get_size");
+        Ok(())
+    }
+}
+
+impl ConformanceResponse {
+    /// Size of the `parse_error` field once written, tag included, 0 if it is not written
+    pub fn parse_error_encoded_len(&self) -> usize {
+        self.parse_error.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `serialize_error` field once written, tag included, 0 if it is not written
+    pub fn serialize_error_encoded_len(&self) -> usize {
+        self.serialize_error.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `timeout_error` field once written, tag included, 0 if it is not written
+    pub fn timeout_error_encoded_len(&self) -> usize {
+        self.timeout_error.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `runtime_error` field once written, tag included, 0 if it is not written
+    pub fn runtime_error_encoded_len(&self) -> usize {
+        self.runtime_error.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `protobuf_payload` field once written, tag included, 0 if it is not written
+    pub fn protobuf_payload_encoded_len(&self) -> usize {
+        self.protobuf_payload.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `json_payload` field once written, tag included, 0 if it is not written
+    pub fn json_payload_encoded_len(&self) -> usize {
+        self.json_payload.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `skipped` field once written, tag included, 0 if it is not written
+    pub fn skipped_encoded_len(&self) -> usize {
+        self.skipped.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `jspb_payload` field once written, tag included, 0 if it is not written
+    pub fn jspb_payload_encoded_len(&self) -> usize {
+        self.jspb_payload.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `text_payload` field once written, tag included, 0 if it is not written
+    pub fn text_payload_encoded_len(&self) -> usize {
+        self.text_payload.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Lower bound of `get_size`: the minimum size of the required fields, which are always written
+    pub const fn size_hint() -> usize {
+        0
+    }
+}
@@ -0,0 +1,91 @@
+//! Testee of the protobuf conformance test runner
+//!
+//! The runner writes requests on stdin and reads responses on stdout, each one
+//! prefixed by its length as a 4 bytes little endian integer. Only binary payloads
+//! are supported, other tests are reported as skipped.
+
+extern crate quick_protobuf;
+
+mod conformance;
+mod test_messages_proto2;
+mod test_messages_proto3;
+
+use std::io::{self, Read, Write};
+
+use quick_protobuf::{MessageRead, MessageWrite, Reader, ReaderOptions, Writer, Result};
+use quick_protobuf::errors::ErrorKind;
+
+use conformance::{ConformanceRequest, ConformanceResponse, WireFormat};
+
+fn main() {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let (mut stdin, mut stdout) = (stdin.lock(), stdout.lock());
+    let mut count = 0;
+    while let Some(request) = read_request(&mut stdin).expect("Cannot read request") {
+        let response = run_test(&request);
+        write_response(&mut stdout, &response).expect("Cannot write response");
+        count += 1;
+    }
+    eprintln!("conformance: received EOF from test runner after {} tests", count);
+}
+
+/// Reads the next request, or `None` once the runner closes stdin
+fn read_request<R: Read>(r: &mut R) -> Result<Option<ConformanceRequest>> {
+    let mut len = [0; 4];
+    match r.read_exact(&mut len) {
+        Ok(()) => (),
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(ErrorKind::Io(e).into()),
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    let mut bytes = vec![0; len];
+    r.read_exact(&mut bytes).map_err(ErrorKind::Io)?;
+    ConformanceRequest::from_reader(&mut Reader::from_reader(&*bytes, len)).map(Some)
+}
+
+fn write_response<W: Write>(w: &mut W, response: &ConformanceResponse) -> Result<()> {
+    let mut bytes = Vec::with_capacity(response.get_size());
+    response.write_message(&mut Writer::new(&mut bytes))?;
+    w.write_all(&(bytes.len() as u32).to_le_bytes()).map_err(ErrorKind::Io)?;
+    w.write_all(&bytes).map_err(ErrorKind::Io)?;
+    w.flush().map_err(ErrorKind::Io)?;
+    Ok(())
+}
+
+fn run_test(request: &ConformanceRequest) -> ConformanceResponse {
+    let payload = match request.protobuf_payload {
+        Some(ref p) => p,
+        None => return skipped("only protobuf input is supported"),
+    };
+    if request.requested_output_format != Some(WireFormat::PROTOBUF) {
+        return skipped("only protobuf output is supported");
+    }
+    match request.message_type.as_deref() {
+        Some("protobuf_test_messages.proto3.TestAllTypesProto3") => {
+            roundtrip::<test_messages_proto3::TestAllTypes>(payload)
+        }
+        Some("protobuf_test_messages.proto2.TestAllTypesProto2") => {
+            roundtrip::<test_messages_proto2::TestAllTypes>(payload)
+        }
+        _ => skipped("unsupported message type"),
+    }
+}
+
+/// Parses the payload as a `M` and serializes it back
+fn roundtrip<M: MessageRead + MessageWrite>(payload: &[u8]) -> ConformanceResponse {
+    let msg = match Reader::from_bytes_with_options(payload, ReaderOptions::default())
+        .and_then(|mut r| M::from_reader(&mut r)) {
+        Ok(m) => m,
+        Err(e) => return ConformanceResponse { parse_error: Some(e.to_string()), ..Default::default() },
+    };
+    let mut bytes = Vec::with_capacity(msg.get_size());
+    match msg.write_message(&mut Writer::new(&mut bytes)) {
+        Ok(()) => ConformanceResponse { protobuf_payload: Some(bytes), ..Default::default() },
+        Err(e) => ConformanceResponse { serialize_error: Some(e.to_string()), ..Default::default() },
+    }
+}
+
+fn skipped(reason: &str) -> ConformanceResponse {
+    ConformanceResponse { skipped: Some(reason.to_string()), ..Default::default() }
+}
@@ -0,0 +1,1166 @@
+//! Automatically generated rust module for 'test_messages_proto2.proto' file
+
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use std::io::{Read, Write};
+use quick_protobuf::{MessageRead, MessageWrite, Reader, Writer, Result};
+use quick_protobuf::sizeofs::*;
+use quick_protobuf::UnknownFields;
+
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+#[repr(i32)]
+pub enum NestedEnum {
+    FOO = 0,
+    BAR = 1,
+    BAZ = 2,
+    NEG = -1,
+}
+
+impl Default for NestedEnum {
+    fn default() -> Self {
+        NestedEnum::FOO
+    }
+}
+
+impl From<i32> for NestedEnum {
+    fn from(i: i32) -> Self {
+        match i {
+            0 => NestedEnum::FOO,
+            1 => NestedEnum::BAR,
+            2 => NestedEnum::BAZ,
+            -1 => NestedEnum::NEG,
+            _ => Self::default(),
+        }
+    }
+}
+
+impl From<NestedEnum> for i32 {
+    fn from(v: NestedEnum) -> i32 {
+        v as i32
+    }
+}
+
+impl NestedEnum {
+    /// Number of the variant
+    pub const fn value(self) -> i32 {
+        self as i32
+    }
+
+    /// Variant of a number defined in the .proto file, `None` for other numbers
+    pub const fn from_i32(i: i32) -> Option<Self> {
+        match i {
+            0 => Some(NestedEnum::FOO),
+            1 => Some(NestedEnum::BAR),
+            2 => Some(NestedEnum::BAZ),
+            -1 => Some(NestedEnum::NEG),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+#[repr(i32)]
+pub enum ForeignEnum {
+    FOREIGN_FOO = 0,
+    FOREIGN_BAR = 1,
+    FOREIGN_BAZ = 2,
+}
+
+impl Default for ForeignEnum {
+    fn default() -> Self {
+        ForeignEnum::FOREIGN_FOO
+    }
+}
+
+impl From<i32> for ForeignEnum {
+    fn from(i: i32) -> Self {
+        match i {
+            0 => ForeignEnum::FOREIGN_FOO,
+            1 => ForeignEnum::FOREIGN_BAR,
+            2 => ForeignEnum::FOREIGN_BAZ,
+            _ => Self::default(),
+        }
+    }
+}
+
+impl From<ForeignEnum> for i32 {
+    fn from(v: ForeignEnum) -> i32 {
+        v as i32
+    }
+}
+
+impl ForeignEnum {
+    /// Number of the variant
+    pub const fn value(self) -> i32 {
+        self as i32
+    }
+
+    /// Variant of a number defined in the .proto file, `None` for other numbers
+    pub const fn from_i32(i: i32) -> Option<Self> {
+        match i {
+            0 => Some(ForeignEnum::FOREIGN_FOO),
+            1 => Some(ForeignEnum::FOREIGN_BAR),
+            2 => Some(ForeignEnum::FOREIGN_BAZ),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct TestAllTypes {
+    pub optional_int32: Option<i32>,
+    pub optional_int64: Option<i64>,
+    pub optional_uint32: Option<u32>,
+    pub optional_uint64: Option<u64>,
+    pub optional_sint32: Option<i32>,
+    pub optional_sint64: Option<i64>,
+    pub optional_fixed32: Option<u32>,
+    pub optional_fixed64: Option<u64>,
+    pub optional_sfixed32: Option<i32>,
+    pub optional_sfixed64: Option<i64>,
+    pub optional_float: Option<f32>,
+    pub optional_double: Option<f64>,
+    pub optional_bool: Option<bool>,
+    pub optional_string: Option<String>,
+    pub optional_bytes: Option<Vec<u8>>,
+    pub optional_nested_message: Option<Box<NestedMessage>>,
+    pub optional_foreign_message: Option<ForeignMessage>,
+    pub optional_nested_enum: Option<NestedEnum>,
+    pub optional_foreign_enum: Option<ForeignEnum>,
+    pub optional_string_piece: Option<String>,
+    pub optional_cord: Option<String>,
+    pub recursive_message: Option<Box<TestAllTypes>>,
+    pub repeated_int32: Vec<i32>,
+    pub repeated_int64: Vec<i64>,
+    pub repeated_uint32: Vec<u32>,
+    pub repeated_uint64: Vec<u64>,
+    pub repeated_sint32: Vec<i32>,
+    pub repeated_sint64: Vec<i64>,
+    pub repeated_fixed32: Vec<u32>,
+    pub repeated_fixed64: Vec<u64>,
+    pub repeated_sfixed32: Vec<i32>,
+    pub repeated_sfixed64: Vec<i64>,
+    pub repeated_float: Vec<f32>,
+    pub repeated_double: Vec<f64>,
+    pub repeated_bool: Vec<bool>,
+    pub repeated_string: Vec<String>,
+    pub repeated_bytes: Vec<Vec<u8>>,
+    pub repeated_nested_message: Vec<NestedMessage>,
+    pub repeated_foreign_message: Vec<ForeignMessage>,
+    pub repeated_nested_enum: Vec<NestedEnum>,
+    pub repeated_foreign_enum: Vec<ForeignEnum>,
+    pub repeated_string_piece: Vec<String>,
+    pub repeated_cord: Vec<String>,
+    pub packed_int32: Vec<i32>,
+    pub packed_int64: Vec<i64>,
+    pub packed_uint32: Vec<u32>,
+    pub packed_uint64: Vec<u64>,
+    pub packed_sint32: Vec<i32>,
+    pub packed_sint64: Vec<i64>,
+    pub packed_fixed32: Vec<u32>,
+    pub packed_fixed64: Vec<u64>,
+    pub packed_sfixed32: Vec<i32>,
+    pub packed_sfixed64: Vec<i64>,
+    pub packed_float: Vec<f32>,
+    pub packed_double: Vec<f64>,
+    pub packed_bool: Vec<bool>,
+    pub packed_nested_enum: Vec<NestedEnum>,
+    pub unpacked_int32: Vec<i32>,
+    pub unpacked_int64: Vec<i64>,
+    pub unpacked_uint32: Vec<u32>,
+    pub unpacked_uint64: Vec<u64>,
+    pub unpacked_sint32: Vec<i32>,
+    pub unpacked_sint64: Vec<i64>,
+    pub unpacked_fixed32: Vec<u32>,
+    pub unpacked_fixed64: Vec<u64>,
+    pub unpacked_sfixed32: Vec<i32>,
+    pub unpacked_sfixed64: Vec<i64>,
+    pub unpacked_float: Vec<f32>,
+    pub unpacked_double: Vec<f64>,
+    pub unpacked_bool: Vec<bool>,
+    pub unpacked_nested_enum: Vec<NestedEnum>,
+    pub unknown_fields: UnknownFields,
+}
+
+impl PartialEq for TestAllTypes {
+    /// Compares the known fields, unknown fields are ignored (see `eq_including_unknown`)
+    fn eq(&self, other: &Self) -> bool {
+        let eq = true;
+        let eq = eq && self.optional_int32 == other.optional_int32;
+        let eq = eq && self.optional_int64 == other.optional_int64;
+        let eq = eq && self.optional_uint32 == other.optional_uint32;
+        let eq = eq && self.optional_uint64 == other.optional_uint64;
+        let eq = eq && self.optional_sint32 == other.optional_sint32;
+        let eq = eq && self.optional_sint64 == other.optional_sint64;
+        let eq = eq && self.optional_fixed32 == other.optional_fixed32;
+        let eq = eq && self.optional_fixed64 == other.optional_fixed64;
+        let eq = eq && self.optional_sfixed32 == other.optional_sfixed32;
+        let eq = eq && self.optional_sfixed64 == other.optional_sfixed64;
+        let eq = eq && self.optional_float == other.optional_float;
+        let eq = eq && self.optional_double == other.optional_double;
+        let eq = eq && self.optional_bool == other.optional_bool;
+        let eq = eq && self.optional_string == other.optional_string;
+        let eq = eq && self.optional_bytes == other.optional_bytes;
+        let eq = eq && self.optional_nested_message == other.optional_nested_message;
+        let eq = eq && self.optional_foreign_message == other.optional_foreign_message;
+        let eq = eq && self.optional_nested_enum == other.optional_nested_enum;
+        let eq = eq && self.optional_foreign_enum == other.optional_foreign_enum;
+        let eq = eq && self.optional_string_piece == other.optional_string_piece;
+        let eq = eq && self.optional_cord == other.optional_cord;
+        let eq = eq && self.recursive_message == other.recursive_message;
+        let eq = eq && self.repeated_int32 == other.repeated_int32;
+        let eq = eq && self.repeated_int64 == other.repeated_int64;
+        let eq = eq && self.repeated_uint32 == other.repeated_uint32;
+        let eq = eq && self.repeated_uint64 == other.repeated_uint64;
+        let eq = eq && self.repeated_sint32 == other.repeated_sint32;
+        let eq = eq && self.repeated_sint64 == other.repeated_sint64;
+        let eq = eq && self.repeated_fixed32 == other.repeated_fixed32;
+        let eq = eq && self.repeated_fixed64 == other.repeated_fixed64;
+        let eq = eq && self.repeated_sfixed32 == other.repeated_sfixed32;
+        let eq = eq && self.repeated_sfixed64 == other.repeated_sfixed64;
+        let eq = eq && self.repeated_float == other.repeated_float;
+        let eq = eq && self.repeated_double == other.repeated_double;
+        let eq = eq && self.repeated_bool == other.repeated_bool;
+        let eq = eq && self.repeated_string == other.repeated_string;
+        let eq = eq && self.repeated_bytes == other.repeated_bytes;
+        let eq = eq && self.repeated_nested_message == other.repeated_nested_message;
+        let eq = eq && self.repeated_foreign_message == other.repeated_foreign_message;
+        let eq = eq && self.repeated_nested_enum == other.repeated_nested_enum;
+        let eq = eq && self.repeated_foreign_enum == other.repeated_foreign_enum;
+        let eq = eq && self.repeated_string_piece == other.repeated_string_piece;
+        let eq = eq && self.repeated_cord == other.repeated_cord;
+        let eq = eq && self.packed_int32 == other.packed_int32;
+        let eq = eq && self.packed_int64 == other.packed_int64;
+        let eq = eq && self.packed_uint32 == other.packed_uint32;
+        let eq = eq && self.packed_uint64 == other.packed_uint64;
+        let eq = eq && self.packed_sint32 == other.packed_sint32;
+        let eq = eq && self.packed_sint64 == other.packed_sint64;
+        let eq = eq && self.packed_fixed32 == other.packed_fixed32;
+        let eq = eq && self.packed_fixed64 == other.packed_fixed64;
+        let eq = eq && self.packed_sfixed32 == other.packed_sfixed32;
+        let eq = eq && self.packed_sfixed64 == other.packed_sfixed64;
+        let eq = eq && self.packed_float == other.packed_float;
+        let eq = eq && self.packed_double == other.packed_double;
+        let eq = eq && self.packed_bool == other.packed_bool;
+        let eq = eq && self.packed_nested_enum == other.packed_nested_enum;
+        let eq = eq && self.unpacked_int32 == other.unpacked_int32;
+        let eq = eq && self.unpacked_int64 == other.unpacked_int64;
+        let eq = eq && self.unpacked_uint32 == other.unpacked_uint32;
+        let eq = eq && self.unpacked_uint64 == other.unpacked_uint64;
+        let eq = eq && self.unpacked_sint32 == other.unpacked_sint32;
+        let eq = eq && self.unpacked_sint64 == other.unpacked_sint64;
+        let eq = eq && self.unpacked_fixed32 == other.unpacked_fixed32;
+        let eq = eq && self.unpacked_fixed64 == other.unpacked_fixed64;
+        let eq = eq && self.unpacked_sfixed32 == other.unpacked_sfixed32;
+        let eq = eq && self.unpacked_sfixed64 == other.unpacked_sfixed64;
+        let eq = eq && self.unpacked_float == other.unpacked_float;
+        let eq = eq && self.unpacked_double == other.unpacked_double;
+        let eq = eq && self.unpacked_bool == other.unpacked_bool;
+        let eq = eq && self.unpacked_nested_enum == other.unpacked_nested_enum;
+        eq
+    }
+}
+
+impl TestAllTypes {
+    /// Compares with `other` like `==`, unknown fields included
+    pub fn eq_including_unknown(&self, other: &Self) -> bool {
+        self == other && self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl MessageRead for TestAllTypes {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
+        let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        let msg = self;
+        while !r.is_eof() {
+            match r.next_tag() {
+                Ok(8) => msg.optional_int32 = Some(r.read_int32()?),
+                Ok(16) => msg.optional_int64 = Some(r.read_int64()?),
+                Ok(24) => msg.optional_uint32 = Some(r.read_uint32()?),
+                Ok(32) => msg.optional_uint64 = Some(r.read_uint64()?),
+                Ok(40) => msg.optional_sint32 = Some(r.read_sint32()?),
+                Ok(48) => msg.optional_sint64 = Some(r.read_sint64()?),
+                Ok(61) => msg.optional_fixed32 = Some(r.read_fixed32()?),
+                Ok(65) => msg.optional_fixed64 = Some(r.read_fixed64()?),
+                Ok(77) => msg.optional_sfixed32 = Some(r.read_sfixed32()?),
+                Ok(81) => msg.optional_sfixed64 = Some(r.read_sfixed64()?),
+                Ok(93) => msg.optional_float = Some(r.read_float()?),
+                Ok(97) => msg.optional_double = Some(r.read_double()?),
+                Ok(104) => msg.optional_bool = Some(r.read_bool()?),
+                Ok(114) => msg.optional_string = Some(r.read_string()?),
+                Ok(122) => msg.optional_bytes = Some(r.read_bytes()?),
+                Ok(146) => r.merge_message(&mut **msg.optional_nested_message.get_or_insert_with(Default::default))?,
+                Ok(154) => r.merge_message(msg.optional_foreign_message.get_or_insert_with(Default::default))?,
+                Ok(168) => msg.optional_nested_enum = Some(r.read_enum()?),
+                Ok(176) => msg.optional_foreign_enum = Some(r.read_enum()?),
+                Ok(194) => msg.optional_string_piece = Some(r.read_string()?),
+                Ok(202) => msg.optional_cord = Some(r.read_string()?),
+                Ok(218) => r.merge_message(&mut **msg.recursive_message.get_or_insert_with(Default::default))?,
+                Ok(248) => { r.check_repeated_len(msg.repeated_int32.len())?; msg.repeated_int32.push(r.read_int32()?); }
+                Ok(250) => msg.repeated_int32.extend(r.read_packed_repeated_field::<i32, _>(|r| r.read_int32())?),
+                Ok(256) => { r.check_repeated_len(msg.repeated_int64.len())?; msg.repeated_int64.push(r.read_int64()?); }
+                Ok(258) => msg.repeated_int64.extend(r.read_packed_repeated_field::<i64, _>(|r| r.read_int64())?),
+                Ok(264) => { r.check_repeated_len(msg.repeated_uint32.len())?; msg.repeated_uint32.push(r.read_uint32()?); }
+                Ok(266) => msg.repeated_uint32.extend(r.read_packed_repeated_field::<u32, _>(|r| r.read_uint32())?),
+                Ok(272) => { r.check_repeated_len(msg.repeated_uint64.len())?; msg.repeated_uint64.push(r.read_uint64()?); }
+                Ok(274) => msg.repeated_uint64.extend(r.read_packed_repeated_field::<u64, _>(|r| r.read_uint64())?),
+                Ok(280) => { r.check_repeated_len(msg.repeated_sint32.len())?; msg.repeated_sint32.push(r.read_sint32()?); }
+                Ok(282) => msg.repeated_sint32.extend(r.read_packed_repeated_field::<i32, _>(|r| r.read_sint32())?),
+                Ok(288) => { r.check_repeated_len(msg.repeated_sint64.len())?; msg.repeated_sint64.push(r.read_sint64()?); }
+                Ok(290) => msg.repeated_sint64.extend(r.read_packed_repeated_field::<i64, _>(|r| r.read_sint64())?),
+                Ok(301) => { r.check_repeated_len(msg.repeated_fixed32.len())?; msg.repeated_fixed32.push(r.read_fixed32()?); }
+                Ok(298) => msg.repeated_fixed32.extend(r.read_packed_fixed::<u32>()?),
+                Ok(305) => { r.check_repeated_len(msg.repeated_fixed64.len())?; msg.repeated_fixed64.push(r.read_fixed64()?); }
+                Ok(306) => msg.repeated_fixed64.extend(r.read_packed_fixed::<u64>()?),
+                Ok(317) => { r.check_repeated_len(msg.repeated_sfixed32.len())?; msg.repeated_sfixed32.push(r.read_sfixed32()?); }
+                Ok(314) => msg.repeated_sfixed32.extend(r.read_packed_fixed::<i32>()?),
+                Ok(321) => { r.check_repeated_len(msg.repeated_sfixed64.len())?; msg.repeated_sfixed64.push(r.read_sfixed64()?); }
+                Ok(322) => msg.repeated_sfixed64.extend(r.read_packed_fixed::<i64>()?),
+                Ok(333) => { r.check_repeated_len(msg.repeated_float.len())?; msg.repeated_float.push(r.read_float()?); }
+                Ok(330) => msg.repeated_float.extend(r.read_packed_fixed::<f32>()?),
+                Ok(337) => { r.check_repeated_len(msg.repeated_double.len())?; msg.repeated_double.push(r.read_double()?); }
+                Ok(338) => msg.repeated_double.extend(r.read_packed_fixed::<f64>()?),
+                Ok(344) => { r.check_repeated_len(msg.repeated_bool.len())?; msg.repeated_bool.push(r.read_bool()?); }
+                Ok(346) => msg.repeated_bool.extend(r.read_packed_repeated_field::<bool, _>(|r| r.read_bool())?),
+                Ok(354) => { r.check_repeated_len(msg.repeated_string.len())?; msg.repeated_string.push(r.read_string()?); }
+                Ok(362) => { r.check_repeated_len(msg.repeated_bytes.len())?; msg.repeated_bytes.push(r.read_bytes()?); }
+                Ok(386) => r.read_message_to_vec(&mut msg.repeated_nested_message)?,
+                Ok(394) => r.read_message_to_vec(&mut msg.repeated_foreign_message)?,
+                Ok(408) => { r.check_repeated_len(msg.repeated_nested_enum.len())?; msg.repeated_nested_enum.push(r.read_enum()?); }
+                Ok(410) => msg.repeated_nested_enum.extend(r.read_packed_repeated_field::<NestedEnum, _>(|r| r.read_enum())?),
+                Ok(416) => { r.check_repeated_len(msg.repeated_foreign_enum.len())?; msg.repeated_foreign_enum.push(r.read_enum()?); }
+                Ok(418) => msg.repeated_foreign_enum.extend(r.read_packed_repeated_field::<ForeignEnum, _>(|r| r.read_enum())?),
+                Ok(434) => { r.check_repeated_len(msg.repeated_string_piece.len())?; msg.repeated_string_piece.push(r.read_string()?); }
+                Ok(442) => { r.check_repeated_len(msg.repeated_cord.len())?; msg.repeated_cord.push(r.read_string()?); }
+                Ok(602) => msg.packed_int32.extend(r.read_packed_repeated_field::<i32, _>(|r| r.read_int32())?),
+                Ok(600) => { r.check_repeated_len(msg.packed_int32.len())?; msg.packed_int32.push(r.read_int32()?); }
+                Ok(610) => msg.packed_int64.extend(r.read_packed_repeated_field::<i64, _>(|r| r.read_int64())?),
+                Ok(608) => { r.check_repeated_len(msg.packed_int64.len())?; msg.packed_int64.push(r.read_int64()?); }
+                Ok(618) => msg.packed_uint32.extend(r.read_packed_repeated_field::<u32, _>(|r| r.read_uint32())?),
+                Ok(616) => { r.check_repeated_len(msg.packed_uint32.len())?; msg.packed_uint32.push(r.read_uint32()?); }
+                Ok(626) => msg.packed_uint64.extend(r.read_packed_repeated_field::<u64, _>(|r| r.read_uint64())?),
+                Ok(624) => { r.check_repeated_len(msg.packed_uint64.len())?; msg.packed_uint64.push(r.read_uint64()?); }
+                Ok(634) => msg.packed_sint32.extend(r.read_packed_repeated_field::<i32, _>(|r| r.read_sint32())?),
+                Ok(632) => { r.check_repeated_len(msg.packed_sint32.len())?; msg.packed_sint32.push(r.read_sint32()?); }
+                Ok(642) => msg.packed_sint64.extend(r.read_packed_repeated_field::<i64, _>(|r| r.read_sint64())?),
+                Ok(640) => { r.check_repeated_len(msg.packed_sint64.len())?; msg.packed_sint64.push(r.read_sint64()?); }
+                Ok(650) => msg.packed_fixed32.extend(r.read_packed_fixed::<u32>()?),
+                Ok(653) => { r.check_repeated_len(msg.packed_fixed32.len())?; msg.packed_fixed32.push(r.read_fixed32()?); }
+                Ok(658) => msg.packed_fixed64.extend(r.read_packed_fixed::<u64>()?),
+                Ok(657) => { r.check_repeated_len(msg.packed_fixed64.len())?; msg.packed_fixed64.push(r.read_fixed64()?); }
+                Ok(666) => msg.packed_sfixed32.extend(r.read_packed_fixed::<i32>()?),
+                Ok(669) => { r.check_repeated_len(msg.packed_sfixed32.len())?; msg.packed_sfixed32.push(r.read_sfixed32()?); }
+                Ok(674) => msg.packed_sfixed64.extend(r.read_packed_fixed::<i64>()?),
+                Ok(673) => { r.check_repeated_len(msg.packed_sfixed64.len())?; msg.packed_sfixed64.push(r.read_sfixed64()?); }
+                Ok(682) => msg.packed_float.extend(r.read_packed_fixed::<f32>()?),
+                Ok(685) => { r.check_repeated_len(msg.packed_float.len())?; msg.packed_float.push(r.read_float()?); }
+                Ok(690) => msg.packed_double.extend(r.read_packed_fixed::<f64>()?),
+                Ok(689) => { r.check_repeated_len(msg.packed_double.len())?; msg.packed_double.push(r.read_double()?); }
+                Ok(698) => msg.packed_bool.extend(r.read_packed_repeated_field::<bool, _>(|r| r.read_bool())?),
+                Ok(696) => { r.check_repeated_len(msg.packed_bool.len())?; msg.packed_bool.push(r.read_bool()?); }
+                Ok(706) => msg.packed_nested_enum.extend(r.read_packed_repeated_field::<NestedEnum, _>(|r| r.read_enum())?),
+                Ok(704) => { r.check_repeated_len(msg.packed_nested_enum.len())?; msg.packed_nested_enum.push(r.read_enum()?); }
+                Ok(712) => { r.check_repeated_len(msg.unpacked_int32.len())?; msg.unpacked_int32.push(r.read_int32()?); }
+                Ok(714) => msg.unpacked_int32.extend(r.read_packed_repeated_field::<i32, _>(|r| r.read_int32())?),
+                Ok(720) => { r.check_repeated_len(msg.unpacked_int64.len())?; msg.unpacked_int64.push(r.read_int64()?); }
+                Ok(722) => msg.unpacked_int64.extend(r.read_packed_repeated_field::<i64, _>(|r| r.read_int64())?),
+                Ok(728) => { r.check_repeated_len(msg.unpacked_uint32.len())?; msg.unpacked_uint32.push(r.read_uint32()?); }
+                Ok(730) => msg.unpacked_uint32.extend(r.read_packed_repeated_field::<u32, _>(|r| r.read_uint32())?),
+                Ok(736) => { r.check_repeated_len(msg.unpacked_uint64.len())?; msg.unpacked_uint64.push(r.read_uint64()?); }
+                Ok(738) => msg.unpacked_uint64.extend(r.read_packed_repeated_field::<u64, _>(|r| r.read_uint64())?),
+                Ok(744) => { r.check_repeated_len(msg.unpacked_sint32.len())?; msg.unpacked_sint32.push(r.read_sint32()?); }
+                Ok(746) => msg.unpacked_sint32.extend(r.read_packed_repeated_field::<i32, _>(|r| r.read_sint32())?),
+                Ok(752) => { r.check_repeated_len(msg.unpacked_sint64.len())?; msg.unpacked_sint64.push(r.read_sint64()?); }
+                Ok(754) => msg.unpacked_sint64.extend(r.read_packed_repeated_field::<i64, _>(|r| r.read_sint64())?),
+                Ok(765) => { r.check_repeated_len(msg.unpacked_fixed32.len())?; msg.unpacked_fixed32.push(r.read_fixed32()?); }
+                Ok(762) => msg.unpacked_fixed32.extend(r.read_packed_fixed::<u32>()?),
+                Ok(769) => { r.check_repeated_len(msg.unpacked_fixed64.len())?; msg.unpacked_fixed64.push(r.read_fixed64()?); }
+                Ok(770) => msg.unpacked_fixed64.extend(r.read_packed_fixed::<u64>()?),
+                Ok(781) => { r.check_repeated_len(msg.unpacked_sfixed32.len())?; msg.unpacked_sfixed32.push(r.read_sfixed32()?); }
+                Ok(778) => msg.unpacked_sfixed32.extend(r.read_packed_fixed::<i32>()?),
+                Ok(785) => { r.check_repeated_len(msg.unpacked_sfixed64.len())?; msg.unpacked_sfixed64.push(r.read_sfixed64()?); }
+                Ok(786) => msg.unpacked_sfixed64.extend(r.read_packed_fixed::<i64>()?),
+                Ok(797) => { r.check_repeated_len(msg.unpacked_float.len())?; msg.unpacked_float.push(r.read_float()?); }
+                Ok(794) => msg.unpacked_float.extend(r.read_packed_fixed::<f32>()?),
+                Ok(801) => { r.check_repeated_len(msg.unpacked_double.len())?; msg.unpacked_double.push(r.read_double()?); }
+                Ok(802) => msg.unpacked_double.extend(r.read_packed_fixed::<f64>()?),
+                Ok(808) => { r.check_repeated_len(msg.unpacked_bool.len())?; msg.unpacked_bool.push(r.read_bool()?); }
+                Ok(810) => msg.unpacked_bool.extend(r.read_packed_repeated_field::<bool, _>(|r| r.read_bool())?),
+                Ok(816) => { r.check_repeated_len(msg.unpacked_nested_enum.len())?; msg.unpacked_nested_enum.push(r.read_enum()?); }
+                Ok(818) => msg.unpacked_nested_enum.extend(r.read_packed_repeated_field::<NestedEnum, _>(|r| r.read_enum())?),
+                Ok(t) => msg.unknown_fields.read_field(r, t)?,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.optional_int32 = Default::default();
+        self.optional_int64 = Default::default();
+        self.optional_uint32 = Default::default();
+        self.optional_uint64 = Default::default();
+        self.optional_sint32 = Default::default();
+        self.optional_sint64 = Default::default();
+        self.optional_fixed32 = Default::default();
+        self.optional_fixed64 = Default::default();
+        self.optional_sfixed32 = Default::default();
+        self.optional_sfixed64 = Default::default();
+        self.optional_float = Default::default();
+        self.optional_double = Default::default();
+        self.optional_bool = Default::default();
+        self.optional_string = Default::default();
+        self.optional_bytes = Default::default();
+        self.optional_nested_message = Default::default();
+        self.optional_foreign_message = Default::default();
+        self.optional_nested_enum = Default::default();
+        self.optional_foreign_enum = Default::default();
+        self.optional_string_piece = Default::default();
+        self.optional_cord = Default::default();
+        self.recursive_message = Default::default();
+        self.repeated_int32.clear();
+        self.repeated_int64.clear();
+        self.repeated_uint32.clear();
+        self.repeated_uint64.clear();
+        self.repeated_sint32.clear();
+        self.repeated_sint64.clear();
+        self.repeated_fixed32.clear();
+        self.repeated_fixed64.clear();
+        self.repeated_sfixed32.clear();
+        self.repeated_sfixed64.clear();
+        self.repeated_float.clear();
+        self.repeated_double.clear();
+        self.repeated_bool.clear();
+        self.repeated_string.clear();
+        self.repeated_bytes.clear();
+        self.repeated_nested_message.clear();
+        self.repeated_foreign_message.clear();
+        self.repeated_nested_enum.clear();
+        self.repeated_foreign_enum.clear();
+        self.repeated_string_piece.clear();
+        self.repeated_cord.clear();
+        self.packed_int32.clear();
+        self.packed_int64.clear();
+        self.packed_uint32.clear();
+        self.packed_uint64.clear();
+        self.packed_sint32.clear();
+        self.packed_sint64.clear();
+        self.packed_fixed32.clear();
+        self.packed_fixed64.clear();
+        self.packed_sfixed32.clear();
+        self.packed_sfixed64.clear();
+        self.packed_float.clear();
+        self.packed_double.clear();
+        self.packed_bool.clear();
+        self.packed_nested_enum.clear();
+        self.unpacked_int32.clear();
+        self.unpacked_int64.clear();
+        self.unpacked_uint32.clear();
+        self.unpacked_uint64.clear();
+        self.unpacked_sint32.clear();
+        self.unpacked_sint64.clear();
+        self.unpacked_fixed32.clear();
+        self.unpacked_fixed64.clear();
+        self.unpacked_sfixed32.clear();
+        self.unpacked_sfixed64.clear();
+        self.unpacked_float.clear();
+        self.unpacked_double.clear();
+        self.unpacked_bool.clear();
+        self.unpacked_nested_enum.clear();
+        self.unknown_fields.clear();
+    }
+
+    fn unknown_fields_mut(&mut self) -> Option<&mut UnknownFields> {
+        Some(&mut self.unknown_fields)
+    }
+}
+
+impl MessageWrite for TestAllTypes {
+    fn get_size(&self) -> usize {
+        self.optional_int32_encoded_len()
+        + self.optional_int64_encoded_len()
+        + self.optional_uint32_encoded_len()
+        + self.optional_uint64_encoded_len()
+        + self.optional_sint32_encoded_len()
+        + self.optional_sint64_encoded_len()
+        + self.optional_fixed32_encoded_len()
+        + self.optional_fixed64_encoded_len()
+        + self.optional_sfixed32_encoded_len()
+        + self.optional_sfixed64_encoded_len()
+        + self.optional_float_encoded_len()
+        + self.optional_double_encoded_len()
+        + self.optional_bool_encoded_len()
+        + self.optional_string_encoded_len()
+        + self.optional_bytes_encoded_len()
+        + self.optional_nested_message_encoded_len()
+        + self.optional_foreign_message_encoded_len()
+        + self.optional_nested_enum_encoded_len()
+        + self.optional_foreign_enum_encoded_len()
+        + self.optional_string_piece_encoded_len()
+        + self.optional_cord_encoded_len()
+        + self.recursive_message_encoded_len()
+        + self.repeated_int32_encoded_len()
+        + self.repeated_int64_encoded_len()
+        + self.repeated_uint32_encoded_len()
+        + self.repeated_uint64_encoded_len()
+        + self.repeated_sint32_encoded_len()
+        + self.repeated_sint64_encoded_len()
+        + self.repeated_fixed32_encoded_len()
+        + self.repeated_fixed64_encoded_len()
+        + self.repeated_sfixed32_encoded_len()
+        + self.repeated_sfixed64_encoded_len()
+        + self.repeated_float_encoded_len()
+        + self.repeated_double_encoded_len()
+        + self.repeated_bool_encoded_len()
+        + self.repeated_string_encoded_len()
+        + self.repeated_bytes_encoded_len()
+        + self.repeated_nested_message_encoded_len()
+        + self.repeated_foreign_message_encoded_len()
+        + self.repeated_nested_enum_encoded_len()
+        + self.repeated_foreign_enum_encoded_len()
+        + self.repeated_string_piece_encoded_len()
+        + self.repeated_cord_encoded_len()
+        + self.packed_int32_encoded_len()
+        + self.packed_int64_encoded_len()
+        + self.packed_uint32_encoded_len()
+        + self.packed_uint64_encoded_len()
+        + self.packed_sint32_encoded_len()
+        + self.packed_sint64_encoded_len()
+        + self.packed_fixed32_encoded_len()
+        + self.packed_fixed64_encoded_len()
+        + self.packed_sfixed32_encoded_len()
+        + self.packed_sfixed64_encoded_len()
+        + self.packed_float_encoded_len()
+        + self.packed_double_encoded_len()
+        + self.packed_bool_encoded_len()
+        + self.packed_nested_enum_encoded_len()
+        + self.unpacked_int32_encoded_len()
+        + self.unpacked_int64_encoded_len()
+        + self.unpacked_uint32_encoded_len()
+        + self.unpacked_uint64_encoded_len()
+        + self.unpacked_sint32_encoded_len()
+        + self.unpacked_sint64_encoded_len()
+        + self.unpacked_fixed32_encoded_len()
+        + self.unpacked_fixed64_encoded_len()
+        + self.unpacked_sfixed32_encoded_len()
+        + self.unpacked_sfixed64_encoded_len()
+        + self.unpacked_float_encoded_len()
+        + self.unpacked_double_encoded_len()
+        + self.unpacked_bool_encoded_len()
+        + self.unpacked_nested_enum_encoded_len()
+        + self.unknown_fields.get_size()
+    }
+
+    fn write_message<W: Write>(&self, r: &mut Writer<W>) -> Result<()> {
+        let start = r.written();
+        if let Some(ref s) = self.optional_int32 { r.write_int32_with_tag(8, *s)?; }
+        if let Some(ref s) = self.optional_int64 { r.write_int64_with_tag(16, *s)?; }
+        if let Some(ref s) = self.optional_uint32 { r.write_uint32_with_tag(24, *s)?; }
+        if let Some(ref s) = self.optional_uint64 { r.write_uint64_with_tag(32, *s)?; }
+        if let Some(ref s) = self.optional_sint32 { r.write_sint32_with_tag(40, *s)?; }
+        if let Some(ref s) = self.optional_sint64 { r.write_sint64_with_tag(48, *s)?; }
+        if let Some(ref s) = self.optional_fixed32 { r.write_fixed32_with_tag(61, *s)?; }
+        if let Some(ref s) = self.optional_fixed64 { r.write_fixed64_with_tag(65, *s)?; }
+        if let Some(ref s) = self.optional_sfixed32 { r.write_sfixed32_with_tag(77, *s)?; }
+        if let Some(ref s) = self.optional_sfixed64 { r.write_sfixed64_with_tag(81, *s)?; }
+        if let Some(ref s) = self.optional_float { r.write_float_with_tag(93, *s)?; }
+        if let Some(ref s) = self.optional_double { r.write_double_with_tag(97, *s)?; }
+        if let Some(ref s) = self.optional_bool { r.write_bool_with_tag(104, *s)?; }
+        if let Some(ref s) = self.optional_string { r.write_string_with_tag(114, s)?; }
+        if let Some(ref s) = self.optional_bytes { r.write_bytes_with_tag(122, s)?; }
+        if let Some(ref s) = self.optional_nested_message { r.write_message_with_tag(146, &**s)?; }
+        if let Some(ref s) = self.optional_foreign_message { r.write_message_with_tag(154, s)?; }
+        if let Some(ref s) = self.optional_nested_enum { r.write_enum_with_tag(168, i32::from(*s))?; }
+        if let Some(ref s) = self.optional_foreign_enum { r.write_enum_with_tag(176, i32::from(*s))?; }
+        if let Some(ref s) = self.optional_string_piece { r.write_string_with_tag(194, s)?; }
+        if let Some(ref s) = self.optional_cord { r.write_string_with_tag(202, s)?; }
+        if let Some(ref s) = self.recursive_message { r.write_message_with_tag(218, &**s)?; }
+        for s in &self.repeated_int32 { r.write_int32_with_tag(248, *s)? }
+        for s in &self.repeated_int64 { r.write_int64_with_tag(256, *s)? }
+        for s in &self.repeated_uint32 { r.write_uint32_with_tag(264, *s)? }
+        for s in &self.repeated_uint64 { r.write_uint64_with_tag(272, *s)? }
+        for s in &self.repeated_sint32 { r.write_sint32_with_tag(280, *s)? }
+        for s in &self.repeated_sint64 { r.write_sint64_with_tag(288, *s)? }
+        for s in &self.repeated_fixed32 { r.write_fixed32_with_tag(301, *s)? }
+        for s in &self.repeated_fixed64 { r.write_fixed64_with_tag(305, *s)? }
+        for s in &self.repeated_sfixed32 { r.write_sfixed32_with_tag(317, *s)? }
+        for s in &self.repeated_sfixed64 { r.write_sfixed64_with_tag(321, *s)? }
+        for s in &self.repeated_float { r.write_float_with_tag(333, *s)? }
+        for s in &self.repeated_double { r.write_double_with_tag(337, *s)? }
+        for s in &self.repeated_bool { r.write_bool_with_tag(344, *s)? }
+        for s in &self.repeated_string { r.write_string_with_tag(354, s)? }
+        for s in &self.repeated_bytes { r.write_bytes_with_tag(362, s)? }
+        for s in &self.repeated_nested_message { r.write_message_with_tag(386, s)? }
+        for s in &self.repeated_foreign_message { r.write_message_with_tag(394, s)? }
+        for s in &self.repeated_nested_enum { r.write_enum_with_tag(408, i32::from(*s))? }
+        for s in &self.repeated_foreign_enum { r.write_enum_with_tag(416, i32::from(*s))? }
+        for s in &self.repeated_string_piece { r.write_string_with_tag(434, s)? }
+        for s in &self.repeated_cord { r.write_string_with_tag(442, s)? }
+        r.write_packed_repeated_field_with_tag(602, &self.packed_int32, |r, m| r.write_int32(*m), &|m| sizeof_int32(*m))?;
+        r.write_packed_repeated_field_with_tag(610, &self.packed_int64, |r, m| r.write_int64(*m), &|m| sizeof_int64(*m))?;
+        r.write_packed_repeated_field_with_tag(618, &self.packed_uint32, |r, m| r.write_uint32(*m), &|m| sizeof_uint32(*m))?;
+        r.write_packed_repeated_field_with_tag(626, &self.packed_uint64, |r, m| r.write_uint64(*m), &|m| sizeof_uint64(*m))?;
+        r.write_packed_repeated_field_with_tag(634, &self.packed_sint32, |r, m| r.write_sint32(*m), &|m| sizeof_sint32(*m))?;
+        r.write_packed_repeated_field_with_tag(642, &self.packed_sint64, |r, m| r.write_sint64(*m), &|m| sizeof_sint64(*m))?;
+        r.write_packed_fixed_size_with_tag(650, &self.packed_fixed32, 4)?;
+        r.write_packed_fixed_size_with_tag(658, &self.packed_fixed64, 8)?;
+        r.write_packed_fixed_size_with_tag(666, &self.packed_sfixed32, 4)?;
+        r.write_packed_fixed_size_with_tag(674, &self.packed_sfixed64, 8)?;
+        r.write_packed_float_with_tag(682, &self.packed_float)?;
+        r.write_packed_double_with_tag(690, &self.packed_double)?;
+        r.write_packed_repeated_field_with_tag(698, &self.packed_bool, |r, m| r.write_bool(*m), &|m| sizeof_bool(*m))?;
+        r.write_packed_repeated_field_with_tag(706, &self.packed_nested_enum, |r, m| r.write_enum(i32::from(*m)), &|m| sizeof_enum(i32::from(*m)))?;
+        for s in &self.unpacked_int32 { r.write_int32_with_tag(712, *s)? }
+        for s in &self.unpacked_int64 { r.write_int64_with_tag(720, *s)? }
+        for s in &self.unpacked_uint32 { r.write_uint32_with_tag(728, *s)? }
+        for s in &self.unpacked_uint64 { r.write_uint64_with_tag(736, *s)? }
+        for s in &self.unpacked_sint32 { r.write_sint32_with_tag(744, *s)? }
+        for s in &self.unpacked_sint64 { r.write_sint64_with_tag(752, *s)? }
+        for s in &self.unpacked_fixed32 { r.write_fixed32_with_tag(765, *s)? }
+        for s in &self.unpacked_fixed64 { r.write_fixed64_with_tag(769, *s)? }
+        for s in &self.unpacked_sfixed32 { r.write_sfixed32_with_tag(781, *s)? }
+        for s in &self.unpacked_sfixed64 { r.write_sfixed64_with_tag(785, *s)? }
+        for s in &self.unpacked_float { r.write_float_with_tag(797, *s)? }
+        for s in &self.unpacked_double { r.write_double_with_tag(801, *s)? }
+        for s in &self.unpacked_bool { r.write_bool_with_tag(808, *s)? }
+        for s in &self.unpacked_nested_enum { r.write_enum_with_tag(816, i32::from(*s))? }
+        self.unknown_fields.write(r)?;
+        debug_assert_eq!(r.written() - start, self.get_size(), "TestAllTypes: written size differs from get_size");
+        Ok(())
+    }
+}
+
+impl TestAllTypes {
+    /// Size of the `optional_int32` field once written, tag included, 0 if it is not written
+    pub fn optional_int32_encoded_len(&self) -> usize {
+        self.optional_int32.as_ref().map_or(0, |m| 1 + sizeof_int32(*m))
+    }
+
+    /// Size of the `optional_int64` field once written, tag included, 0 if it is not written
+    pub fn optional_int64_encoded_len(&self) -> usize {
+        self.optional_int64.as_ref().map_or(0, |m| 1 + sizeof_int64(*m))
+    }
+
+    /// Size of the `optional_uint32` field once written, tag included, 0 if it is not written
+    pub fn optional_uint32_encoded_len(&self) -> usize {
+        self.optional_uint32.as_ref().map_or(0, |m| 1 + sizeof_uint32(*m))
+    }
+
+    /// Size of the `optional_uint64` field once written, tag included, 0 if it is not written
+    pub fn optional_uint64_encoded_len(&self) -> usize {
+        self.optional_uint64.as_ref().map_or(0, |m| 1 + sizeof_uint64(*m))
+    }
+
+    /// Size of the `optional_sint32` field once written, tag included, 0 if it is not written
+    pub fn optional_sint32_encoded_len(&self) -> usize {
+        self.optional_sint32.as_ref().map_or(0, |m| 1 + sizeof_sint32(*m))
+    }
+
+    /// Size of the `optional_sint64` field once written, tag included, 0 if it is not written
+    pub fn optional_sint64_encoded_len(&self) -> usize {
+        self.optional_sint64.as_ref().map_or(0, |m| 1 + sizeof_sint64(*m))
+    }
+
+    /// Size of the `optional_fixed32` field once written, tag included, 0 if it is not written
+    pub fn optional_fixed32_encoded_len(&self) -> usize {
+        self.optional_fixed32.as_ref().map_or(0, |m| 1 + 4)
+    }
+
+    /// Size of the `optional_fixed64` field once written, tag included, 0 if it is not written
+    pub fn optional_fixed64_encoded_len(&self) -> usize {
+        self.optional_fixed64.as_ref().map_or(0, |m| 1 + 8)
+    }
+
+    /// Size of the `optional_sfixed32` field once written, tag included, 0 if it is not written
+    pub fn optional_sfixed32_encoded_len(&self) -> usize {
+        self.optional_sfixed32.as_ref().map_or(0, |m| 1 + 4)
+    }
+
+    /// Size of the `optional_sfixed64` field once written, tag included, 0 if it is not written
+    pub fn optional_sfixed64_encoded_len(&self) -> usize {
+        self.optional_sfixed64.as_ref().map_or(0, |m| 1 + 8)
+    }
+
+    /// Size of the `optional_float` field once written, tag included, 0 if it is not written
+    pub fn optional_float_encoded_len(&self) -> usize {
+        self.optional_float.as_ref().map_or(0, |m| 1 + 4)
+    }
+
+    /// Size of the `optional_double` field once written, tag included, 0 if it is not written
+    pub fn optional_double_encoded_len(&self) -> usize {
+        self.optional_double.as_ref().map_or(0, |m| 1 + 8)
+    }
+
+    /// Size of the `optional_bool` field once written, tag included, 0 if it is not written
+    pub fn optional_bool_encoded_len(&self) -> usize {
+        self.optional_bool.as_ref().map_or(0, |m| 1 + sizeof_bool(*m))
+    }
+
+    /// Size of the `optional_string` field once written, tag included, 0 if it is not written
+    pub fn optional_string_encoded_len(&self) -> usize {
+        self.optional_string.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `optional_bytes` field once written, tag included, 0 if it is not written
+    pub fn optional_bytes_encoded_len(&self) -> usize {
+        self.optional_bytes.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `optional_nested_message` field once written, tag included, 0 if it is not written
+    pub fn optional_nested_message_encoded_len(&self) -> usize {
+        self.optional_nested_message.as_ref().map_or(0, |m| 2 + sizeof_var_length(m.get_size()))
+    }
+
+    /// Size of the `optional_foreign_message` field once written, tag included, 0 if it is not written
+    pub fn optional_foreign_message_encoded_len(&self) -> usize {
+        self.optional_foreign_message.as_ref().map_or(0, |m| 2 + sizeof_var_length(m.get_size()))
+    }
+
+    /// Size of the `optional_nested_enum` field once written, tag included, 0 if it is not written
+    pub fn optional_nested_enum_encoded_len(&self) -> usize {
+        self.optional_nested_enum.as_ref().map_or(0, |m| 2 + sizeof_enum(i32::from(*m)))
+    }
+
+    /// Size of the `optional_foreign_enum` field once written, tag included, 0 if it is not written
+    pub fn optional_foreign_enum_encoded_len(&self) -> usize {
+        self.optional_foreign_enum.as_ref().map_or(0, |m| 2 + sizeof_enum(i32::from(*m)))
+    }
+
+    /// Size of the `optional_string_piece` field once written, tag included, 0 if it is not written
+    pub fn optional_string_piece_encoded_len(&self) -> usize {
+        self.optional_string_piece.as_ref().map_or(0, |m| 2 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `optional_cord` field once written, tag included, 0 if it is not written
+    pub fn optional_cord_encoded_len(&self) -> usize {
+        self.optional_cord.as_ref().map_or(0, |m| 2 + sizeof_var_length(m.len()))
+    }
+
+    /// Size of the `recursive_message` field once written, tag included, 0 if it is not written
+    pub fn recursive_message_encoded_len(&self) -> usize {
+        self.recursive_message.as_ref().map_or(0, |m| 2 + sizeof_var_length(m.get_size()))
+    }
+
+    /// Size of the `repeated_int32` field once written, tag included, 0 if it is not written
+    pub fn repeated_int32_encoded_len(&self) -> usize {
+        self.repeated_int32.iter().map(|s| 2 + sizeof_int32(*s)).sum::<usize>()
+    }
+
+    /// Size of the `repeated_int64` field once written, tag included, 0 if it is not written
+    pub fn repeated_int64_encoded_len(&self) -> usize {
+        self.repeated_int64.iter().map(|s| 2 + sizeof_int64(*s)).sum::<usize>()
+    }
+
+    /// Size of the `repeated_uint32` field once written, tag included, 0 if it is not written
+    pub fn repeated_uint32_encoded_len(&self) -> usize {
+        self.repeated_uint32.iter().map(|s| 2 + sizeof_uint32(*s)).sum::<usize>()
+    }
+
+    /// Size of the `repeated_uint64` field once written, tag included, 0 if it is not written
+    pub fn repeated_uint64_encoded_len(&self) -> usize {
+        self.repeated_uint64.iter().map(|s| 2 + sizeof_uint64(*s)).sum::<usize>()
+    }
+
+    /// Size of the `repeated_sint32` field once written, tag included, 0 if it is not written
+    pub fn repeated_sint32_encoded_len(&self) -> usize {
+        self.repeated_sint32.iter().map(|s| 2 + sizeof_sint32(*s)).sum::<usize>()
+    }
+
+    /// Size of the `repeated_sint64` field once written, tag included, 0 if it is not written
+    pub fn repeated_sint64_encoded_len(&self) -> usize {
+        self.repeated_sint64.iter().map(|s| 2 + sizeof_sint64(*s)).sum::<usize>()
+    }
+
+    /// Size of the `repeated_fixed32` field once written, tag included, 0 if it is not written
+    pub fn repeated_fixed32_encoded_len(&self) -> usize {
+        (2 + 4) * self.repeated_fixed32.len()
+    }
+
+    /// Size of the `repeated_fixed64` field once written, tag included, 0 if it is not written
+    pub fn repeated_fixed64_encoded_len(&self) -> usize {
+        (2 + 8) * self.repeated_fixed64.len()
+    }
+
+    /// Size of the `repeated_sfixed32` field once written, tag included, 0 if it is not written
+    pub fn repeated_sfixed32_encoded_len(&self) -> usize {
+        (2 + 4) * self.repeated_sfixed32.len()
+    }
+
+    /// Size of the `repeated_sfixed64` field once written, tag included, 0 if it is not written
+    pub fn repeated_sfixed64_encoded_len(&self) -> usize {
+        (2 + 8) * self.repeated_sfixed64.len()
+    }
+
+    /// Size of the `repeated_float` field once written, tag included, 0 if it is not written
+    pub fn repeated_float_encoded_len(&self) -> usize {
+        (2 + 4) * self.repeated_float.len()
+    }
+
+    /// Size of the `repeated_double` field once written, tag included, 0 if it is not written
+    pub fn repeated_double_encoded_len(&self) -> usize {
+        (2 + 8) * self.repeated_double.len()
+    }
+
+    /// Size of the `repeated_bool` field once written, tag included, 0 if it is not written
+    pub fn repeated_bool_encoded_len(&self) -> usize {
+        self.repeated_bool.iter().map(|s| 2 + sizeof_bool(*s)).sum::<usize>()
+    }
+
+    /// Size of the `repeated_string` field once written, tag included, 0 if it is not written
+    pub fn repeated_string_encoded_len(&self) -> usize {
+        self.repeated_string.iter().map(|s| 2 + sizeof_var_length(s.len())).sum::<usize>()
+    }
+
+    /// Size of the `repeated_bytes` field once written, tag included, 0 if it is not written
+    pub fn repeated_bytes_encoded_len(&self) -> usize {
+        self.repeated_bytes.iter().map(|s| 2 + sizeof_var_length(s.len())).sum::<usize>()
+    }
+
+    /// Size of the `repeated_nested_message` field once written, tag included, 0 if it is not written
+    pub fn repeated_nested_message_encoded_len(&self) -> usize {
+        self.repeated_nested_message.iter().map(|s| 2 + sizeof_var_length(s.get_size())).sum::<usize>()
+    }
+
+    /// Size of the `repeated_foreign_message` field once written, tag included, 0 if it is not written
+    pub fn repeated_foreign_message_encoded_len(&self) -> usize {
+        self.repeated_foreign_message.iter().map(|s| 2 + sizeof_var_length(s.get_size())).sum::<usize>()
+    }
+
+    /// Size of the `repeated_nested_enum` field once written, tag included, 0 if it is not written
+    pub fn repeated_nested_enum_encoded_len(&self) -> usize {
+        self.repeated_nested_enum.iter().map(|s| 2 + sizeof_enum(i32::from(*s))).sum::<usize>()
+    }
+
+    /// Size of the `repeated_foreign_enum` field once written, tag included, 0 if it is not written
+    pub fn repeated_foreign_enum_encoded_len(&self) -> usize {
+        self.repeated_foreign_enum.iter().map(|s| 2 + sizeof_enum(i32::from(*s))).sum::<usize>()
+    }
+
+    /// Size of the `repeated_string_piece` field once written, tag included, 0 if it is not written
+    pub fn repeated_string_piece_encoded_len(&self) -> usize {
+        self.repeated_string_piece.iter().map(|s| 2 + sizeof_var_length(s.len())).sum::<usize>()
+    }
+
+    /// Size of the `repeated_cord` field once written, tag included, 0 if it is not written
+    pub fn repeated_cord_encoded_len(&self) -> usize {
+        self.repeated_cord.iter().map(|s| 2 + sizeof_var_length(s.len())).sum::<usize>()
+    }
+
+    /// Size of the `packed_int32` field once written, tag included, 0 if it is not written
+    pub fn packed_int32_encoded_len(&self) -> usize {
+        if self.packed_int32.is_empty() { 0 } else { 2 + sizeof_var_length(self.packed_int32.iter().map(|s| sizeof_int32(*s)).sum::<usize>()) }
+    }
+
+    /// Size of the `packed_int64` field once written, tag included, 0 if it is not written
+    pub fn packed_int64_encoded_len(&self) -> usize {
+        if self.packed_int64.is_empty() { 0 } else { 2 + sizeof_var_length(self.packed_int64.iter().map(|s| sizeof_int64(*s)).sum::<usize>()) }
+    }
+
+    /// Size of the `packed_uint32` field once written, tag included, 0 if it is not written
+    pub fn packed_uint32_encoded_len(&self) -> usize {
+        if self.packed_uint32.is_empty() { 0 } else { 2 + sizeof_var_length(self.packed_uint32.iter().map(|s| sizeof_uint32(*s)).sum::<usize>()) }
+    }
+
+    /// Size of the `packed_uint64` field once written, tag included, 0 if it is not written
+    pub fn packed_uint64_encoded_len(&self) -> usize {
+        if self.packed_uint64.is_empty() { 0 } else { 2 + sizeof_var_length(self.packed_uint64.iter().map(|s| sizeof_uint64(*s)).sum::<usize>()) }
+    }
+
+    /// Size of the `packed_sint32` field once written, tag included, 0 if it is not written
+    pub fn packed_sint32_encoded_len(&self) -> usize {
+        if self.packed_sint32.is_empty() { 0 } else { 2 + sizeof_var_length(self.packed_sint32.iter().map(|s| sizeof_sint32(*s)).sum::<usize>()) }
+    }
+
+    /// Size of the `packed_sint64` field once written, tag included, 0 if it is not written
+    pub fn packed_sint64_encoded_len(&self) -> usize {
+        if self.packed_sint64.is_empty() { 0 } else { 2 + sizeof_var_length(self.packed_sint64.iter().map(|s| sizeof_sint64(*s)).sum::<usize>()) }
+    }
+
+    /// Size of the `packed_fixed32` field once written, tag included, 0 if it is not written
+    pub fn packed_fixed32_encoded_len(&self) -> usize {
+        if self.packed_fixed32.is_empty() { 0 } else { 2 + sizeof_var_length(self.packed_fixed32.len() * 4) }
+    }
+
+    /// Size of the `packed_fixed64` field once written, tag included, 0 if it is not written
+    pub fn packed_fixed64_encoded_len(&self) -> usize {
+        if self.packed_fixed64.is_empty() { 0 } else { 2 + sizeof_var_length(self.packed_fixed64.len() * 8) }
+    }
+
+    /// Size of the `packed_sfixed32` field once written, tag included, 0 if it is not written
+    pub fn packed_sfixed32_encoded_len(&self) -> usize {
+        if self.packed_sfixed32.is_empty() { 0 } else { 2 + sizeof_var_length(self.packed_sfixed32.len() * 4) }
+    }
+
+    /// Size of the `packed_sfixed64` field once written, tag included, 0 if it is not written
+    pub fn packed_sfixed64_encoded_len(&self) -> usize {
+        if self.packed_sfixed64.is_empty() { 0 } else { 2 + sizeof_var_length(self.packed_sfixed64.len() * 8) }
+    }
+
+    /// Size of the `packed_float` field once written, tag included, 0 if it is not written
+    pub fn packed_float_encoded_len(&self) -> usize {
+        if self.packed_float.is_empty() { 0 } else { 2 + sizeof_var_length(self.packed_float.len() * 4) }
+    }
+
+    /// Size of the `packed_double` field once written, tag included, 0 if it is not written
+    pub fn packed_double_encoded_len(&self) -> usize {
+        if self.packed_double.is_empty() { 0 } else { 2 + sizeof_var_length(self.packed_double.len() * 8) }
+    }
+
+    /// Size of the `packed_bool` field once written, tag included, 0 if it is not written
+    pub fn packed_bool_encoded_len(&self) -> usize {
+        if self.packed_bool.is_empty() { 0 } else { 2 + sizeof_var_length(self.packed_bool.iter().map(|s| sizeof_bool(*s)).sum::<usize>()) }
+    }
+
+    /// Size of the `packed_nested_enum` field once written, tag included, 0 if it is not written
+    pub fn packed_nested_enum_encoded_len(&self) -> usize {
+        if self.packed_nested_enum.is_empty() { 0 } else { 2 + sizeof_var_length(self.packed_nested_enum.iter().map(|s| sizeof_enum(i32::from(*s))).sum::<usize>()) }
+    }
+
+    /// Size of the `unpacked_int32` field once written, tag included, 0 if it is not written
+    pub fn unpacked_int32_encoded_len(&self) -> usize {
+        self.unpacked_int32.iter().map(|s| 2 + sizeof_int32(*s)).sum::<usize>()
+    }
+
+    /// Size of the `unpacked_int64` field once written, tag included, 0 if it is not written
+    pub fn unpacked_int64_encoded_len(&self) -> usize {
+        self.unpacked_int64.iter().map(|s| 2 + sizeof_int64(*s)).sum::<usize>()
+    }
+
+    /// Size of the `unpacked_uint32` field once written, tag included, 0 if it is not written
+    pub fn unpacked_uint32_encoded_len(&self) -> usize {
+        self.unpacked_uint32.iter().map(|s| 2 + sizeof_uint32(*s)).sum::<usize>()
+    }
+
+    /// Size of the `unpacked_uint64` field once written, tag included, 0 if it is not written
+    pub fn unpacked_uint64_encoded_len(&self) -> usize {
+        self.unpacked_uint64.iter().map(|s| 2 + sizeof_uint64(*s)).sum::<usize>()
+    }
+
+    /// Size of the `unpacked_sint32` field once written, tag included, 0 if it is not written
+    pub fn unpacked_sint32_encoded_len(&self) -> usize {
+        self.unpacked_sint32.iter().map(|s| 2 + sizeof_sint32(*s)).sum::<usize>()
+    }
+
+    /// Size of the `unpacked_sint64` field once written, tag included, 0 if it is not written
+    pub fn unpacked_sint64_encoded_len(&self) -> usize {
+        self.unpacked_sint64.iter().map(|s| 2 + sizeof_sint64(*s)).sum::<usize>()
+    }
+
+    /// Size of the `unpacked_fixed32` field once written, tag included, 0 if it is not written
+    pub fn unpacked_fixed32_encoded_len(&self) -> usize {
+        (2 + 4) * self.unpacked_fixed32.len()
+    }
+
+    /// Size of the `unpacked_fixed64` field once written, tag included, 0 if it is not written
+    pub fn unpacked_fixed64_encoded_len(&self) -> usize {
+        (2 + 8) * self.unpacked_fixed64.len()
+    }
+
+    /// Size of the `unpacked_sfixed32` field once written, tag included, 0 if it is not written
+    pub fn unpacked_sfixed32_encoded_len(&self) -> usize {
+        (2 + 4) * self.unpacked_sfixed32.len()
+    }
+
+    /// Size of the `unpacked_sfixed64` field once written, tag included, 0 if it is not written
+    pub fn unpacked_sfixed64_encoded_len(&self) -> usize {
+        (2 + 8) * self.unpacked_sfixed64.len()
+    }
+
+    /// Size of the `unpacked_float` field once written, tag included, 0 if it is not written
+    pub fn unpacked_float_encoded_len(&self) -> usize {
+        (2 + 4) * self.unpacked_float.len()
+    }
+
+    /// Size of the `unpacked_double` field once written, tag included, 0 if it is not written
+    pub fn unpacked_double_encoded_len(&self) -> usize {
+        (2 + 8) * self.unpacked_double.len()
+    }
+
+    /// Size of the `unpacked_bool` field once written, tag included, 0 if it is not written
+    pub fn unpacked_bool_encoded_len(&self) -> usize {
+        self.unpacked_bool.iter().map(|s| 2 + sizeof_bool(*s)).sum::<usize>()
+    }
+
+    /// Size of the `unpacked_nested_enum` field once written, tag included, 0 if it is not written
+    pub fn unpacked_nested_enum_encoded_len(&self) -> usize {
+        self.unpacked_nested_enum.iter().map(|s| 2 + sizeof_enum(i32::from(*s))).sum::<usize>()
+    }
+
+    /// Lower bound of `get_size`: the minimum size of the required fields, which are always written
+    pub const fn size_hint() -> usize {
+        0
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct NestedMessage {
+    pub a: Option<i32>,
+    pub corecursive: Option<Box<TestAllTypes>>,
+    pub unknown_fields: UnknownFields,
+}
+
+impl PartialEq for NestedMessage {
+    /// Compares the known fields, unknown fields are ignored (see `eq_including_unknown`)
+    fn eq(&self, other: &Self) -> bool {
+        let eq = true;
+        let eq = eq && self.a == other.a;
+        let eq = eq && self.corecursive == other.corecursive;
+        eq
+    }
+}
+
+impl NestedMessage {
+    /// Compares with `other` like `==`, unknown fields included
+    pub fn eq_including_unknown(&self, other: &Self) -> bool {
+        self == other && self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl MessageRead for NestedMessage {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
+        let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        let msg = self;
+        while !r.is_eof() {
+            match r.next_tag() {
+                Ok(8) => msg.a = Some(r.read_int32()?),
+                Ok(18) => r.merge_message(&mut **msg.corecursive.get_or_insert_with(Default::default))?,
+                Ok(t) => msg.unknown_fields.read_field(r, t)?,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.a = Default::default();
+        self.corecursive = Default::default();
+        self.unknown_fields.clear();
+    }
+
+    fn unknown_fields_mut(&mut self) -> Option<&mut UnknownFields> {
+        Some(&mut self.unknown_fields)
+    }
+}
+
+impl MessageWrite for NestedMessage {
+    fn get_size(&self) -> usize {
+        self.a_encoded_len()
+        + self.corecursive_encoded_len()
+        + self.unknown_fields.get_size()
+    }
+
+    fn write_message<W: Write>(&self, r: &mut Writer<W>) -> Result<()> {
+        let start = r.written();
+        if let Some(ref s) = self.a { r.write_int32_with_tag(8, *s)?; }
+        if let Some(ref s) = self.corecursive { r.write_message_with_tag(18, &**s)?; }
+        self.unknown_fields.write(r)?;
+        debug_assert_eq!(r.written() - start, self.get_size(), "NestedMessage: written size differs from get_size");
+        Ok(())
+    }
+}
+
+impl NestedMessage {
+    /// Size of the `a` field once written, tag included, 0 if it is not written
+    pub fn a_encoded_len(&self) -> usize {
+        self.a.as_ref().map_or(0, |m| 1 + sizeof_int32(*m))
+    }
+
+    /// Size of the `corecursive` field once written, tag included, 0 if it is not written
+    pub fn corecursive_encoded_len(&self) -> usize {
+        self.corecursive.as_ref().map_or(0, |m| 1 + sizeof_var_length(m.get_size()))
+    }
+
+    /// Lower bound of `get_size`: the minimum size of the required fields, which are always written
+    pub const fn size_hint() -> usize {
+        0
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ForeignMessage {
+    pub c: Option<i32>,
+    pub unknown_fields: UnknownFields,
+}
+
+impl PartialEq for ForeignMessage {
+    /// Compares the known fields, unknown fields are ignored (see `eq_including_unknown`)
+    fn eq(&self, other: &Self) -> bool {
+        let eq = true;
+        let eq = eq && self.c == other.c;
+        eq
+    }
+}
+
+impl ForeignMessage {
+    /// Compares with `other` like `==`, unknown fields included
+    pub fn eq_including_unknown(&self, other: &Self) -> bool {
+        self == other && self.unknown_fields == other.unknown_fields
+    }
+}
+
+impl MessageRead for ForeignMessage {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
+        let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        let msg = self;
+        while !r.is_eof() {
+            match r.next_tag() {
+                Ok(8) => msg.c = Some(r.read_int32()?),
+                Ok(t) => msg.unknown_fields.read_field(r, t)?,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.c = Default::default();
+        self.unknown_fields.clear();
+    }
+
+    fn unknown_fields_mut(&mut self) -> Option<&mut UnknownFields> {
+        Some(&mut self.unknown_fields)
+    }
+}
+
+impl MessageWrite for ForeignMessage {
+    fn get_size(&self) -> usize {
+        self.c_encoded_len()
+        + self.unknown_fields.get_size()
+    }
+
+    fn write_message<W: Write>(&self, r: &mut Writer<W>) -> Result<()> {
+        let start = r.written();
+        if let Some(ref s) = self.c { r.write_int32_with_tag(8, *s)?; }
+        self.unknown_fields.write(r)?;
+        debug_assert_eq!(r.written() - start, self.get_size(), "ForeignMessage: written size differs from get_size");
+        Ok(())
+    }
+}
+
+impl ForeignMessage {
+    /// Size of the `c` field once written, tag included, 0 if it is not written
+    pub fn c_encoded_len(&self) -> usize {
+        self.c.as_ref().map_or(0, |m| 1 + sizeof_int32(*m))
+    }
+
+    /// Lower bound of `get_size`: the minimum size of the required fields, which are always written
+    pub const fn size_hint() -> usize {
+        0
+    }
+}
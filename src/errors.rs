@@ -1,32 +1,241 @@
-//! A module to handle all errors via error-chain crate
+//! A module to handle all errors
+//!
+//! `Error` implements `std::error::Error + Send + Sync`, with the io and utf8 errors it wraps as
+//! its `source`, so that it can be wrapped in the error types of other crates (`thiserror`,
+//! `anyhow` ...). New kinds of errors may be added: `ErrorKind` is `#[non_exhaustive]`
 
-#![allow(missing_docs)]
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::str::Utf8Error;
+use std::string::FromUtf8Error;
 
-error_chain! {
-    foreign_links {
-        Io(::std::io::Error);
-        Utf8(::std::string::FromUtf8Error);
-        StrUtf8(::std::str::Utf8Error);
-    }
-    errors {
-        Deprecated(feat: &'static str) {
-            description("deprecated feature")
-            display("feature '{}' has been deprecated", feat)
+/// Convenient wrapper around `std::Result`
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// The kind of an `Error`
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Error of the underlying reader or writer
+    Io(io::Error),
+    /// Invalid utf8 bytes converted to a `String`
+    Utf8(FromUtf8Error),
+    /// Invalid utf8 bytes converted to a `str`
+    StrUtf8(Utf8Error),
+    /// Generic error with a message
+    Msg(String),
+    /// The input ends before the end of a value, more bytes are needed
+    UnexpectedEof,
+    /// A deprecated feature is used
+    Deprecated(&'static str),
+    /// A tag has a wire type greater than 5
+    UnknownWireType(u8),
+    /// A tag (value) with field number 0 or the reserved wire type 6 or 7
+    InvalidTag(u32),
+    /// An end group tag of a field number without a matching start group tag
+    UnmatchedEndGroup(u32),
+    /// A map entry with a key or a value of an unexpected tag
+    MapEntryInvalid(u32),
+    /// A tagged union record with an unknown discriminator
+    UnknownDiscriminator(u8),
+    /// A line of invalid base64, `LineDelimitedReader`
+    Base64(usize),
+    /// The input is longer than the length (in bytes) given to read it, or its message overruns it
+    LengthMismatch(usize),
+    /// A varint longer than 10 bytes
+    Varint,
+    /// The end of the input is reached
+    Eof,
+    /// The output exceeds its maximum size, in bytes
+    SizeLimit(usize),
+    /// The input (length, maximum length) is too large
+    InputTooLarge(usize, usize),
+    /// A length which does not fit in `usize`
+    LengthOverflow(u64),
+    /// A field (length, maximum allocation) is too large to be allocated
+    AllocLimit(usize, usize),
+    /// A field (length, bytes left) overruns its enclosing message
+    WrongLen(usize, usize),
+    /// A packed field (length, item size) whose length is not a multiple of its values size
+    PackedLength(usize, usize),
+    /// A packed field (length) whose last value does not end with the field
+    PackedTruncated(usize),
+    /// A repeated field has more than this many elements
+    RepeatedLimit(usize),
+    /// The input has more than this many messages
+    MessageLimit(usize),
+    /// Messages are nested more than this many levels deep
+    RecursionLimit(usize),
+    /// A buffer (needed, available bytes) is too small
+    BufferTooSmall(usize, usize),
+    /// A string (field number, position of the first invalid byte) is not valid utf8
+    InvalidUtf8(u32, usize),
+    /// A field (field number, value) of a closed enum has a value it does not define
+    UnknownEnumValue(u32, i32),
+    /// A required field, by its path from the read message, is missing
+    MissingRequired(String),
+    /// A field which must be set to convert a message is not
+    MissingField(&'static str),
+    /// A message cannot be parsed
+    ParseMessage(String),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErrorKind::Io(ref err) => write!(f, "io error: {}", err),
+            ErrorKind::Utf8(ref err) => err.fmt(f),
+            ErrorKind::StrUtf8(ref err) => err.fmt(f),
+            ErrorKind::Msg(ref s) => f.write_str(s),
+            ErrorKind::UnexpectedEof => f.write_str("unexpected end of input, more bytes are needed"),
+            ErrorKind::Deprecated(feat) => write!(f, "feature '{}' has been deprecated", feat),
+            ErrorKind::UnknownWireType(t) => write!(f, "wire type must be less than 6, found {}", t),
+            ErrorKind::InvalidTag(tag) => {
+                write!(f, "invalid tag {} of field {} and wire type {}", tag, tag >> 3, tag & 0x7)
+            }
+            ErrorKind::UnmatchedEndGroup(field_number) => {
+                write!(f, "unexpected end group tag for field {}", field_number)
+            }
+            ErrorKind::MapEntryInvalid(tag) => write!(f, "map entry key or value with unexpected tag {}", tag),
+            ErrorKind::UnknownDiscriminator(d) => write!(f, "unknown record discriminator {}", d),
+            ErrorKind::Base64(line) => write!(f, "invalid base64 on line {}", line),
+            ErrorKind::LengthMismatch(len) => write!(f, "input does not end with its message of {} bytes", len),
+            ErrorKind::Varint => f.write_str("cannot decode varint"),
+            ErrorKind::Eof => f.write_str("unexpected end of file"),
+            ErrorKind::SizeLimit(max_len) => write!(f, "output exceeds the maximum size of {} bytes", max_len),
+            ErrorKind::InputTooLarge(len, max_len) => {
+                write!(f, "input of {} bytes exceeds the maximum length of {} bytes", len, max_len)
+            }
+            ErrorKind::LengthOverflow(len) => write!(f, "length of {} bytes does not fit in usize", len),
+            ErrorKind::AllocLimit(len, max_alloc) => {
+                write!(f, "field of {} bytes exceeds the maximum allocation of {} bytes", len, max_alloc)
+            }
+            ErrorKind::WrongLen(len, left) => {
+                write!(f, "field of {} bytes overruns its enclosing message, which has {} bytes left", len, left)
+            }
+            ErrorKind::PackedLength(len, item_size) => {
+                write!(f, "packed field of {} bytes is not a multiple of its {} bytes values", len, item_size)
+            }
+            ErrorKind::PackedTruncated(len) => {
+                write!(f, "packed field of {} bytes does not end with its last value", len)
+            }
+            ErrorKind::RepeatedLimit(max) => write!(f, "repeated field has more than {} elements", max),
+            ErrorKind::MessageLimit(max) => write!(f, "input has more than {} messages", max),
+            ErrorKind::RecursionLimit(max_depth) => {
+                write!(f, "messages are nested more than {} levels deep", max_depth)
+            }
+            ErrorKind::BufferTooSmall(needed, available) => {
+                write!(f, "buffer too small: needed {} bytes, only {} available", needed, available)
+            }
+            ErrorKind::InvalidUtf8(field_number, position) => {
+                write!(f, "string of field {} is not valid utf8 from byte {}", field_number, position)
+            }
+            ErrorKind::UnknownEnumValue(field_number, value) => {
+                write!(f, "enum field {} has the undefined value {}", field_number, value)
+            }
+            ErrorKind::MissingRequired(ref path) => write!(f, "required field '{}' is missing", path),
+            ErrorKind::MissingField(name) => write!(f, "field '{}' is not set", name),
+            ErrorKind::ParseMessage(ref s) => write!(f, "error while parsing message: {}", s),
         }
-        UnknownWireType(t: u8) {
-            description("unknown wire type")
-            display("wire type must be less than 6, found {}", t)
+    }
+}
+
+/// The error of all the operations of this crate
+#[derive(Debug)]
+pub struct Error(ErrorKind);
+
+impl Error {
+    /// Creates an error of a kind
+    pub fn from_kind(kind: ErrorKind) -> Error {
+        Error(kind)
+    }
+
+    /// Gets the kind of the error
+    pub fn kind(&self) -> &ErrorKind {
+        &self.0
+    }
+
+    /// Converts into the kind of the error
+    pub fn into_kind(self) -> ErrorKind {
+        self.0
+    }
+
+    /// Prefixes the path of a `MissingRequired` error with the field of the nested message it
+    /// occurred in, e.g. `inner.id`, other errors are left as is
+    pub fn in_field(self, name: &str) -> Error {
+        match self.0 {
+            ErrorKind::MissingRequired(ref path) => ErrorKind::MissingRequired(format!("{}.{}", name, path)).into(),
+            _ => self,
         }
-        Varint {
-            description("cannot decode varint")
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self.0 {
+            ErrorKind::Io(ref err) => Some(err),
+            ErrorKind::Utf8(ref err) => Some(err),
+            ErrorKind::StrUtf8(ref err) => Some(err),
+            _ => None,
         }
-        Eof {
-            description("unexpected end of file")
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Error {
+        Error(kind)
+    }
+}
+
+/// `io::ErrorKind::UnexpectedEof` means more bytes are needed, not a failure of the underlying `Read`
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => ErrorKind::UnexpectedEof.into(),
+            _ => ErrorKind::Io(e).into(),
         }
-        ParseMessage(s: String) {
-            description("error while parsing message")
-            display("error while parsing message: {}", s)
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(e: FromUtf8Error) -> Error {
+        ErrorKind::Utf8(e).into()
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(e: Utf8Error) -> Error {
+        ErrorKind::StrUtf8(e).into()
+    }
+}
+
+impl<'a> From<&'a str> for Error {
+    fn from(s: &'a str) -> Error {
+        ErrorKind::Msg(s.to_string()).into()
+    }
+}
+
+impl From<String> for Error {
+    fn from(s: String) -> Error {
+        ErrorKind::Msg(s).into()
+    }
+}
 
+/// Errors are converted to `io::Error`s, e.g. in `Read` or `Write` implementations, keeping io
+/// errors as they are
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        match e.0 {
+            ErrorKind::Io(err) => err,
+            ErrorKind::UnexpectedEof => io::ErrorKind::UnexpectedEof.into(),
+            kind => io::Error::new(io::ErrorKind::InvalidData, Error(kind)),
         }
     }
 }
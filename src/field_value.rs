@@ -0,0 +1,40 @@
+//! A module to access the fields of a message by number, without a descriptor
+//!
+//! Generated code (pb-rs `--field-values` option) implements `FieldByNumber` on messages, e.g. for
+//! generic logging or redaction of fields whose numbers are only known at runtime
+
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+/// The value of a field, integers widened to 64 bits
+#[derive(Debug, Clone)]
+pub enum FieldValue<'a> {
+    /// `int32`, `int64`, `sint32`, `sint64`, `sfixed32` and `sfixed64` fields
+    Int64(i64),
+    /// `uint32`, `uint64`, `fixed32` and `fixed64` fields
+    Uint64(u64),
+    /// `float` and `double` fields
+    Double(f64),
+    /// `bool` fields
+    Bool(bool),
+    /// Enum fields, as their number
+    Enum(i32),
+    /// `string` fields
+    String(Cow<'a, str>),
+    /// `bytes` fields
+    Bytes(Cow<'a, [u8]>),
+    /// Message fields, whose fields can be accessed in turn
+    Message(&'a dyn FieldByNumber),
+    /// The values of a repeated field, in order
+    Repeated(Vec<FieldValue<'a>>),
+}
+
+/// A message whose fields can be accessed by number
+pub trait FieldByNumber: Debug {
+
+    /// Gets the value of the field number `n`, `None` if the message has no such field or if it
+    /// is not set (optional fields without default)
+    ///
+    /// Fields of a user type (pb-rs `--type` option) are converted to their protobuf value
+    fn field_by_number(&self, n: u32) -> Option<FieldValue<'_>>;
+}
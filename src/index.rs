@@ -0,0 +1,74 @@
+//! A module to locate the fields of an encoded message, to read them later on demand
+//!
+//! `Reader::index_fields` walks a message once, without reading the values, and records
+//! where each field is: a lazy view of the message only decodes the fields it accesses
+
+use std::ops::Range;
+
+use reader::{Reader, WireType};
+
+/// The location of a field in an encoded message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedField {
+    /// Number of the field
+    pub number: u32,
+    /// Wire type of the field
+    pub wire_type: WireType,
+    /// Offset of the tag of the field
+    pub start: usize,
+    /// Byte range of the value, after the tag (length prefix included, as expected by `read_*`)
+    pub value: Range<usize>,
+}
+
+impl IndexedField {
+
+    /// Creates a reader of the value of the field, `bytes` being the indexed message
+    ///
+    /// e.g. `field.reader(bytes).read_string()`, or `read_message` for a nested message
+    pub fn reader<'a>(&self, bytes: &'a [u8]) -> Reader<&'a [u8]> {
+        let value = &bytes[self.value.clone()];
+        Reader::from_reader(value, value.len())
+    }
+}
+
+/// The locations of the fields of an encoded message, in the order they are found
+///
+/// Offsets are relative to the position of the reader when `Reader::index_fields` is called,
+/// i.e. they are indexes in the slice of the message if it is read from its first byte
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldIndex {
+    fields: Vec<IndexedField>,
+}
+
+impl FieldIndex {
+
+    /// Adds the location of the next field
+    pub(crate) fn push(&mut self, field: IndexedField) {
+        self.fields.push(field);
+    }
+
+    /// Gets the last occurrence of a field, the one whose value is kept for a singular field
+    pub fn get(&self, number: u32) -> Option<&IndexedField> {
+        self.fields.iter().rev().find(|f| f.number == number)
+    }
+
+    /// Iterates over all the occurrences of a field, e.g. the items of a repeated field
+    pub fn get_all(&self, number: u32) -> impl Iterator<Item = &IndexedField> {
+        self.fields.iter().filter(move |f| f.number == number)
+    }
+
+    /// Iterates over all the fields
+    pub fn iter(&self) -> impl Iterator<Item = &IndexedField> {
+        self.fields.iter()
+    }
+
+    /// Gets the number of fields, each occurrence of a repeated field counting as one
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Checks if the message has no field
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
@@ -0,0 +1,70 @@
+//! A module to store equal strings once
+//!
+//! Generated code (pb-rs `(rust.intern)` option) reads `string` fields as `Arc<str>` with
+//! `Reader::read_string_interned`, through the `Interner` of the reader: all the equal strings
+//! read by a reader share the same storage, e.g. for repeated labels or categories. Strings are
+//! `Arc`s so that readers, and messages, can be sent to other threads
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::str::{self, Utf8Error};
+
+use errors::{Error, Result};
+
+/// A set of shared strings, each distinct string being stored once
+#[derive(Debug, Default)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+    /// Bytes of the string being read, reused from one string to the next
+    buf: Vec<u8>,
+}
+
+impl Interner {
+
+    /// Creates a new empty `Interner`
+    pub fn new() -> Interner {
+        Interner::default()
+    }
+
+    /// Gets the shared copy of `s`, storing it if it is not there yet
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(arc) = self.strings.get(s) {
+            return arc.clone();
+        }
+        let arc = Arc::<str>::from(s);
+        self.strings.insert(arc.clone());
+        arc
+    }
+
+    /// Interns the utf8 string read into a buffer of `len` bytes by `read`, failing with the
+    /// error given by `invalid` if it is not valid utf8
+    pub(crate) fn intern_with<F, I>(&mut self, len: usize, read: F, invalid: I) -> Result<Arc<str>>
+        where F: FnOnce(&mut [u8]) -> Result<()>,
+              I: FnOnce(Utf8Error) -> Error,
+    {
+        let mut buf = ::std::mem::take(&mut self.buf);
+        buf.clear();
+        buf.resize(len, 0);
+        let arc = read(&mut buf).and_then(|_| match str::from_utf8(&buf) {
+            Ok(s) => Ok(self.intern(s)),
+            Err(e) => Err(invalid(e)),
+        });
+        self.buf = buf;
+        arc
+    }
+
+    /// Gets the number of distinct strings
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Checks if no string has been interned
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Forgets all the strings, which are freed once they are not used anymore
+    pub fn clear(&mut self) {
+        self.strings.clear();
+    }
+}
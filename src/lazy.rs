@@ -0,0 +1,133 @@
+//! A module to read nested messages lazily
+//!
+//! Generated code (pb-rs `(rust.lazy)` field option) stores such message fields as a
+//! `LazyMessage`: the message is only parsed when it is accessed, and written back as is otherwise
+
+use std::cell::OnceCell;
+use std::io::{Read, Write};
+
+use errors::Result;
+use message::{MessageRead, MessageWrite};
+use reader::Reader;
+use writer::Writer;
+
+/// A message kept as its encoded bytes, parsed on first access
+///
+/// Parsing uses the default `ReaderOptions`, not the ones of the reader the bytes were read with.
+/// Once mutably accessed, the message is written from its fields instead of the bytes
+#[derive(Debug, Clone)]
+pub struct LazyMessage<M> {
+    /// Encoded message, `None` once the message may have been modified
+    bytes: Option<Vec<u8>>,
+    message: OnceCell<M>,
+}
+
+impl<M> Default for LazyMessage<M> {
+    fn default() -> Self {
+        LazyMessage::from_bytes(Vec::new())
+    }
+}
+
+impl<M> LazyMessage<M> {
+
+    /// Creates a `LazyMessage` from an encoded message, without parsing it
+    pub fn from_bytes(bytes: Vec<u8>) -> LazyMessage<M> {
+        LazyMessage { bytes: Some(bytes), message: OnceCell::new() }
+    }
+
+    /// Creates a `LazyMessage` of an already parsed message
+    pub fn new(message: M) -> LazyMessage<M> {
+        LazyMessage { bytes: None, message: OnceCell::from(message) }
+    }
+
+    /// Gets the encoded message, `None` once it has been mutably accessed
+    pub fn raw(&self) -> Option<&[u8]> {
+        self.bytes.as_deref()
+    }
+
+    /// Checks if the message has been parsed
+    pub fn is_parsed(&self) -> bool {
+        self.message.get().is_some()
+    }
+}
+
+impl<M: MessageRead> LazyMessage<M> {
+
+    /// Gets the message, parsing it on first access
+    ///
+    /// Parsing errors are returned on each access, until the bytes are replaced
+    pub fn get(&self) -> Result<&M> {
+        if let Some(m) = self.message.get() {
+            return Ok(m);
+        }
+        let bytes = self.bytes.as_ref().expect("a message without bytes is parsed");
+        let m = M::from_reader(&mut Reader::from_reader(&**bytes, bytes.len()))?;
+        Ok(self.message.get_or_init(|| m))
+    }
+
+    /// Gets the message mutably, parsing it on first access, it is then written from its fields
+    pub fn get_mut(&mut self) -> Result<&mut M> {
+        self.get()?;
+        self.bytes = None;
+        Ok(self.message.get_mut().expect("message has just been parsed"))
+    }
+
+    /// Converts into the message, parsing it if it has not been yet
+    pub fn into_inner(self) -> Result<M> {
+        self.get()?;
+        Ok(self.message.into_inner().expect("message has just been parsed"))
+    }
+}
+
+impl<M: MessageRead> MessageRead for LazyMessage<M> {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
+        let mut bytes = Vec::new();
+        r.read_remaining_raw(&mut bytes)?;
+        Ok(LazyMessage::from_bytes(bytes))
+    }
+
+    /// Appends the fields to the encoded message, which merges them when it is parsed, or merges
+    /// them into the message if it has been mutably accessed
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        match self.bytes {
+            Some(ref mut bytes) => {
+                self.message = OnceCell::new();
+                r.read_remaining_raw(bytes)
+            }
+            None => self.message.get_mut().expect("a message without bytes is parsed").merge_from(r),
+        }
+    }
+}
+
+impl<M: MessageWrite> MessageWrite for LazyMessage<M> {
+    fn write_message<W: Write>(&self, w: &mut Writer<W>) -> Result<()> {
+        match (self.raw(), self.message.get()) {
+            (Some(bytes), _) => w.write_raw_bytes(bytes),
+            (None, Some(m)) => m.write_message(w),
+            (None, None) => unreachable!("a message without bytes is parsed"),
+        }
+    }
+
+    fn get_size(&self) -> usize {
+        match (self.raw(), self.message.get()) {
+            (Some(bytes), _) => bytes.len(),
+            (None, Some(m)) => m.get_size(),
+            (None, None) => unreachable!("a message without bytes is parsed"),
+        }
+    }
+}
+
+/// Messages are equal if their bytes are, or else if they parse to equal messages
+impl<M: MessageRead + PartialEq> PartialEq for LazyMessage<M> {
+    fn eq(&self, other: &Self) -> bool {
+        if let (Some(a), Some(b)) = (self.raw(), other.raw()) {
+            if a == b {
+                return true;
+            }
+        }
+        match (self.get(), other.get()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
+}
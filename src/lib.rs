@@ -4,11 +4,10 @@
 
 #![deny(missing_docs)]
 
-#![recursion_limit = "1024"]
 #![allow(dead_code)]
+// `is_multiple_of` needs rust 1.87
+#![allow(clippy::manual_is_multiple_of)]
 
-#[macro_use]
-extern crate error_chain;
 extern crate byteorder;
 
 pub mod errors;
@@ -16,8 +15,21 @@ pub mod message;
 pub mod reader;
 pub mod writer;
 pub mod sizeofs;
+pub mod unknown;
+pub mod text;
+pub mod line_delimited;
+pub mod validate;
+pub mod intern;
+pub mod index;
+pub mod message_set;
+pub mod field_value;
+pub mod lazy;
+pub mod pool;
 
 pub use errors::Result;
-pub use message::{MessageRead, MessageWrite};
-pub use reader::Reader;
+pub use message::{MessageRead, MessageReadBorrowed, MessageWrite, ProstMessage, ProtoConvert};
+pub use reader::{Reader, ReaderOptions};
 pub use writer::Writer;
+pub use unknown::UnknownFields;
+pub use validate::{Validate, ValidationError};
+pub use intern::Interner;
@@ -0,0 +1,119 @@
+//! A module to read and write streams of base64 encoded messages, one per line
+//!
+//! Such streams are common in line oriented pipelines (logs, text files, stdin/stdout)
+
+use std::io::{BufRead, Write};
+
+use errors::{Result, ErrorKind};
+use message::{MessageRead, MessageWrite};
+use reader::Reader;
+use writer::Writer;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A reader of base64 encoded messages, one per line
+///
+/// A blank line is an empty message (written as such by `LineDelimitedWriter`).
+/// An invalid line fails with an error but does not stop the stream: the next call reads the next line
+pub struct LineDelimitedReader<R> {
+    inner: R,
+    line: String,
+    line_number: usize,
+}
+
+impl<R: BufRead> LineDelimitedReader<R> {
+
+    /// Creates a new `LineDelimitedReader`
+    pub fn new(r: R) -> LineDelimitedReader<R> {
+        LineDelimitedReader { inner: r, line: String::new(), line_number: 0 }
+    }
+
+    /// Reads the message of the next line, `None` at the end of the input
+    pub fn read_message<M: MessageRead>(&mut self) -> Result<Option<M>> {
+        self.line.clear();
+        if self.inner.read_line(&mut self.line)? == 0 {
+            return Ok(None);
+        }
+        self.line_number += 1;
+        let bytes = decode(self.line.trim().as_bytes()).ok_or(ErrorKind::Base64(self.line_number))?;
+        let mut reader = Reader::from_reader(&*bytes, bytes.len());
+        M::from_reader(&mut reader).map(Some)
+    }
+
+    /// Gets the number of the last line read, starting at 1
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// Gets the underlying reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+/// A writer of base64 encoded messages, one per line
+pub struct LineDelimitedWriter<W> {
+    inner: W,
+}
+
+impl<W: Write> LineDelimitedWriter<W> {
+
+    /// Creates a new `LineDelimitedWriter`
+    pub fn new(w: W) -> LineDelimitedWriter<W> {
+        LineDelimitedWriter { inner: w }
+    }
+
+    /// Writes a message on its own line, terminated by `\n`
+    pub fn write_message<M: MessageWrite>(&mut self, m: &M) -> Result<()> {
+        let mut bytes = Vec::with_capacity(m.get_size());
+        m.write_message(&mut Writer::new(&mut bytes))?;
+        let mut line = encode(&bytes);
+        line.push(b'\n');
+        self.inner.write_all(&line).map_err(|e| e.into())
+    }
+
+    /// Gets the underlying writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Encodes `bytes` in padded standard base64
+pub(crate) fn encode(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len().div_ceil(3) * 4 + 1);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) & 0x3F]);
+            } else {
+                out.push(b'=');
+            }
+        }
+    }
+    out
+}
+
+/// Decodes standard base64, with or without padding
+pub(crate) fn decode(line: &[u8]) -> Option<Vec<u8>> {
+    let line = match line.iter().position(|&c| c == b'=') {
+        Some(p) if line[p..].iter().all(|&c| c == b'=') && line.len() % 4 == 0 => &line[..p],
+        Some(_) => return None,
+        None => line,
+    };
+    if line.len() % 4 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(line.len() * 3 / 4);
+    for chunk in line.chunks(4) {
+        let mut n = 0;
+        for (i, &c) in chunk.iter().enumerate() {
+            n |= (ALPHABET.iter().position(|&a| a == c)? as u32) << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            out.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(out)
+}
@@ -6,9 +6,10 @@ use std::io::{Read, BufReader, Write, BufWriter};
 use std::path::Path;
 use std::fs::File;
 
-use errors::Result;
+use errors::{Result, ErrorKind};
 use reader::Reader;
 use writer::Writer;
+use unknown::UnknownFields;
 
 /// A trait to handle deserialization based on parsed `Field`s
 pub trait MessageRead: Sized {
@@ -19,11 +20,50 @@ pub trait MessageRead: Sized {
     /// out of .proto file
     fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self>;
 
+    /// Reads fields from a `Reader` into `self`
+    ///
+    /// Generated code overrides existing fields and appends to repeated ones,
+    /// reusing their allocations. The default implementation replaces `self` entirely
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        *self = Self::from_reader(r)?;
+        Ok(())
+    }
+
+    /// Reads the fields of an encoded message into `self`, with the `merge_from` semantics
+    ///
+    /// Singular fields are overwritten, repeated fields appended and nested messages merged, e.g.
+    /// to apply incremental updates encoded as partial messages
+    fn merge_from_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.merge_from(&mut Reader::from_reader(bytes, bytes.len()))
+    }
+
+    /// Gets the unknown fields of the message, if it keeps them
+    ///
+    /// Fields left in the length of a nested message by `merge_from` are read into them,
+    /// otherwise they are skipped
+    fn unknown_fields_mut(&mut self) -> Option<&mut UnknownFields> {
+        None
+    }
+
+    /// Resets all fields to their default value
+    ///
+    /// Generated code keeps the allocations of repeated fields, to be reused by `merge_from`
+    fn clear(&mut self) where Self: Default {
+        *self = Self::default();
+    }
+
+    /// Creates Message out of the `len` bytes of any `Read` (file, socket ...)
+    ///
+    /// Convenient method for the top `Message` in the hierarchy of binary messages
+    fn decode_from_reader<R: Read>(r: R, len: usize) -> Result<Self> {
+        Self::from_reader(&mut Reader::from_reader(r, len))
+    }
+
     /// Creates Message out of a file
     ///
     /// Convenient method for the top `Message` in the hierarchy of binary messages
     fn from_file<P: AsRef<Path>>(p: P) -> Result<Self> {
-        let len = p.as_ref().metadata()?.len() as usize;
+        let len = file_len(p.as_ref())?;
         let file = BufReader::new(File::open(p)?);
         let mut reader = Reader::from_reader(file, len);
         Self::from_reader(&mut reader)
@@ -36,7 +76,7 @@ pub trait MessageRead: Sized {
     /// The file is _entirely_ read into memory before being parsed, 
     /// which boost performances but uses twice as much of memory
     fn from_file_for_speed<P: AsRef<Path>>(p: P) -> Result<Self> {
-        let len = p.as_ref().metadata()?.len() as usize;
+        let len = file_len(p.as_ref())?;
         let v = {
             let mut v = Vec::with_capacity(len);
             let mut r = File::open(p)?;
@@ -49,6 +89,49 @@ pub trait MessageRead: Sized {
     }
 }
 
+/// Reads boxed messages, e.g. nested in recursive messages
+impl<M: MessageRead> MessageRead for Box<M> {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
+        M::from_reader(r).map(Box::new)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        (**self).merge_from(r)
+    }
+
+    fn unknown_fields_mut(&mut self) -> Option<&mut UnknownFields> {
+        (**self).unknown_fields_mut()
+    }
+}
+
+/// A trait to deserialize messages borrowing (some of) their fields from the input
+///
+/// Implemented by generated code instead of `MessageRead` for messages with `string` or `bytes`
+/// fields borrowed as `Cow` (pb-rs `(rust.borrow)` option), which can only be read from a slice
+pub trait MessageReadBorrowed<'a>: Sized {
+
+    /// Creates `Self` from a `Reader` of the input slice
+    fn from_reader(r: &mut Reader<&'a [u8]>) -> Result<Self>;
+
+    /// Reads fields from a `Reader` into `self`
+    ///
+    /// The default implementation replaces `self` entirely
+    fn merge_from(&mut self, r: &mut Reader<&'a [u8]>) -> Result<()> {
+        *self = Self::from_reader(r)?;
+        Ok(())
+    }
+
+    /// Resets all fields to their default value
+    fn clear(&mut self) where Self: Default {
+        *self = Self::default();
+    }
+
+    /// Creates `Self` borrowing from `bytes`
+    fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+        Self::from_reader(&mut Reader::from_reader(bytes, bytes.len()))
+    }
+}
+
 /// A trait to handle deserialization based on parsed `Field`s
 pub trait MessageWrite: Sized {
 
@@ -58,6 +141,11 @@ pub trait MessageWrite: Sized {
     /// Computes necessary binary size of self once serialized in protobuf
     fn get_size(&self) -> usize;
 
+    /// Writes self, without length prefix, into any `Write` (file, socket ...)
+    fn encode_to_writer<W: Write>(&self, w: W) -> Result<()> {
+        self.write_message(&mut Writer::new(w))
+    }
+
     /// Writes self into a file
     fn write_file<P: AsRef<Path>>(&self, p: P) -> Result<()> {
         let file = BufWriter::new(File::create(p)?);
@@ -65,3 +153,71 @@ pub trait MessageWrite: Sized {
         self.write_message(&mut writer)
     }
 }
+
+/// The methods of the `prost::Message` trait, on top of `MessageRead` and `MessageWrite`
+///
+/// Generated code (pb-rs `--prost` option) implements it on messages, so that code written against
+/// prost messages can use them as is. `clear` is `MessageRead::clear`
+pub trait ProstMessage: MessageRead + MessageWrite {
+
+    /// Appends the encoded message to `buf`, without length prefix
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.reserve(self.get_size());
+        self.write_message(&mut Writer::new(buf))
+    }
+
+    /// Encodes the message into a new `Vec`
+    fn encode_to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode(&mut buf).expect("writing into a Vec cannot fail");
+        buf
+    }
+
+    /// Decodes a message from all of `buf`
+    fn decode(buf: &[u8]) -> Result<Self> {
+        Self::from_reader(&mut Reader::from_reader(buf, buf.len()))
+    }
+
+    /// Decodes the fields of `buf` and merges them into `self`
+    fn merge(&mut self, buf: &[u8]) -> Result<()> {
+        self.merge_from(&mut Reader::from_reader(buf, buf.len()))
+    }
+
+    /// Gets the length of the encoded message, `MessageWrite::get_size`
+    fn encoded_len(&self) -> usize {
+        self.get_size()
+    }
+}
+
+/// Gets the length of a file, failing if it doesn't fit in `usize` (files over 4 GB on 32-bit targets)
+fn file_len(p: &Path) -> Result<usize> {
+    let len = p.metadata()?.len();
+    if len > usize::MAX as u64 {
+        return Err(ErrorKind::LengthOverflow(len).into());
+    }
+    Ok(len as usize)
+}
+
+/// A conversion between a user type and the rust type of a protobuf field
+/// (`Vec<u8>` for `bytes`, `String`, `i64` for `int64` ...)
+///
+/// Generated code uses it for fields with a user type (pb-rs `--type` option),
+/// e.g. to read a `bytes` field directly as a uuid
+pub trait ProtoConvert<P>: Sized {
+
+    /// Converts a read value, fails if it is not valid for this type
+    fn from_proto(p: P) -> Result<Self>;
+
+    /// Converts into the value to write
+    fn to_proto(&self) -> P;
+}
+
+/// Type state of a required field in a generated builder: the field has been set
+///
+/// Builders (pb-rs `--builders` option) only implement `build` once all their required fields are `Set`
+#[derive(Debug)]
+pub struct Set;
+
+/// Type state of a required field in a generated builder: the field has not been set yet
+#[derive(Debug)]
+pub struct Unset;
@@ -0,0 +1,115 @@
+//! A module to read and write the legacy MessageSet wire format
+//!
+//! Messages with the proto2 `message_set_wire_format` option are encoded as a repeated group
+//! `Item = 1 { required int32 type_id = 2; required bytes message = 3; }`, each item holding a
+//! message of the type identified by `type_id`. This format is deprecated, it is only supported
+//! to communicate with legacy services: use `Any` or extensions-free messages otherwise
+
+use std::io::{Read, Write};
+
+use errors::Result;
+use message::{MessageRead, MessageWrite};
+use reader::Reader;
+use writer::Writer;
+use sizeofs::{sizeof_varint, sizeof_var_length};
+
+/// Tag of the start of an item group
+pub(crate) const ITEM_START: u32 = 1 << 3 | 3;
+/// Tag of the end of an item group
+pub(crate) const ITEM_END: u32 = 1 << 3 | 4;
+/// Tag of the type id of an item
+pub(crate) const TYPE_ID: u32 = 2 << 3;
+/// Tag of the message of an item
+pub(crate) const MESSAGE: u32 = 3 << 3 | 2;
+
+/// The encoded messages of a MessageSet (legacy), keyed by type id, in the order they have been
+/// read or inserted
+///
+/// Read with `Reader::read_message_set` and written with `Writer::write_message_set`, or as any
+/// message through `MessageRead` and `MessageWrite`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MessageSet {
+    items: Vec<(u32, Vec<u8>)>,
+}
+
+impl MessageSet {
+
+    /// Creates an empty `MessageSet`
+    pub fn new() -> MessageSet {
+        MessageSet::default()
+    }
+
+    /// Gets the encoded message of `type_id`
+    pub fn get_raw(&self, type_id: u32) -> Option<&[u8]> {
+        self.items.iter().find(|i| i.0 == type_id).map(|i| &*i.1)
+    }
+
+    /// Reads the message of `type_id`, `None` if there is none
+    pub fn get<M: MessageRead>(&self, type_id: u32) -> Option<Result<M>> {
+        self.get_raw(type_id).map(|bytes| M::from_reader(&mut Reader::from_reader(bytes, bytes.len())))
+    }
+
+    /// Sets the encoded message of `type_id`, replacing the previous one
+    pub fn insert_raw(&mut self, type_id: u32, bytes: Vec<u8>) {
+        match self.items.iter_mut().find(|i| i.0 == type_id) {
+            Some(item) => item.1 = bytes,
+            None => self.items.push((type_id, bytes)),
+        }
+    }
+
+    /// Sets the message of `type_id`, replacing the previous one
+    pub fn insert<M: MessageWrite>(&mut self, type_id: u32, m: &M) -> Result<()> {
+        let mut bytes = Vec::with_capacity(m.get_size());
+        m.write_message(&mut Writer::new(&mut bytes))?;
+        self.insert_raw(type_id, bytes);
+        Ok(())
+    }
+
+    /// Appends `bytes` to the encoded message of `type_id`, which merges them into it
+    ///
+    /// Items with the same type id are merged when they are read
+    pub(crate) fn merge_raw(&mut self, type_id: u32, bytes: Vec<u8>) {
+        match self.items.iter_mut().find(|i| i.0 == type_id) {
+            Some(item) => item.1.extend_from_slice(&bytes),
+            None => self.items.push((type_id, bytes)),
+        }
+    }
+
+    /// Removes the message of `type_id`, returning it if there was one
+    pub fn remove(&mut self, type_id: u32) -> Option<Vec<u8>> {
+        let i = self.items.iter().position(|i| i.0 == type_id)?;
+        Some(self.items.remove(i).1)
+    }
+
+    /// Iterates over all the items as (type id, encoded message)
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &[u8])> {
+        self.items.iter().map(|i| (i.0, &*i.1))
+    }
+
+    /// Gets the number of items
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Checks if there is no item
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl MessageRead for MessageSet {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
+        r.read_message_set()
+    }
+}
+
+impl MessageWrite for MessageSet {
+    fn write_message<W: Write>(&self, w: &mut Writer<W>) -> Result<()> {
+        w.write_message_set(self)
+    }
+
+    fn get_size(&self) -> usize {
+        // start and end group tags, type id and message tags are 1 byte each
+        self.items.iter().map(|i| 4 + sizeof_varint(i.0 as u64) + sizeof_var_length(i.1.len())).sum()
+    }
+}
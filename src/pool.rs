@@ -0,0 +1,256 @@
+//! A module to reuse the allocations of messages across reads
+//!
+//! A `Pool` hands out cleared messages to read into, and takes them back once processed.
+//! Generated code (pb-rs `pool` container) stores repeated message fields as a `PooledVec`,
+//! which keeps its cleared messages to read into them again: a message cleared by the pool
+//! retains the capacity of its repeated fields at every level of nesting
+
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::iter::FromIterator;
+use std::ops::{Deref, DerefMut};
+
+use errors::Result;
+use message::MessageRead;
+use reader::Reader;
+
+/// A pool of cleared messages
+///
+/// ```ignore
+/// let mut pool = Pool::new();
+/// for bytes in batch {
+///     let msg: Foo = pool.read_message(&mut Reader::from_reader(bytes, bytes.len()))?;
+///     process(&msg);
+///     pool.put(msg);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Pool<M> {
+    free: Vec<M>,
+}
+
+impl<M> Default for Pool<M> {
+    fn default() -> Self {
+        Pool::new()
+    }
+}
+
+impl<M> Pool<M> {
+
+    /// Creates an empty pool
+    pub fn new() -> Pool<M> {
+        Pool { free: Vec::new() }
+    }
+
+    /// Gets the number of messages available in the pool
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Checks if the pool has no message available
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
+}
+
+impl<M: MessageRead + Default> Pool<M> {
+
+    /// Gets a cleared message from the pool, or a new one if the pool is empty
+    pub fn get(&mut self) -> M {
+        self.free.pop().unwrap_or_default()
+    }
+
+    /// Clears `m` and returns it to the pool, keeping its allocations
+    pub fn put(&mut self, mut m: M) {
+        m.clear();
+        self.free.push(m);
+    }
+
+    /// Reads a message, without length prefix, into a message of the pool
+    ///
+    /// On failure, the message is returned to the pool
+    pub fn read_message<R: Read>(&mut self, r: &mut Reader<R>) -> Result<M> {
+        let mut m = self.get();
+        match m.merge_from(r) {
+            Ok(()) => Ok(m),
+            Err(e) => {
+                self.put(m);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// A `Vec` of messages keeping its messages once cleared, to read into them again
+///
+/// Dereferences to the slice of its messages. `clear` and `truncate` keep the removed messages as
+/// spares, which `push_with` reads into, so that their own allocations are reused. Spares are
+/// cleared before being reused, not when removed, and are dropped by `release_spares`
+#[derive(Clone)]
+pub struct PooledVec<M> {
+    items: Vec<M>,
+    len: usize,
+}
+
+impl<M> Default for PooledVec<M> {
+    fn default() -> Self {
+        PooledVec::new()
+    }
+}
+
+impl<M> PooledVec<M> {
+
+    /// Creates an empty `PooledVec`
+    pub fn new() -> PooledVec<M> {
+        PooledVec { items: Vec::new(), len: 0 }
+    }
+
+    /// Appends a message, dropping a spare if any
+    pub fn push(&mut self, m: M) {
+        if self.len < self.items.len() {
+            self.items[self.len] = m;
+        } else {
+            self.items.push(m);
+        }
+        self.len += 1;
+    }
+
+    /// Removes the messages after the first `len` ones, keeping them as spares
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// Removes all the messages, keeping them as spares
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Gets the number of spare messages
+    pub fn spares(&self) -> usize {
+        self.items.len() - self.len
+    }
+
+    /// Drops the spare messages
+    pub fn release_spares(&mut self) {
+        self.items.truncate(self.len);
+    }
+}
+
+impl<M: MessageRead + Default> PooledVec<M> {
+
+    /// Appends a message set by `read`, from a cleared spare or a new message
+    ///
+    /// If `read` fails, the message is kept as a spare and not appended
+    pub fn push_with<F: FnOnce(&mut M) -> Result<()>>(&mut self, read: F) -> Result<()> {
+        if self.len < self.items.len() {
+            self.items[self.len].clear();
+        } else {
+            self.items.push(M::default());
+        }
+        read(&mut self.items[self.len])?;
+        self.len += 1;
+        Ok(())
+    }
+}
+
+impl<M> Deref for PooledVec<M> {
+    type Target = [M];
+
+    fn deref(&self) -> &[M] {
+        &self.items[..self.len]
+    }
+}
+
+impl<M> DerefMut for PooledVec<M> {
+    fn deref_mut(&mut self) -> &mut [M] {
+        &mut self.items[..self.len]
+    }
+}
+
+impl<M: ::std::fmt::Debug> ::std::fmt::Debug for PooledVec<M> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<M: PartialEq> PartialEq for PooledVec<M> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<M: Eq> Eq for PooledVec<M> {}
+
+impl<M: PartialOrd> PartialOrd for PooledVec<M> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<M: Ord> Ord for PooledVec<M> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<M: Hash> Hash for PooledVec<M> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<M> From<Vec<M>> for PooledVec<M> {
+    fn from(items: Vec<M>) -> Self {
+        let len = items.len();
+        PooledVec { items, len }
+    }
+}
+
+impl<M> From<PooledVec<M>> for Vec<M> {
+    fn from(mut v: PooledVec<M>) -> Self {
+        v.release_spares();
+        v.items
+    }
+}
+
+impl<M> FromIterator<M> for PooledVec<M> {
+    fn from_iter<I: IntoIterator<Item = M>>(iter: I) -> Self {
+        PooledVec::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl<M> Extend<M> for PooledVec<M> {
+    fn extend<I: IntoIterator<Item = M>>(&mut self, iter: I) {
+        for m in iter {
+            self.push(m);
+        }
+    }
+}
+
+impl<M> IntoIterator for PooledVec<M> {
+    type Item = M;
+    type IntoIter = ::std::vec::IntoIter<M>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Vec::from(self).into_iter()
+    }
+}
+
+impl<'a, M> IntoIterator for &'a PooledVec<M> {
+    type Item = &'a M;
+    type IntoIter = ::std::slice::Iter<'a, M>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, M> IntoIterator for &'a mut PooledVec<M> {
+    type Item = &'a mut M;
+    type IntoIter = ::std::slice::IterMut<'a, M>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
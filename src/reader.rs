@@ -1,63 +1,572 @@
 //! A module to manage protobuf deserialization
 
-use std::io::Read;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{Chain, Read};
+use std::str::Utf8Error;
+use std::sync::Arc;
 
-use errors::{Result, ErrorKind};
-use message::MessageRead;
+use errors::{Error, Result, ErrorKind};
+use message::{MessageRead, MessageReadBorrowed};
+use intern::Interner;
+use index::{FieldIndex, IndexedField};
+use message_set::{self, MessageSet};
+use pool::PooledVec;
 
 use byteorder::ReadBytesExt;
 use byteorder::LittleEndian as LE;
 
-const WIRE_TYPE_VARINT: u8 = 0;
-const WIRE_TYPE_FIXED64: u8 = 1;
-const WIRE_TYPE_LENGTH_DELIMITED: u8 = 2;
-const WIRE_TYPE_START_GROUP: u8 = 3;
-const WIRE_TYPE_END_GROUP: u8 = 4;
-const WIRE_TYPE_FIXED32: u8 = 5;
+/// The wire type of a field, the 3 lowest bits of its tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireType {
+    /// int32, int64, uint32, uint64, sint32, sint64, bool, enum
+    Varint = 0,
+    /// fixed64, sfixed64, double
+    Fixed64 = 1,
+    /// string, bytes, embedded messages, packed repeated fields
+    LengthDelimited = 2,
+    /// groups (deprecated)
+    StartGroup = 3,
+    /// groups (deprecated)
+    EndGroup = 4,
+    /// fixed32, sfixed32, float
+    Fixed32 = 5,
+}
+
+impl WireType {
+    /// Gets the wire type from its value, fails if greater than 5
+    pub fn from_u8(v: u8) -> Result<WireType> {
+        match v {
+            0 => Ok(WireType::Varint),
+            1 => Ok(WireType::Fixed64),
+            2 => Ok(WireType::LengthDelimited),
+            3 => Ok(WireType::StartGroup),
+            4 => Ok(WireType::EndGroup),
+            5 => Ok(WireType::Fixed32),
+            t => Err(ErrorKind::UnknownWireType(t).into()),
+        }
+    }
+
+    /// Gets the value of the wire type
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// Splits a tag value into its field number and wire type
+    pub fn from_tag(tag: u32) -> Result<(u32, WireType)> {
+        Ok((tag >> 3, WireType::from_u8((tag & 0x7) as u8)?))
+    }
+}
+
+/// Splits a tag value read from the input, failing with `InvalidTag` on field number 0 or a
+/// reserved wire type
+fn split_tag(tag: u32) -> Result<(u32, WireType)> {
+    match WireType::from_tag(tag) {
+        Ok((field_number, wire_type)) if field_number != 0 => Ok((field_number, wire_type)),
+        _ => Err(ErrorKind::InvalidTag(tag).into()),
+    }
+}
+
+/// A `string` field content, which may not be valid utf8
+///
+/// Returned by `Reader::read_string_or_bytes`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StringOrBytes {
+    /// A valid utf8 string
+    String(String),
+    /// The original bytes, which are not valid utf8
+    Bytes(Vec<u8>),
+}
+
+/// A step of the parsing, reported to the hook of `Reader::set_trace`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// The tag of a field has been read
+    Field {
+        /// Position of the tag from the start of the input
+        position: usize,
+        /// Nesting of the message of the field, 0 for the top message
+        depth: usize,
+        /// Number of the field
+        field_number: u32,
+        /// Wire type of the field
+        wire_type: WireType,
+    },
+    /// The value of the last field has been read: the varint, the bits of a fixed size value or the
+    /// length of a length delimited value (the fields of nested messages follow). Fields which are
+    /// skipped, or whose value cannot be read, have none
+    Value(u64),
+}
+
+/// Limits of a `Reader`, to read untrusted input with a single policy
+///
+/// `ReaderOptions::default()` gives secure limits, suited to most messages:
+/// 100 levels of nesting (like the reference implementation), 64MB for inputs
+/// and for any single `bytes`, `string` or packed field, 16M elements per
+/// repeated field, and strict lengths.
+/// `Reader::from_reader` has no limits (`ReaderOptions::unlimited()`)
+///
+/// ```rust,ignore
+/// let options = ReaderOptions { max_alloc: 1 << 20, ..ReaderOptions::default() };
+/// let mut reader = Reader::from_bytes_with_options(&bytes, options)?;
+/// let msg = Foo::from_reader(&mut reader)?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReaderOptions {
+    /// Maximum nesting of messages and groups
+    pub max_depth: usize,
+    /// Maximum length of a `bytes`, `string` or packed field, or of raw unknown fields
+    pub max_alloc: usize,
+    /// Lengths must fit in their enclosing message, see `Reader::set_bounded`
+    pub strict: bool,
+    /// Maximum length of the whole input
+    pub max_len: usize,
+    /// Maximum number of elements of a repeated (packed or not) field
+    ///
+    /// A few bytes of input are enough for an element, which can take much more memory
+    /// once parsed (an empty message is 2 bytes), `max_alloc` alone does not bound it
+    pub max_repeated_len: usize,
+    /// Proto2 `required` fields must be in the input, see `Reader::check_required`
+    pub check_required: bool,
+}
+
+impl Default for ReaderOptions {
+    fn default() -> ReaderOptions {
+        ReaderOptions {
+            max_depth: 100,
+            max_alloc: 64 << 20,
+            strict: true,
+            max_len: 64 << 20,
+            max_repeated_len: 16 << 20,
+            check_required: false,
+        }
+    }
+}
+
+impl ReaderOptions {
+    /// Options without any limit, only for trusted input
+    pub fn unlimited() -> ReaderOptions {
+        ReaderOptions {
+            max_depth: usize::MAX,
+            max_alloc: usize::MAX,
+            strict: true,
+            max_len: usize::MAX,
+            max_repeated_len: usize::MAX,
+            check_required: false,
+        }
+    }
+}
 
 /// A struct to read protocol binary files
 pub struct Reader<R> {
     inner: R,
     len: usize,
+    options: ReaderOptions,
+    depth: usize,
+    /// Reading a nested message (or length delimited record), `len` is its remaining length
+    nested: bool,
+    /// Strings read by `read_string_interned`, created by the first one
+    interner: Option<Box<Interner>>,
+    /// Number of bytes read, see `position`
+    position: usize,
+    /// Last tag read, giving the field number of errors
+    tag: u32,
+    /// Hook of `set_trace`
+    trace: Option<Box<dyn FnMut(TraceEvent) + Send>>,
+    /// The value of the last field has not been traced yet
+    trace_value: bool,
+}
+
+impl<'a> Reader<&'a [u8]> {
+
+    /// Creates a reader of `bytes`, fails if they are longer than `options.max_len`
+    pub fn from_bytes_with_options(bytes: &'a [u8], options: ReaderOptions) -> Result<Reader<&'a [u8]>> {
+        Reader::from_reader_with_options(bytes, bytes.len(), options)
+    }
+
+    /// Reads bytes borrowed from the input, without copying them
+    ///
+    /// Nothing is allocated, so `max_alloc` does not apply
+    pub fn read_bytes_borrowed(&mut self) -> Result<&'a [u8]> {
+        let len = self.read_len()?;
+        if len > self.inner.len() {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        self.sub_len(len);
+        let (bytes, rest) = self.inner.split_at(len);
+        self.inner = rest;
+        Ok(bytes)
+    }
+
+    /// Reads a packed repeated field of fixed size values (fixed32, fixed64, float, double ...)
+    /// borrowed from the input, without copying them
+    ///
+    /// The values are only borrowed on little endian hosts, and if their bytes are aligned for `M`
+    /// (e.g. the input is read from a `Vec<u64>` buffer, with values at an offset multiple of
+    /// their size): otherwise they are copied, and swapped on big endian hosts, as with
+    /// `read_packed_fixed`. Fails like `read_packed_fixed`, `max_alloc` only applies to copies
+    pub fn read_packed_fixed_borrowed<M: Fixed>(&mut self) -> Result<Cow<'a, [M]>> {
+        let len = self.read_len()?;
+        self.check_packed_fixed_len::<M>(len)?;
+        if len > self.inner.len() {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        let aligned = self.inner.as_ptr() as usize % ::std::mem::align_of::<M>() == 0;
+        if cfg!(target_endian = "big") || !aligned {
+            self.check_alloc(len)?;
+            return self.read_fixed_values(len).map(Cow::Owned);
+        }
+        self.sub_len(len);
+        let (bytes, rest) = self.inner.split_at(len);
+        self.inner = rest;
+        // little endian bytes, aligned for `M`, and any bytes are a valid `Fixed` value
+        let values = unsafe {
+            ::std::slice::from_raw_parts(bytes.as_ptr() as *const M, len / ::std::mem::size_of::<M>())
+        };
+        Ok(Cow::Borrowed(values))
+    }
+
+    /// Reads string borrowed from the input, without copying it
+    pub fn read_string_borrowed(&mut self) -> Result<&'a str> {
+        let bytes = self.read_bytes_borrowed()?;
+        let start = self.position - bytes.len();
+        ::std::str::from_utf8(bytes).map_err(|e| self.invalid_utf8(start, e))
+    }
+
+    /// Reads a nested message borrowing from the input
+    pub fn read_message_borrowed<M: MessageReadBorrowed<'a>>(&mut self) -> Result<M> {
+        let len = self.read_len()?;
+        self.read_nested(len, M::from_reader)
+    }
+
+    /// Reads a nested message borrowing from the input and merges it into an existing instance
+    pub fn merge_message_borrowed<M: MessageReadBorrowed<'a>>(&mut self, out: &mut M) -> Result<()> {
+        let len = self.read_len()?;
+        self.read_nested(len, |r| out.merge_from(r))
+    }
+
+    /// Checks a nested message with `validate` (e.g. the generated `validate_fields`), which reads
+    /// its fields without building it, within the limits of `read_message`
+    pub fn validate_message<F: FnOnce(&mut Self) -> Result<()>>(&mut self, validate: F) -> Result<()> {
+        let len = self.read_len()?;
+        self.read_nested(len, validate)
+    }
+
+    /// Reads only the field `field_number` of an encoded message with `read`, `None` if the
+    /// message does not have it, e.g. to route a message by its id without decoding it
+    ///
+    /// ```rust,ignore
+    /// let id = Reader::extract_field(bytes, 1, |r| r.read_uint64())?;
+    /// ```
+    ///
+    /// Returns the *first* occurrence of the field: for a singular field written several times,
+    /// or a message merged from several occurrences, it differs from the decoded value
+    pub fn extract_field<T, F>(bytes: &'a [u8], field_number: u32, read: F) -> Result<Option<T>>
+        where F: FnOnce(&mut Reader<&'a [u8]>) -> Result<T>
+    {
+        let mut r = Reader::from_reader(bytes, bytes.len());
+        match r.seek_field(field_number)? {
+            Some(_) => read(&mut r).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<'a> Reader<Chain<&'a [u8], &'a [u8]>> {
+
+    /// Creates a reader of all the bytes of a `VecDeque`, read across its two contiguous
+    /// segments without copying them
+    pub fn from_deque(buf: &'a VecDeque<u8>) -> Reader<Chain<&'a [u8], &'a [u8]>> {
+        let (front, back) = buf.as_slices();
+        Reader::from_reader(front.chain(back), buf.len())
+    }
+
+    /// Reads a length delimited message from the front of `buf`, and the number of bytes it takes,
+    /// `None` if `buf` does not hold the whole message yet
+    ///
+    /// Once the message is handled, its bytes are to be drained from `buf`, e.g. in a server
+    /// accumulating the bytes of a socket:
+    ///
+    /// ```rust,ignore
+    /// while let Some((msg, len)) = Reader::read_message_from_deque::<Request>(&buf)? {
+    ///     buf.drain(..len);
+    ///     handle(msg);
+    /// }
+    /// ```
+    pub fn read_message_from_deque<M: MessageRead>(buf: &'a VecDeque<u8>) -> Result<Option<(M, usize)>> {
+        let mut r = Reader::from_deque(buf);
+        let len = match r.read_len() {
+            Ok(len) => len,
+            Err(ref e) if matches!(*e.kind(), ErrorKind::UnexpectedEof) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let msg = r.read_nested(len, |r| {
+            let mut msg = M::from_reader(r)?;
+            r.read_trailing_fields(&mut msg)?;
+            Ok(msg)
+        })?;
+        Ok(Some((msg, r.position())))
+    }
 }
 
 impl<R: Read> Reader<R> {
 
     /// Creates a new protocol buffer reader with the maximum len of bytes to read
     pub fn from_reader(r: R, len: usize) -> Reader<R> {
-        Reader { inner: r, len: len }
+        Reader { inner: r, len: len, options: ReaderOptions::unlimited(), depth: 0, nested: false, interner: None,
+                 position: 0, tag: 0, trace: None, trace_value: false }
+    }
+
+    /// Creates a new protocol buffer reader with some limits, fails if `len` exceeds `options.max_len`
+    pub fn from_reader_with_options(r: R, len: usize, options: ReaderOptions) -> Result<Reader<R>> {
+        if len > options.max_len {
+            return Err(ErrorKind::InputTooLarge(len, options.max_len).into());
+        }
+        Ok(Reader { inner: r, len, options, depth: 0, nested: false, interner: None, position: 0, tag: 0,
+                    trace: None, trace_value: false })
+    }
+
+    /// Sets whether lengths are bounded by the length of the enclosing message (default `true`)
+    ///
+    /// When unbounded, the declared lengths of nested messages and bytes are trusted even if
+    /// they exceed the remaining length, and are read directly from the inner `Read`.
+    /// For instance when reading through a decompressor, where the `len` given to
+    /// `from_reader` (the compressed size) is smaller than the decoded data.
+    /// The remaining length then saturates at 0, which ends the enclosing message.
+    /// Tags are still validated, see `next_tag`
+    pub fn set_bounded(&mut self, bounded: bool) {
+        self.options.strict = bounded;
+    }
+
+    /// Sets a hook called with each field read, and its value, e.g. to log where a malformed
+    /// message fails to parse
+    ///
+    /// ```rust,ignore
+    /// r.set_trace(|e| eprintln!("{:?}", e));
+    /// ```
+    pub fn set_trace<F: FnMut(TraceEvent) + Send + 'static>(&mut self, trace: F) {
+        self.trace = Some(Box::new(trace));
+    }
+
+    /// Reports the value of the last field read to the trace hook, if it has not been yet
+    fn trace_value(&mut self, value: u64) {
+        if self.trace_value {
+            self.trace_value = false;
+            if let Some(ref mut trace) = self.trace {
+                trace(TraceEvent::Value(value));
+            }
+        }
+    }
+
+    /// Sets whether messages missing a proto2 `required` field are rejected (default `false`)
+    pub fn set_check_required(&mut self, check: bool) {
+        self.options.check_required = check;
+    }
+
+    /// Fails with `MissingRequired` if required fields have not been read, `seen` telling for each
+    /// of the `names` whether it has been, when the `check_required` option is set
+    ///
+    /// Called by generated code at the end of `merge_from`. The path of the error is then prefixed
+    /// with the fields of the enclosing messages (`Error::in_field`), e.g. `items[2].id`.
+    /// Each occurrence of a message must have its required fields, even if occurrences are merged
+    pub fn check_required(&self, seen: &[bool], names: &[&str]) -> Result<()> {
+        if self.options.check_required {
+            if let Some(i) = seen.iter().position(|s| !s) {
+                return Err(ErrorKind::MissingRequired(names[i].to_string()).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails if a field of `len` bytes exceeds `max_alloc`
+    fn check_alloc(&self, len: usize) -> Result<()> {
+        if len > self.options.max_alloc {
+            return Err(ErrorKind::AllocLimit(len, self.options.max_alloc).into());
+        }
+        Ok(())
+    }
+
+    /// Fails with `RepeatedLimit` if a repeated field of `len` elements cannot get another one
+    ///
+    /// Called by generated code before adding an element to a repeated field
+    pub fn check_repeated_len(&self, len: usize) -> Result<()> {
+        if len >= self.options.max_repeated_len {
+            return Err(ErrorKind::RepeatedLimit(self.options.max_repeated_len).into());
+        }
+        Ok(())
+    }
+
+    /// Reads a nested message (or group) of `len` bytes with `read`, fails if it exceeds `max_depth`
+    fn read_nested<T, F: FnOnce(&mut Self) -> Result<T>>(&mut self, len: usize, read: F) -> Result<T> {
+        if self.depth >= self.options.max_depth {
+            return Err(ErrorKind::RecursionLimit(self.options.max_depth).into());
+        }
+        let (cur_len, cur_nested) = (self.len, self.nested);
+        self.len = len;
+        self.nested = true;
+        self.depth += 1;
+        let t = read(self)?;
+        self.depth -= 1;
+        self.len = cur_len;
+        self.nested = cur_nested;
+        self.shrink_len(len);
+        Ok(t)
+    }
+
+    /// Reduces the remaining length once `len` bytes have been read
+    fn sub_len(&mut self, len: usize) {
+        // an unbounded length may be up to `usize::MAX`
+        self.position = self.position.saturating_add(len);
+        self.shrink_len(len);
+    }
+
+    /// Reduces the remaining length by `len` bytes which have already been counted as read,
+    /// e.g. once back from a nested message
+    fn shrink_len(&mut self, len: usize) {
+        if self.options.strict {
+            self.len -= len;
+        } else {
+            self.len = self.len.saturating_sub(len);
+        }
     }
 
     /// Reads next tag, `None` if all bytes have been read
+    ///
+    /// Fails with `InvalidTag` on a tag of field number 0 or of the reserved wire type 6 or 7,
+    /// which can only be read from corrupt input
     pub fn next_tag(&mut self) -> Result<u32> {
-        self.read_varint().map(|i| (i as u32))
+        if self.trace.is_none() {
+            self.tag = self.read_varint_raw()? as u32;
+            split_tag(self.tag)?;
+            return Ok(self.tag);
+        }
+        let (position, depth) = (self.position, self.depth);
+        self.trace_value = false;
+        self.tag = self.read_varint_raw()? as u32;
+        split_tag(self.tag)?;
+        if let (Ok((field_number, wire_type)), Some(trace)) = (WireType::from_tag(self.tag), self.trace.as_mut()) {
+            trace(TraceEvent::Field { position, depth, field_number, wire_type });
+            self.trace_value = true;
+        }
+        Ok(self.tag)
     }
 
-    /// Reads the next varint encoded u64
+    /// Reads next tag as its `(field_number, wire_type)`, `None` if all bytes have been read
+    ///
+    /// Unlike `next_tag`, callers can match on the field number instead of the raw tag value.
+    /// Fails with `InvalidTag` on field number 0 or a reserved wire type, like `next_tag`
+    pub fn next_field(&mut self) -> Result<Option<(u32, WireType)>> {
+        if self.is_eof() {
+            return Ok(None);
+        }
+        let tag = self.next_tag()?;
+        split_tag(tag).map(Some)
+    }
+
+    /// Reads next tag among `tags`, skipping all the other fields, `None` if all bytes have been read
+    ///
+    /// `tags` are tag values (`field_number << 3 | wire_type`), like in generated `from_reader`.
+    /// Useful to cheaply parse only a few fields of a wide message:
+    ///
+    /// ```rust,ignore
+    /// while let Some(tag) = r.next_tag_in(&[10, 24])? {
+    ///     match tag {
+    ///         10 => msg.name = r.read_string()?,
+    ///         _ => msg.id = r.read_int32()?,
+    ///     }
+    /// }
+    /// ```
+    pub fn next_tag_in(&mut self, tags: &[u32]) -> Result<Option<u32>> {
+        while !self.is_eof() {
+            let tag = self.next_tag()?;
+            if tags.contains(&tag) {
+                return Ok(Some(tag));
+            }
+            self.read_unknown(tag)?;
+        }
+        Ok(None)
+    }
+
+    /// Skips the fields up to the next occurrence of `field_number`, returning its wire type once
+    /// its tag has been read, `None` if all bytes have been read
+    pub fn seek_field(&mut self, field_number: u32) -> Result<Option<WireType>> {
+        while let Some((number, wire_type)) = self.next_field()? {
+            if number == field_number {
+                return Ok(Some(wire_type));
+            }
+            self.skip_field(number, wire_type)?;
+        }
+        Ok(None)
+    }
+
+    /// Reads one byte, failing with `UnexpectedEof` rather than reading past the remaining length
+    fn read_byte(&mut self) -> Result<u8> {
+        if self.options.strict && self.len == 0 {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        self.sub_len(1);
+        self.inner.read_u8().map_err(|e| e.into())
+    }
+
+    /// Reads the next varint encoded u64, the value of the last field for `set_trace`
     fn read_varint(&mut self) -> Result<u64> {
+        let v = self.read_varint_raw()?;
+        self.trace_value(v);
+        Ok(v)
+    }
+
+    /// Reads the next varint encoded u64
+    fn read_varint_raw(&mut self) -> Result<u64> {
         let mut r: u64 = 0;
         let mut i = 0;
         for _ in 0..9 {
-            self.len -= 1;
-            let b = self.inner.read_u8()?;
+            let b = self.read_byte()?;
             r |= ((b & 0x7f) as u64) << i;
             if b < 0x80 {
                 return Ok(r);
             }
             i += 7;
         }
-        self.len -= 1;
-        match self.inner.read_u8()? {
+        // the tenth byte holds bit 63 only: any other bit, continuation included, overflows
+        match self.read_byte()? {
             0 => Ok(r),
             1 => {
                 r |= 1 << 63;
                 Ok(r)
             }
-            _ => Err(ErrorKind::Varint.into()), // we have only one spare bit to fit into
+            _ => Err(ErrorKind::Varint.into()),
         }
     }
 
+    /// Reads the length of a length delimited value
+    fn read_len(&mut self) -> Result<usize> {
+        let len = self.read_varint()?;
+        self.check_len(len)
+    }
+
+    /// Converts a declared length to `usize`, without truncating it on 32-bit targets
+    ///
+    /// Fails with `LengthOverflow` if it doesn't fit in `usize`, and when bounded, if it exceeds
+    /// the remaining length: with `WrongLen` in a nested message, which the field would overrun
+    /// (reading the next fields of its parent as its own), else with `UnexpectedEof`
+    fn check_len(&self, len: u64) -> Result<usize> {
+        if len > usize::MAX as u64 {
+            return Err(ErrorKind::LengthOverflow(len).into());
+        }
+        let len = len as usize;
+        if self.options.strict && len > self.len {
+            if self.nested {
+                return Err(ErrorKind::WrongLen(len, self.len).into());
+            }
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        Ok(len)
+    }
+
     /// Reads int32 (varint)
+    ///
+    /// Negative values are sign-extended to 10 bytes by conformant encoders (and `Writer::write_int32`),
+    /// some others write them on 5 bytes. Both are read correctly, only the low 32 bits being kept
     pub fn read_int32(&mut self) -> Result<i32> {
         self.read_varint().map(|i| i as i32)
     }
@@ -91,40 +600,64 @@ impl<R: Read> Reader<R> {
         Ok(((n >> 1) as i64) ^ (-((n & 1) as i64)))
     }
 
+    /// Fails with `UnexpectedEof` if a fixed size value of `size` bytes exceeds the remaining length
+    fn check_fixed_len(&self, size: usize) -> Result<()> {
+        if self.options.strict && self.len < size {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+
     /// Reads fixed64 (little endian u64)
     pub fn read_fixed64(&mut self) -> Result<u64> {
-        self.len -= 8;
-        self.inner.read_u64::<LE>().map_err(|e| e.into())
+        self.check_fixed_len(8)?;
+        self.sub_len(8);
+        let v = self.inner.read_u64::<LE>()?;
+        self.trace_value(v);
+        Ok(v)
     }
 
     /// Reads fixed32 (little endian u32)
     pub fn read_fixed32(&mut self) -> Result<u32> {
-        self.len -= 4;
-        self.inner.read_u32::<LE>().map_err(|e| e.into())
+        self.check_fixed_len(4)?;
+        self.sub_len(4);
+        let v = self.inner.read_u32::<LE>()?;
+        self.trace_value(v as u64);
+        Ok(v)
     }
 
     /// Reads sfixed64 (little endian i64)
     pub fn read_sfixed64(&mut self) -> Result<i64> {
-        self.len -= 8;
-        self.inner.read_i64::<LE>().map_err(|e| e.into())
+        self.read_fixed64().map(|v| v as i64)
     }
 
     /// Reads sfixed32 (little endian i32)
     pub fn read_sfixed32(&mut self) -> Result<i32> {
-        self.len -= 4;
-        self.inner.read_i32::<LE>().map_err(|e| e.into())
+        self.read_fixed32().map(|v| v as i32)
     }
 
     /// Reads float (little endian f32)
     pub fn read_float(&mut self) -> Result<f32> {
-        self.len -= 4;
-        self.inner.read_f32::<LE>().map_err(|e| e.into())
+        self.read_fixed32().map(f32::from_bits)
     }
 
     /// Reads double (little endian f64)
     pub fn read_double(&mut self) -> Result<f64> {
-        self.len -= 8;
-        self.inner.read_f64::<LE>().map_err(|e| e.into())
+        self.read_fixed64().map(f64::from_bits)
+    }
+
+    /// Reads the raw IEEE-754 bits of a float, without converting them
+    ///
+    /// Keeps NaN payloads intact, e.g. to hash or compare floats bit for bit
+    pub fn read_float_bits(&mut self) -> Result<u32> {
+        self.read_fixed32()
+    }
+
+    /// Reads the raw IEEE-754 bits of a double, without converting them
+    ///
+    /// Keeps NaN payloads intact, e.g. to hash or compare doubles bit for bit
+    pub fn read_double_bits(&mut self) -> Result<u64> {
+        self.read_fixed64()
     }
 
     /// Reads bool (varint, check if == 0)
@@ -139,73 +672,606 @@ impl<R: Read> Reader<R> {
 
     /// Reads bytes (Vec<u8>)
     pub fn read_bytes(&mut self) -> Result<Vec<u8>> {
-        let len = self.read_varint()? as usize;
-        self.len -= len;
+        let len = self.read_len()?;
+        self.check_alloc(len)?;
+        self.sub_len(len);
         let mut vec = Vec::with_capacity(len);
         unsafe { vec.set_len(len); }
         self.inner.read_exact(&mut vec[..])?;
         Ok(vec)
     }
 
+    /// Reads bytes into a caller provided buffer, returns the number of bytes read
+    ///
+    /// Avoids allocating for small or fixed size `bytes` fields (hashes, ids ...).
+    /// Fails with `ErrorKind::BufferTooSmall` if the payload doesn't fit into `out`
+    pub fn read_bytes_into(&mut self, out: &mut [u8]) -> Result<usize> {
+        let len = self.read_len()?;
+        if len > out.len() {
+            return Err(ErrorKind::BufferTooSmall(len, out.len()).into());
+        }
+        self.sub_len(len);
+        self.inner.read_exact(&mut out[..len])?;
+        Ok(len)
+    }
+
     /// Reads string (String)
+    ///
+    /// Fails with `InvalidUtf8`, giving the field number and the position of the first invalid byte,
+    /// `read_string_or_bytes` keeps the bytes of such strings instead
     pub fn read_string(&mut self) -> Result<String> {
         let vec = self.read_bytes()?;
-        String::from_utf8(vec).map_err(|e| e.into())
+        let start = self.position - vec.len();
+        String::from_utf8(vec).map_err(|e| self.invalid_utf8(start, e.utf8_error()))
+    }
+
+    /// Reads string shared with the equal strings read before, through the interner of the reader
+    ///
+    /// Only the first occurrence of a string is allocated, the next ones are looked up
+    pub fn read_string_interned(&mut self) -> Result<Arc<str>> {
+        let len = self.read_len()?;
+        self.check_alloc(len)?;
+        let (start, field_number) = (self.position, self.tag >> 3);
+        self.sub_len(len);
+        let inner = &mut self.inner;
+        let interner = self.interner.get_or_insert_with(Default::default);
+        interner.intern_with(len, |buf| inner.read_exact(buf).map_err(|e| e.into()),
+                                  |e| ErrorKind::InvalidUtf8(field_number, start + e.valid_up_to()).into())
+    }
+
+    /// Sets the interner of `read_string_interned`, e.g. to share strings with the messages
+    /// read by another reader (see `take_interner`)
+    pub fn set_interner(&mut self, interner: Interner) {
+        self.interner = Some(Box::new(interner));
+    }
+
+    /// Takes the interner of `read_string_interned`, leaving an empty one
+    pub fn take_interner(&mut self) -> Interner {
+        self.interner.take().map_or_else(Interner::new, |i| *i)
+    }
+
+    /// Reads string (String) or, if not valid utf8, the raw bytes (Vec<u8>)
+    ///
+    /// Unlike `read_string`, invalid utf8 is not an error: the original bytes
+    /// are kept so the field can be inspected or forwarded as is
+    pub fn read_string_or_bytes(&mut self) -> Result<StringOrBytes> {
+        let vec = self.read_bytes()?;
+        Ok(match String::from_utf8(vec) {
+            Ok(s) => StringOrBytes::String(s),
+            Err(e) => StringOrBytes::Bytes(e.into_bytes()),
+        })
     }
 
     /// Reads packed repeated field (Vec<M>)
     ///
     /// Note: packed field are stored as a variable length chunk of data, while regular repeated
     /// fields behaves like an iterator, yielding their tag everytime
+    ///
+    /// Fails with `PackedTruncated` if the last value does not end with the field, but is cut
+    /// by its end or, when not bounded, overruns it
     pub fn read_packed_repeated_field<M, F: FnMut(&mut Self) -> Result<M>>(&mut self, mut read: F) -> Result<Vec<M>> {
-        let len = self.read_varint()? as usize;
+        let mut v = Vec::new();
+        self.read_packed_for_each(|r| {
+            r.check_repeated_len(v.len())?;
+            v.push(read(r)?);
+            Ok(())
+        })?;
+        Ok(v)
+    }
+
+    /// Reads each value of a packed repeated field with `read`, without collecting them, e.g. to
+    /// check them. Fails like `read_packed_repeated_field`
+    pub fn read_packed_for_each<F: FnMut(&mut Self) -> Result<()>>(&mut self, mut read: F) -> Result<()> {
+        let len = self.read_len()?;
+        self.check_alloc(len)?;
+        let (cur_len, start) = (self.len, self.position);
+        self.len = len;
+        while !self.is_eof() {
+            match read(self) {
+                Err(ref e) if self.len == 0 && matches!(*e.kind(), ErrorKind::UnexpectedEof) => {
+                    return Err(ErrorKind::PackedTruncated(len).into());
+                }
+                Ok(_) if self.position - start > len => return Err(ErrorKind::PackedTruncated(len).into()),
+                r => r?,
+            }
+        }
+        self.len = cur_len;
+        self.shrink_len(len);
+        Ok(())
+    }
+
+    /// Reads a packed repeated field of fixed size items (fixed32, fixed64, float, double ...)
+    ///
+    /// Same as `read_packed_repeated_field` but allocates the `Vec` once, the number
+    /// of items being the length divided by `item_size`. Fails with `PackedLength` if the length
+    /// is not a multiple of `item_size`
+    pub fn read_packed_fixed_size<M, F: FnMut(&mut Self) -> Result<M>>(&mut self, item_size: usize, mut read: F) -> Result<Vec<M>> {
+        let len = self.read_len()?;
+        self.check_alloc(len)?;
+        if len % item_size != 0 {
+            return Err(ErrorKind::PackedLength(len, item_size).into());
+        }
+        if len / item_size > self.options.max_repeated_len {
+            return Err(ErrorKind::RepeatedLimit(self.options.max_repeated_len).into());
+        }
         let cur_len = self.len;
         self.len = len;
-        let mut v = Vec::new();
+        let mut v = Vec::with_capacity(len / item_size);
         while !self.is_eof() {
             v.push(read(self)?);
         }
-        self.len = cur_len - len;
+        self.len = cur_len;
+        self.shrink_len(len);
+        Ok(v)
+    }
+
+    /// Reads a packed repeated field of fixed size values (fixed32, fixed64, float, double ...) in bulk
+    ///
+    /// Same as `read_packed_fixed_size` but copies all the bytes at once instead of reading the values
+    /// one by one. Fails with `PackedLength` if the length is not a multiple of the size of the values
+    pub fn read_packed_fixed<M: Fixed>(&mut self) -> Result<Vec<M>> {
+        let len = self.read_len()?;
+        self.check_alloc(len)?;
+        self.check_packed_fixed_len::<M>(len)?;
+        self.read_fixed_values(len)
+    }
+
+    /// Checks the length of a packed field of fixed size values `M`
+    fn check_packed_fixed_len<M: Fixed>(&self, len: usize) -> Result<()> {
+        let item_size = ::std::mem::size_of::<M>();
+        if len % item_size != 0 {
+            return Err(ErrorKind::PackedLength(len, item_size).into());
+        }
+        if len / item_size > self.options.max_repeated_len {
+            return Err(ErrorKind::RepeatedLimit(self.options.max_repeated_len).into());
+        }
+        Ok(())
+    }
+
+    /// Copies the `len` bytes of fixed size values `M`, once their length is checked
+    fn read_fixed_values<M: Fixed>(&mut self, len: usize) -> Result<Vec<M>> {
+        let item_size = ::std::mem::size_of::<M>();
+        self.sub_len(len);
+        let mut v = Vec::<M>::with_capacity(len / item_size);
+        let bytes = unsafe {
+            // zeroed first, which is a valid value for all `Fixed` types
+            ::std::ptr::write_bytes(v.as_mut_ptr(), 0, len / item_size);
+            v.set_len(len / item_size);
+            ::std::slice::from_raw_parts_mut(v.as_mut_ptr() as *mut u8, len)
+        };
+        self.inner.read_exact(bytes)?;
+        if cfg!(target_endian = "big") {
+            for item in bytes.chunks_mut(item_size) {
+                item.reverse();
+            }
+        }
         Ok(v)
     }
 
+    /// Reads a nested message and pushes it into `v`, for non packed `repeated` message fields
+    ///
+    /// When `v` is full, it reserves room for as many messages of this length as the bytes
+    /// left could hold, instead of growing one push at a time. The reservation never takes more
+    /// memory than the bytes left to read or `max_alloc`, nor room for more than `max_repeated_len`
+    /// messages. As the length of a reader may exceed its actual input, past 1024 messages `v` at most
+    /// doubles. Fails with `RepeatedLimit` if `v` already holds `max_repeated_len` messages
+    pub fn read_message_to_vec<M: MessageRead>(&mut self, v: &mut Vec<M>) -> Result<()> {
+        self.check_repeated_len(v.len())?;
+        let len = self.read_len()?;
+        if self.options.strict && v.len() == v.capacity() {
+            let size = ::std::mem::size_of::<M>();
+            let item_len = len.saturating_add(2).max(size);
+            let left = (self.options.max_repeated_len - v.len())
+                .min(self.options.max_alloc / size.max(1))
+                .min(v.len().max(1024));
+            v.reserve((1 + self.len.saturating_sub(len) / item_len).min(left));
+        }
+        let msg = self.read_nested(len, |r| {
+            let mut msg = M::from_reader(r)?;
+            r.read_trailing_fields(&mut msg)?;
+            Ok(msg)
+        })?;
+        v.push(msg);
+        Ok(())
+    }
+
+    /// Reads a nested message into a spare message of `v`, for `repeated` message fields stored in
+    /// a `PooledVec`, reusing the allocations of the messages `v` has been cleared of
+    ///
+    /// Fails with `RepeatedLimit` if `v` already holds `max_repeated_len` messages
+    pub fn read_message_to_pooled_vec<M: MessageRead + Default>(&mut self, v: &mut PooledVec<M>) -> Result<()> {
+        self.check_repeated_len(v.len())?;
+        v.push_with(|m| self.merge_message(m))
+    }
+
+    /// Reads a message, without length prefix, from all the remaining bytes and checks that the
+    /// input ends with it
+    ///
+    /// Fails with `LengthMismatch` if the inner `Read` has bytes left after the length given to the
+    /// reader, or if the message overruns it (when not bounded). An inner `Read` shorter than this
+    /// length fails with `UnexpectedEof`, like other reads
+    pub fn read_message_exact<M: MessageRead>(&mut self) -> Result<M> {
+        let (end, len) = (self.position + self.len, self.len);
+        let msg = M::from_reader(self)?;
+        if self.position != end || self.inner.read(&mut [0])? != 0 {
+            return Err(ErrorKind::LengthMismatch(len).into());
+        }
+        Ok(msg)
+    }
+
     /// Reads a nested message
     pub fn read_message<M: MessageRead>(&mut self) -> Result<M> {
-        let len = self.read_varint()? as usize;
-        let cur_len = self.len;
-        self.len = len;
-        let msg = M::from_reader(self)?;
-        self.len = cur_len - len;
+        let len = self.read_len()?;
+        self.read_nested(len, |r| {
+            let mut msg = M::from_reader(r)?;
+            r.read_trailing_fields(&mut msg)?;
+            Ok(msg)
+        })
+    }
+
+    /// Reads a nested message directly into a `Box`
+    ///
+    /// The fields are merged into a boxed `Default` message, instead of being read into a message
+    /// on the stack then moved into the `Box`. The `Default` value itself may still be built on
+    /// the stack before being moved into the `Box`
+    pub fn read_message_boxed<M: MessageRead + Default>(&mut self) -> Result<Box<M>> {
+        let mut msg = Box::<M>::default();
+        self.merge_message(&mut *msg)?;
         Ok(msg)
     }
 
+    /// Reads length delimited messages until the end of the input, at most `max` of them
+    ///
+    /// Fails with `MessageLimit` if more messages remain, bounding how many messages an input
+    /// can force to allocate (`usize::MAX` reads them all), and with `UnexpectedEof` if the
+    /// last message is truncated
+    pub fn read_all_messages<M: MessageRead>(&mut self, max: usize) -> Result<Vec<M>> {
+        let mut messages = Vec::new();
+        while !self.is_eof() {
+            if messages.len() == max {
+                return Err(ErrorKind::MessageLimit(max).into());
+            }
+            messages.push(self.read_message()?);
+        }
+        Ok(messages)
+    }
+
+    /// Reads a record of a tagged union: a one byte discriminator then a length delimited body,
+    /// `None` if all bytes have been read
+    ///
+    /// `read` decodes the body for this discriminator, it can fail with `UnknownDiscriminator`.
+    /// Bytes of the body it leaves are skipped. A record truncated anywhere, including right after
+    /// its discriminator, fails with `UnexpectedEof`. Records are written by `Writer::write_tagged_union`:
+    ///
+    /// ```rust,ignore
+    /// while let Some(event) = r.read_tagged_union(|d, r| match d {
+    ///     1 => Ok(Event::Login(Login::from_reader(r)?)),
+    ///     2 => Ok(Event::Logout(Logout::from_reader(r)?)),
+    ///     d => Err(ErrorKind::UnknownDiscriminator(d).into()),
+    /// })? {
+    ///     handle(event);
+    /// }
+    /// ```
+    pub fn read_tagged_union<T, F: FnOnce(u8, &mut Self) -> Result<T>>(&mut self, read: F) -> Result<Option<T>> {
+        if self.is_eof() {
+            return Ok(None);
+        }
+        let discriminator = self.read_byte()?;
+        let len = self.read_len()?;
+        self.read_nested(len, |r| {
+            let t = read(discriminator, r)?;
+            let left = r.len;
+            r.scan_bytes(left, &mut None)?;
+            Ok(t)
+        }).map(Some)
+    }
+
+    /// Reads a map entry, a nested message with its key as field 1 and its value as field 2
+    ///
+    /// `key_tag` and `value_tag` are the expected tags of the key and value (e.g. `10` and `18`).
+    /// As allowed by the spec, a missing key or value is set to its default and other fields
+    /// are skipped. A key or value with another wire type fails with `MapEntryInvalid`
+    pub fn read_map<K, V, FK, FV>(&mut self, key_tag: u32, value_tag: u32,
+                                  mut read_key: FK, mut read_value: FV) -> Result<(K, V)>
+        where K: Default, V: Default,
+              FK: FnMut(&mut Self) -> Result<K>,
+              FV: FnMut(&mut Self) -> Result<V>,
+    {
+        let len = self.read_len()?;
+        self.read_nested(len, |r| {
+            let (mut key, mut value) = (K::default(), V::default());
+            while !r.is_eof() {
+                match r.next_tag()? {
+                    t if t == key_tag => key = read_key(r)?,
+                    t if t == value_tag => value = read_value(r)?,
+                    t if t >> 3 == 1 || t >> 3 == 2 => return Err(ErrorKind::MapEntryInvalid(t).into()),
+                    t => r.read_unknown(t)?,
+                }
+            }
+            Ok((key, value))
+        })
+    }
+
+    /// Reads a nested message into an existing instance
+    ///
+    /// `out` is cleared first then merged with the message, so a reused instance
+    /// absorbs the allocations of its repeated fields
+    pub fn read_message_into<M: MessageRead + Default>(&mut self, out: &mut M) -> Result<()> {
+        let len = self.read_len()?;
+        out.clear();
+        self.read_nested(len, |r| {
+            out.merge_from(r)?;
+            r.read_trailing_fields(out)
+        })
+    }
+
+    /// Reads a nested message and merges it into an existing instance
+    ///
+    /// Used when a singular message field occurs several times: the occurrences are merged,
+    /// as if their fields had been sent in a single one
+    pub fn merge_message<M: MessageRead>(&mut self, out: &mut M) -> Result<()> {
+        let len = self.read_len()?;
+        self.read_nested(len, |r| {
+            out.merge_from(r)?;
+            r.read_trailing_fields(out)
+        })
+    }
+
+    /// Reads a length delimited message and merges it into `out`, `false` if all bytes have been read
+    ///
+    /// Concatenated messages are equivalent to their merge: a message written as several
+    /// length delimited frames, each with a part of its fields, is accumulated frame by frame
+    ///
+    /// ```rust,ignore
+    /// let mut msg = Foo::default();
+    /// while r.merge_length_delimited(&mut msg)? {}
+    /// ```
+    pub fn merge_length_delimited<M: MessageRead>(&mut self, out: &mut M) -> Result<bool> {
+        if self.is_eof() {
+            return Ok(false);
+        }
+        self.merge_message(out)?;
+        Ok(true)
+    }
+
+    /// Reads all the remaining bytes, e.g. the fields of a nested message, appending them to `out`
+    ///
+    /// Fails if `out` would exceed `max_alloc`
+    pub fn read_remaining_raw(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        let len = self.len;
+        self.scan_bytes(len, &mut Some(out))
+    }
+
+    /// Reads the fields a nested message has not consumed within its length
+    ///
+    /// They are handled as unknown fields: kept if the message keeps them, skipped otherwise
+    fn read_trailing_fields<M: MessageRead>(&mut self, msg: &mut M) -> Result<()> {
+        while !self.is_eof() {
+            let tag = self.next_tag()?;
+            match msg.unknown_fields_mut() {
+                Some(u) => u.read_field(self, tag)?,
+                None => self.read_unknown(tag)?,
+            }
+        }
+        Ok(())
+    }
+
     /// Reads unknown data, based on its tag value (which itself gives us the wire_type value)
     pub fn read_unknown(&mut self, tag_value: u32) -> Result<()> {
-        match (tag_value & 0x7) as u8 {
-            WIRE_TYPE_VARINT => { self.read_varint()?; },
-            WIRE_TYPE_FIXED64 => {
-                self.len -= 8;
+        let (field_number, wire_type) = WireType::from_tag(tag_value)?;
+        self.skip_field(field_number, wire_type)
+    }
+
+    /// Skips the value of a field, once its tag has been read, e.g. as returned by `next_field`
+    ///
+    /// The field number is only used by groups, to find their matching end group tag
+    pub fn skip_field(&mut self, field_number: u32, wire_type: WireType) -> Result<()> {
+        match wire_type {
+            WireType::Varint => { self.read_varint_raw()?; },
+            WireType::Fixed64 => {
+                self.check_fixed_len(8)?;
+                self.sub_len(8);
                 self.inner.read_exact(&mut [0; 8])?;
             }
-            WIRE_TYPE_FIXED32 => {
-                self.len -= 4;
+            WireType::Fixed32 => {
+                self.check_fixed_len(4)?;
+                self.sub_len(4);
                 self.inner.read_exact(&mut [0; 4])?;
             }
-            WIRE_TYPE_LENGTH_DELIMITED => {
-                let len = self.read_varint()? as usize;
-                if len == 0 { return Ok(()); }
-                self.len -= len;
-                let mut buf = Vec::with_capacity(len);
-                unsafe { buf.set_len(len); }
-                self.inner.read_exact(&mut buf)?;
+            WireType::LengthDelimited => {
+                let len = self.read_len()?;
+                self.scan_bytes(len, &mut None)?;
             },
-            WIRE_TYPE_START_GROUP | 
-                WIRE_TYPE_END_GROUP => { return Err(ErrorKind::Deprecated("group").into()); },
-            t => { return Err(ErrorKind::UnknownWireType(t).into()); },
+            WireType::StartGroup => self.skip_group(field_number)?,
+            WireType::EndGroup => { return Err(ErrorKind::UnmatchedEndGroup(field_number).into()); },
+        }
+        Ok(())
+    }
+
+    /// Walks all the remaining fields without reading them, calling `visitor` with their
+    /// `(field_number, wire_type, len)`
+    ///
+    /// `len` is the encoded length of the value after the tag, length prefix and end group tag
+    /// included. Nothing is allocated, e.g. to cheaply compute field size statistics over a corpus
+    pub fn scan<F: FnMut(u32, WireType, usize)>(&mut self, mut visitor: F) -> Result<()> {
+        while !self.is_eof() {
+            let tag = self.next_tag()?;
+            let (number, wire_type) = WireType::from_tag(tag)?;
+            let len = self.len;
+            self.read_unknown(tag)?;
+            visitor(number, wire_type, len - self.len);
         }
         Ok(())
     }
 
+    /// Walks all the remaining fields without reading them, recording where each of them is
+    ///
+    /// Offsets are counted from the current position, e.g. from the first byte of a message read
+    /// from a slice, so that fields can later be read on demand with `IndexedField::reader`
+    pub fn index_fields(&mut self) -> Result<FieldIndex> {
+        let len = self.len;
+        let mut index = FieldIndex::default();
+        while !self.is_eof() {
+            let start = len - self.len;
+            let (number, wire_type) = WireType::from_tag(self.next_tag()?)?;
+            let value_start = len - self.len;
+            self.skip_field(number, wire_type)?;
+            index.push(IndexedField { number, wire_type, start, value: value_start..len - self.len });
+        }
+        Ok(index)
+    }
+
+    /// Reads all the remaining bytes as a MessageSet (legacy wire format of the proto2
+    /// `message_set_wire_format` option), e.g. a whole message read from a slice
+    ///
+    /// Items with the same type id are merged, fields other than items are skipped.
+    /// Fails with `MissingField` if an item has no type id
+    pub fn read_message_set(&mut self) -> Result<MessageSet> {
+        let mut set = MessageSet::new();
+        while !self.is_eof() {
+            match self.next_tag()? {
+                message_set::ITEM_START => {
+                    let mut type_id = None;
+                    let mut message = Vec::new();
+                    loop {
+                        match self.next_tag()? {
+                            message_set::TYPE_ID => type_id = Some(self.read_uint32()?),
+                            message_set::MESSAGE => message = self.read_bytes()?,
+                            message_set::ITEM_END => break,
+                            t => self.read_unknown(t)?,
+                        }
+                    }
+                    let type_id = type_id.ok_or(ErrorKind::MissingField("type_id"))?;
+                    set.merge_raw(type_id, message);
+                }
+                t => self.read_unknown(t)?,
+            }
+        }
+        Ok(set)
+    }
+
+    /// Skips a group, once its start group tag has been read, up to its matching end group tag
+    pub fn skip_group(&mut self, field_number: u32) -> Result<()> {
+        self.scan_group(field_number, &mut None).map(|_| ())
+    }
+
+    /// Reads a group, once its start group tag has been read, into its raw encoded bytes
+    ///
+    /// The bytes are the fields of the group as found in the input, excluding
+    /// both the start group and the end group tags: they can be forwarded with
+    /// `Writer::write_group_raw` without being understood
+    pub fn read_group_raw(&mut self, field_number: u32) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        let end = self.scan_group(field_number, &mut Some(&mut buf))?;
+        buf.truncate(end);
+        Ok(buf)
+    }
+
+    /// Reads the value of an unknown field, once its tag has been read, into its raw encoded bytes
+    ///
+    /// Groups are read up to, and including, their end group tag, so that writing
+    /// the tag then the bytes gives back the original field
+    pub fn read_unknown_raw(&mut self, tag_value: u32) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.scan_field(tag_value, &mut Some(&mut buf))?;
+        Ok(buf)
+    }
+
+    /// Reads the value of a field, copying its bytes into `out`
+    fn scan_field(&mut self, tag_value: u32, out: &mut Option<&mut Vec<u8>>) -> Result<()> {
+        let (number, wire_type) = WireType::from_tag(tag_value)?;
+        match wire_type {
+            WireType::Varint => { self.scan_varint(out)?; },
+            WireType::Fixed64 => {
+                self.check_fixed_len(8)?;
+                self.scan_bytes(8, out)?;
+            }
+            WireType::Fixed32 => {
+                self.check_fixed_len(4)?;
+                self.scan_bytes(4, out)?;
+            }
+            WireType::LengthDelimited => {
+                let len = self.scan_varint(out)?;
+                let len = self.check_len(len)?;
+                self.scan_bytes(len, out)?;
+            }
+            WireType::StartGroup => { self.scan_group(number, out)?; },
+            WireType::EndGroup => return Err(ErrorKind::UnmatchedEndGroup(number).into()),
+        }
+        Ok(())
+    }
+
+    /// Reads all fields up to, and including, the end group tag of `field_number`, copying their bytes into `out`
+    ///
+    /// Returns the position of the end group tag in `out`
+    fn scan_group(&mut self, field_number: u32, out: &mut Option<&mut Vec<u8>>) -> Result<usize> {
+        if self.depth >= self.options.max_depth {
+            return Err(ErrorKind::RecursionLimit(self.options.max_depth).into());
+        }
+        self.depth += 1;
+        loop {
+            let start = out.as_ref().map_or(0, |o| o.len());
+            let tag = self.scan_varint(out)? as u32;
+            if tag == field_number << 3 | WireType::EndGroup as u32 {
+                self.depth -= 1;
+                return Ok(start);
+            }
+            self.scan_field(tag, out)?;
+        }
+    }
+
+    /// Reads a varint as is, copying its bytes into `out`
+    fn scan_varint(&mut self, out: &mut Option<&mut Vec<u8>>) -> Result<u64> {
+        let mut r: u64 = 0;
+        for i in 0..10 {
+            let b = self.read_byte()?;
+            if let Some(ref mut o) = *out {
+                o.push(b);
+            }
+            if i == 9 && b > 1 {
+                break;
+            }
+            r |= ((b & 0x7f) as u64) << (7 * i);
+            if b < 0x80 {
+                return Ok(r);
+            }
+        }
+        Err(ErrorKind::Varint.into())
+    }
+
+    /// Reads `len` bytes, copying them into `out`
+    fn scan_bytes(&mut self, len: usize, out: &mut Option<&mut Vec<u8>>) -> Result<()> {
+        self.sub_len(len);
+        match *out {
+            Some(ref mut o) => {
+                let start = o.len();
+                let end = start.checked_add(len).ok_or(ErrorKind::LengthOverflow(len as u64))?;
+                if end > self.options.max_alloc {
+                    return Err(ErrorKind::AllocLimit(end, self.options.max_alloc).into());
+                }
+                o.resize(end, 0);
+                self.inner.read_exact(&mut o[start..])?;
+            }
+            None => {
+                let skipped = ::std::io::copy(&mut (&mut self.inner).take(len as u64), &mut ::std::io::sink())?;
+                if skipped < len as u64 {
+                    return Err(ErrorKind::UnexpectedEof.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the number of bytes read since the creation of the reader
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Error of the string of the last field, read from `start`, which is not valid utf8
+    fn invalid_utf8(&self, start: usize, e: Utf8Error) -> Error {
+        ErrorKind::InvalidUtf8(self.tag >> 3, start + e.valid_up_to()).into()
+    }
+
     /// Gets the remaining length of bytes not read yet
     pub fn len(&self) -> usize {
         self.len
@@ -220,6 +1286,124 @@ impl<R: Read> Reader<R> {
     pub fn is_eof(&self) -> bool {
         self.len == 0
     }
+
+    /// Iterates over the fields of the message without any schema
+    pub fn fields<'a>(&'a mut self) -> FieldReader<'a, R> {
+        FieldReader { reader: self, tag: 0 }
+    }
+}
+
+/// A schema-less reader yielding the fields of a message one by one
+///
+/// Each call to `next_field` only reads the field tag: the value must then
+/// either be read with the matching `read_*` method or skipped
+pub struct FieldReader<'a, R: 'a> {
+    reader: &'a mut Reader<R>,
+    tag: u32,
+}
+
+impl<'a, R: Read> FieldReader<'a, R> {
+
+    /// Reads the next field tag and returns its `(field_number, wire_type)`
+    ///
+    /// Returns `None` once all the bytes have been read
+    pub fn next_field(&mut self) -> Result<Option<(u32, WireType)>> {
+        let field = self.reader.next_field()?;
+        if let Some((number, wire_type)) = field {
+            self.tag = number << 3 | wire_type.as_u8() as u32;
+        }
+        Ok(field)
+    }
+
+    /// Skips the value of the last field returned by `next_field`
+    pub fn skip(&mut self) -> Result<()> {
+        self.reader.read_unknown(self.tag)
+    }
+
+    /// Gets the underlying reader, to read the value of the current field
+    pub fn reader(&mut self) -> &mut Reader<R> {
+        self.reader
+    }
+}
+
+/// A number stored on a fixed number of little endian bytes: the values of `fixed32`, `fixed64`,
+/// `sfixed32`, `sfixed64`, `float` and `double` fields
+///
+/// Packed fields of such values are read in bulk by `Reader::read_packed_fixed`, or borrowed by
+/// `Reader::read_packed_fixed_borrowed`
+///
+/// # Safety
+///
+/// Any `size_of::<Self>()` bytes must be a valid value, as they are copied as is
+pub unsafe trait Fixed: Copy {}
+
+unsafe impl Fixed for u32 {}
+unsafe impl Fixed for i32 {}
+unsafe impl Fixed for u64 {}
+unsafe impl Fixed for i64 {}
+unsafe impl Fixed for f32 {}
+unsafe impl Fixed for f64 {}
+
+/// An iterator over the values of a packed repeated field
+///
+/// Reads the content of the field (without its length prefix), for instance
+/// a standalone blob of packed values: `Packed::sint64(bytes)`
+pub struct Packed<'a, M> {
+    reader: Reader<&'a [u8]>,
+    read: fn(&mut Reader<&'a [u8]>) -> Result<M>,
+}
+
+macro_rules! packed_constructors {
+    ($($name:ident: $read:ident => $ty:ty,)*) => ($(
+        impl<'a> Packed<'a, $ty> {
+            #[doc = concat!("Iterates over packed `", stringify!($name), "` values")]
+            pub fn $name(bytes: &'a [u8]) -> Packed<'a, $ty> {
+                Packed::new(bytes, Reader::$read)
+            }
+        }
+    )*);
+}
+
+packed_constructors! {
+    int32: read_int32 => i32,
+    int64: read_int64 => i64,
+    uint32: read_uint32 => u32,
+    uint64: read_uint64 => u64,
+    sint32: read_sint32 => i32,
+    sint64: read_sint64 => i64,
+    bool: read_bool => bool,
+    fixed32: read_fixed32 => u32,
+    fixed64: read_fixed64 => u64,
+    sfixed32: read_sfixed32 => i32,
+    sfixed64: read_sfixed64 => i64,
+    float: read_float => f32,
+    double: read_double => f64,
+}
+
+impl<'a, M> Packed<'a, M> {
+    /// Iterates over the values of `bytes`, read one by one with `read`
+    pub fn new(bytes: &'a [u8], read: fn(&mut Reader<&'a [u8]>) -> Result<M>) -> Packed<'a, M> {
+        Packed {
+            reader: Reader::from_reader(bytes, bytes.len()),
+            read,
+        }
+    }
+}
+
+impl<'a, M> Iterator for Packed<'a, M> {
+    type Item = Result<M>;
+
+    fn next(&mut self) -> Option<Result<M>> {
+        if self.reader.is_eof() {
+            return None;
+        }
+        let v = (self.read)(&mut self.reader);
+        if v.is_err() {
+            // the remaining bytes cannot be trusted
+            self.reader.len = 0;
+        }
+        Some(v)
+    }
 }
 
 #[test]
@@ -0,0 +1,146 @@
+//! A module to print messages in the protobuf text format
+//!
+//! Generated code (pb-rs `--display` option) implements `TextFormat` and `Display`
+//! on messages: `{}` prints them on one line, `{:#}` on several, indented, lines
+
+use std::fmt::{self, Display, Formatter, Write};
+
+/// A message which can be printed in the text format
+pub trait TextFormat {
+
+    /// Prints all the fields of the message which would be written
+    fn fmt_text(&self, t: &mut TextFormatter) -> fmt::Result;
+}
+
+/// A printer of fields in the text format
+///
+/// Numbers, booleans and enums are printed with their `Display` implementation
+pub struct TextFormatter<'a, 'b: 'a> {
+    f: &'a mut Formatter<'b>,
+    multiline: bool,
+    indent: usize,
+    empty: bool,
+}
+
+impl<'a, 'b: 'a> TextFormatter<'a, 'b> {
+
+    /// Creates a new `TextFormatter`, printing multiple lines with the alternate flag (`{:#}`)
+    pub fn new(f: &'a mut Formatter<'b>) -> TextFormatter<'a, 'b> {
+        let multiline = f.alternate();
+        TextFormatter {
+            f,
+            multiline,
+            indent: 0,
+            empty: true,
+        }
+    }
+
+    /// Prints all the fields of a top level message
+    pub fn write<M: TextFormat>(&mut self, m: &M) -> fmt::Result {
+        m.fmt_text(self)
+    }
+
+    /// Prints a scalar (number, bool or enum) field
+    pub fn scalar<T: Display>(&mut self, name: &str, v: &T) -> fmt::Result {
+        self.start(name)?;
+        write!(self.f, ": {}", v)?;
+        self.end()
+    }
+
+    /// Prints a float or double field, with `nan`, `inf` and `-inf` for non finite values
+    pub fn float<T: Display + Copy + Into<f64>>(&mut self, name: &str, v: &T) -> fmt::Result {
+        self.start(name)?;
+        let f: f64 = (*v).into();
+        if f.is_nan() {
+            self.f.write_str(": nan")?;
+        } else if f.is_infinite() {
+            self.f.write_str(if f > 0. { ": inf" } else { ": -inf" })?;
+        } else {
+            write!(self.f, ": {}", v)?;
+        }
+        self.end()
+    }
+
+    /// Prints a string field, escaping quotes and control characters
+    pub fn string(&mut self, name: &str, v: &str) -> fmt::Result {
+        self.start(name)?;
+        self.f.write_str(": \"")?;
+        for c in v.chars() {
+            if (c as u32) < 0x80 {
+                self.write_escaped(c as u8)?;
+            } else {
+                self.f.write_char(c)?;
+            }
+        }
+        self.f.write_char('"')?;
+        self.end()
+    }
+
+    /// Prints a bytes field, escaping non printable bytes in octal
+    pub fn bytes(&mut self, name: &str, v: &[u8]) -> fmt::Result {
+        self.start(name)?;
+        self.f.write_str(": \"")?;
+        for b in v {
+            self.write_escaped(*b)?;
+        }
+        self.f.write_char('"')?;
+        self.end()
+    }
+
+    /// Prints a message field, between braces
+    pub fn message<M: TextFormat>(&mut self, name: &str, m: &M) -> fmt::Result {
+        self.start(name)?;
+        self.f.write_str(" {")?;
+        if self.multiline {
+            self.f.write_char('\n')?;
+        }
+        self.indent += 1;
+        m.fmt_text(self)?;
+        self.indent -= 1;
+        if self.multiline {
+            self.write_indent()?;
+        } else {
+            self.f.write_char(' ')?;
+        }
+        self.f.write_char('}')?;
+        self.end()
+    }
+
+    fn start(&mut self, name: &str) -> fmt::Result {
+        if self.multiline {
+            self.write_indent()?;
+        } else if !self.empty {
+            self.f.write_char(' ')?;
+        }
+        self.empty = false;
+        self.f.write_str(name)
+    }
+
+    fn end(&mut self) -> fmt::Result {
+        if self.multiline {
+            self.f.write_char('\n')
+        } else {
+            Ok(())
+        }
+    }
+
+    fn write_indent(&mut self) -> fmt::Result {
+        for _ in 0..self.indent {
+            self.f.write_str("  ")?;
+        }
+        Ok(())
+    }
+
+    fn write_escaped(&mut self, b: u8) -> fmt::Result {
+        match b {
+            b'"' => self.f.write_str("\\\""),
+            b'\'' => self.f.write_str("\\'"),
+            b'\\' => self.f.write_str("\\\\"),
+            b'\n' => self.f.write_str("\\n"),
+            b'\r' => self.f.write_str("\\r"),
+            b'\t' => self.f.write_str("\\t"),
+            0x20..=0x7E => self.f.write_char(b as char),
+            b => write!(self.f, "\\{:03o}", b),
+        }
+    }
+}
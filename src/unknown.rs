@@ -0,0 +1,101 @@
+//! A module to keep the fields of a message which are not defined in its .proto file
+//!
+//! Generated code (pb-rs `--unknown-fields` option) stores them in an `unknown_fields`
+//! member, so that they are written back instead of being lost
+
+use std::io::{Read, Write};
+
+use errors::{Result, ErrorKind};
+use line_delimited;
+use reader::Reader;
+use writer::Writer;
+use sizeofs::sizeof_varint;
+
+/// Unknown fields of a message, in the order they have been read
+///
+/// Each field is kept as its tag and its raw encoded value
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct UnknownFields {
+    fields: Vec<(u32, Vec<u8>)>,
+}
+
+impl UnknownFields {
+
+    /// Reads the value of a field, once its `tag` has been read
+    pub fn read_field<R: Read>(&mut self, r: &mut Reader<R>, tag: u32) -> Result<()> {
+        let value = r.read_unknown_raw(tag)?;
+        self.fields.push((tag, value));
+        Ok(())
+    }
+
+    /// Iterates over all the fields as (tag, raw encoded value)
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &[u8])> {
+        self.fields.iter().map(|f| (f.0, &*f.1))
+    }
+
+    /// Gets the number of fields
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Checks if there is no unknown field
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Removes all fields, keeping the allocated memory
+    pub fn clear(&mut self) {
+        self.fields.clear();
+    }
+
+    /// Computes the binary size of all fields, tags included
+    pub fn get_size(&self) -> usize {
+        self.fields.iter().map(|f| sizeof_varint(f.0 as u64) + f.1.len()).sum()
+    }
+
+    /// Gets a copy of the fields sorted by increasing field number
+    ///
+    /// The sort is stable: fields with the same number keep the order they have been read in
+    pub fn sorted(&self) -> UnknownFields {
+        let mut fields = self.fields.clone();
+        fields.sort_by_key(|f| f.0 >> 3);
+        UnknownFields { fields }
+    }
+
+    /// Writes all fields, tags included, with `Writer::write_unknown_fields`
+    pub fn write<W: Write>(&self, w: &mut Writer<W>) -> Result<()> {
+        w.write_unknown_fields(self)
+    }
+
+    /// Encodes all fields, tags included, in the order they have been read in
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.get_size());
+        self.write(&mut Writer::new(&mut bytes)).expect("writing into a Vec cannot fail");
+        bytes
+    }
+
+    /// Reads the fields encoded in `bytes`, tags included
+    pub fn from_bytes(bytes: &[u8]) -> Result<UnknownFields> {
+        let mut r = Reader::from_reader(bytes, bytes.len());
+        let mut fields = UnknownFields::default();
+        while !r.is_eof() {
+            let tag = r.next_tag()?;
+            fields.read_field(&mut r, tag)?;
+        }
+        Ok(fields)
+    }
+
+    /// Encodes all fields in padded standard base64, e.g. to keep them in JSON
+    /// (pb-rs `--serde-unknown-fields` option)
+    pub fn to_base64(&self) -> String {
+        String::from_utf8(line_delimited::encode(&self.to_bytes())).expect("base64 is ascii")
+    }
+
+    /// Reads the fields encoded in standard base64, with or without padding
+    pub fn from_base64(s: &str) -> Result<UnknownFields> {
+        match line_delimited::decode(s.as_bytes()) {
+            Some(bytes) => UnknownFields::from_bytes(&bytes),
+            None => Err(ErrorKind::Msg("unknown fields are not valid base64".to_string()).into()),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! A module to check the constraints set on fields in a .proto file
+//!
+//! Generated code (pb-rs `--validate` option) implements `Validate` on messages, checking the
+//! `(rust.required)`, `(rust.non_empty)`, `(rust.max_len)`, `(rust.min)` and `(rust.max)` field options
+
+use std::fmt;
+
+/// A constraint set on a field with a custom option
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constraint {
+    /// `(rust.required)`: an optional field must be set
+    Required,
+    /// `(rust.non_empty)`: a repeated, string or bytes field must not be empty
+    NonEmpty,
+    /// `(rust.max_len)`: a string must have at most this many chars, bytes at most this many bytes
+    MaxLen(usize),
+    /// `(rust.min)`: a number must be greater than or equal to this bound
+    Min(f64),
+    /// `(rust.max)`: a number must be less than or equal to this bound
+    Max(f64),
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Constraint::Required => f.write_str("must be set"),
+            Constraint::NonEmpty => f.write_str("must not be empty"),
+            Constraint::MaxLen(n) => write!(f, "must have a length of at most {}", n),
+            Constraint::Min(n) => write!(f, "must be at least {}", n),
+            Constraint::Max(n) => write!(f, "must be at most {}", n),
+        }
+    }
+}
+
+/// A constraint violated by a field
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    /// Path of the field from the validated message, e.g. `name`, or `items[2].name` for a field
+    /// of the third message of a repeated `items` field
+    pub field: String,
+    /// The violated constraint
+    pub constraint: Constraint,
+}
+
+impl ValidationError {
+
+    /// Creates a new `ValidationError` for the field `name` of the message at `path`
+    pub fn new(path: &str, name: &str, constraint: Constraint) -> ValidationError {
+        ValidationError { field: format!("{}{}", path, name), constraint }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.field, self.constraint)
+    }
+}
+
+impl ::std::error::Error for ValidationError {}
+
+/// A message whose fields can be checked against their constraints
+pub trait Validate {
+
+    /// Appends the constraints violated by the fields of the message, and of its nested messages,
+    /// to `errors`, their path prefixed by `path` (empty for the validated message)
+    fn validate_into(&self, path: &str, errors: &mut Vec<ValidationError>);
+
+    /// Checks all the constraints, returning all the violated ones if any
+    fn validate(&self) -> ::std::result::Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        self.validate_into("", &mut errors);
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
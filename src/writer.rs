@@ -2,8 +2,11 @@
 
 use std::io::Write;
 
-use errors::Result;
+use errors::{Result, ErrorKind};
 use message::MessageWrite;
+use sizeofs::{sizeof_varint, sizeof_var_length};
+use unknown::UnknownFields;
+use message_set::{self, MessageSet};
 
 use byteorder::WriteBytesExt;
 use byteorder::LittleEndian as LE;
@@ -11,17 +14,83 @@ use byteorder::LittleEndian as LE;
 /// A struct to write protobuf messages
 pub struct Writer<W> {
     inner: W,
+    written: usize,
+    max_len: Option<usize>,
+    sort_unknown_fields: bool,
+    canonicalize_nan: bool,
 }
 
 impl<W: Write> Writer<W> {
 
     /// Creates a new `ProtobufWriter`
     pub fn new(w: W) -> Writer<W> {
-        Writer { inner: w }
+        Writer { inner: w, written: 0, max_len: None, sort_unknown_fields: false, canonicalize_nan: false }
+    }
+
+    /// Sets the maximum number of bytes this writer can write
+    ///
+    /// Once reached, writes fail with `ErrorKind::SizeLimit` instead of producing an
+    /// oversized output. Nested messages are checked before being written
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = Some(max_len);
+    }
+
+    /// Sets the order `write_unknown_fields` writes unknown fields in, for this message and its
+    /// nested messages: the order they have been read in (the default), or sorted by field number
+    ///
+    /// Sorting makes the output deterministic whatever the order unknown fields have been read
+    /// or merged in, e.g. to sign the encoded bytes
+    pub fn set_sort_unknown_fields(&mut self, sort: bool) {
+        self.sort_unknown_fields = sort;
+    }
+
+    /// Sets whether NaN `float` and `double` values are all written with the bit pattern of
+    /// `f32::NAN` and `f64::NAN`, whatever their sign and payload (default `false`)
+    ///
+    /// Messages equal by value, including NaN values, are then written to the same bytes, e.g. to
+    /// deduplicate them by hash. By default, values are written bit for bit
+    pub fn set_canonicalize_nan(&mut self, canonicalize: bool) {
+        self.canonicalize_nan = canonicalize;
+    }
+
+    /// `v`, or `f32::NAN` if it is a NaN which must be canonicalized
+    fn float_value(&self, v: f32) -> f32 {
+        if self.canonicalize_nan && v.is_nan() { f32::NAN } else { v }
+    }
+
+    /// `v`, or `f64::NAN` if it is a NaN which must be canonicalized
+    fn double_value(&self, v: f64) -> f64 {
+        if self.canonicalize_nan && v.is_nan() { f64::NAN } else { v }
+    }
+
+    /// Unwraps the underlying writer, e.g. a hasher to finalize once a message has been written through it
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Gets the number of bytes written so far
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Counts `len` more bytes as written, failing if it exceeds the maximum length
+    fn consume(&mut self, len: usize) -> Result<()> {
+        self.check_len(len)?;
+        self.written += len;
+        Ok(())
+    }
+
+    /// Fails early if `len` more bytes cannot be written, without counting them
+    fn check_len(&self, len: usize) -> Result<()> {
+        match self.max_len {
+            Some(max_len) if self.written + len > max_len => Err(ErrorKind::SizeLimit(max_len).into()),
+            _ => Ok(()),
+        }
     }
 
     /// Writes a `varint` (compacted `u64`)
     pub fn write_varint(&mut self, mut v: u64) -> Result<()> {
+        self.consume(sizeof_varint(v))?;
         while v > 0x7F {
             self.inner.write_u8(((v as u8) & 0x7F) | 0x80)?;
             v >>= 7;
@@ -56,7 +125,7 @@ impl<W: Write> Writer<W> {
 
     /// Writes a `sint32` which is internally coded as a `varint`
     pub fn write_sint32(&mut self, v: i32) -> Result<()> {
-        self.write_varint(((v << 1) ^ (v >> 31)) as u64)
+        self.write_varint(((v << 1) ^ (v >> 31)) as u32 as u64)
     }
 
     /// Writes a `sint64` which is internally coded as a `varint`
@@ -66,31 +135,39 @@ impl<W: Write> Writer<W> {
 
     /// Writes a `fixed64` which is little endian coded `u64`
     pub fn write_fixed64(&mut self, v: u64) -> Result<()> {
+        self.consume(8)?;
         self.inner.write_u64::<LE>(v).map_err(|e| e.into())
     }
 
     /// Writes a `fixed32` which is little endian coded `u32`
     pub fn write_fixed32(&mut self, v: u32) -> Result<()> {
+        self.consume(4)?;
         self.inner.write_u32::<LE>(v).map_err(|e| e.into())
     }
 
     /// Writes a `sfixed64` which is little endian coded `i64`
     pub fn write_sfixed64(&mut self, v: i64) -> Result<()> {
+        self.consume(8)?;
         self.inner.write_i64::<LE>(v).map_err(|e| e.into())
     }
 
     /// Writes a `sfixed32` which is little endian coded `i32`
     pub fn write_sfixed32(&mut self, v: i32) -> Result<()> {
+        self.consume(4)?;
         self.inner.write_i32::<LE>(v).map_err(|e| e.into())
     }
 
     /// Writes a `float`
     pub fn write_float(&mut self, v: f32) -> Result<()> {
+        self.consume(4)?;
+        let v = self.float_value(v);
         self.inner.write_f32::<LE>(v).map_err(|e| e.into())
     }
 
     /// Writes a `double`
     pub fn write_double(&mut self, v: f64) -> Result<()> {
+        self.consume(8)?;
+        let v = self.double_value(v);
         self.inner.write_f64::<LE>(v).map_err(|e| e.into())
     }
 
@@ -106,7 +183,9 @@ impl<W: Write> Writer<W> {
 
     /// Writes `bytes`: length first then the chunk of data
     pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.check_len(sizeof_var_length(bytes.len()))?;
         self.write_varint(bytes.len() as u64)?;
+        self.consume(bytes.len())?;
         self.inner.write_all(bytes).map_err(|e| e.into())
     }
 
@@ -131,24 +210,115 @@ impl<W: Write> Writer<W> {
         Ok(())
     }
 
+    /// Writes packed repeated field from an iterator, without collecting its items
+    ///
+    /// The length prefix is computed with `size` on a clone of `iter`, then the items are written
+    /// with `write`: both passes must yield the same items (e.g. `v.iter().map(|m| m.id)`)
+    pub fn write_packed_from_iter<T, I, F, S>(&mut self, iter: I, mut write: F, size: S) -> Result<()>
+        where I: Iterator<Item = T> + Clone,
+              F: FnMut(&mut Self, T) -> Result<()>,
+              S: Fn(&T) -> usize,
+    {
+        let len: usize = iter.clone().map(|t| size(&t)).sum();
+        self.check_len(sizeof_var_length(len))?;
+        self.write_varint(len as u64)?;
+        let start = self.written;
+        for t in iter {
+            write(self, t)?;
+        }
+        debug_assert_eq!(len, self.written - start, "iterator yielded different items when cloned");
+        Ok(())
+    }
+
     /// Writes packed repeated field when we know the size of items
     ///
     /// `item_size` is internally used to compute the total length
     /// As the length is fixed (and the same as rust internal representation, we can directly dump
-    /// all data at once
+    /// all data at once. Protobuf being little endian, each item is byte swapped on big endian hosts,
+    /// one at a time, without copying the whole field
     pub fn write_packed_fixed_size<M>(&mut self, v: &[M], item_size: usize) -> Result<()> {
         let len = v.len() * item_size;
         let bytes = unsafe { ::std::slice::from_raw_parts(v as *const [M] as *const M as *const u8, len) };
-        self.write_bytes(bytes)
+        if cfg!(target_endian = "big") {
+            self.check_len(sizeof_var_length(len))?;
+            self.write_varint(len as u64)?;
+            self.consume(len)?;
+            let mut swapped = [0u8; 8];
+            for item in bytes.chunks(item_size) {
+                let swapped = &mut swapped[..item_size];
+                swapped.copy_from_slice(item);
+                swapped.reverse();
+                self.inner.write_all(swapped)?;
+            }
+            Ok(())
+        } else {
+            self.write_bytes(bytes)
+        }
     }
 
     /// Writes a message which implements `MessageWrite`
     pub fn write_message<M: MessageWrite>(&mut self, m: &M) -> Result<()> {
         let len = m.get_size();
+        self.check_len(sizeof_var_length(len))?;
         self.write_varint(len as u64)?;
         m.write_message(self)
     }
 
+    /// Writes a record of a tagged union: `discriminator` then the length delimited message,
+    /// as read by `Reader::read_tagged_union`
+    pub fn write_tagged_union<M: MessageWrite>(&mut self, discriminator: u8, m: &M) -> Result<()> {
+        self.consume(1)?;
+        self.inner.write_u8(discriminator)?;
+        self.write_message(m)
+    }
+
+    /// Writes a group of `field_number` from its raw encoded fields, as read by `Reader::read_group_raw`
+    pub fn write_group_raw(&mut self, field_number: u32, bytes: &[u8]) -> Result<()> {
+        self.write_tag(field_number << 3 | 3)?;
+        self.write_raw_bytes(bytes)?;
+        self.write_tag(field_number << 3 | 4)
+    }
+
+    /// Writes the items of a MessageSet (legacy wire format of the proto2 `message_set_wire_format`
+    /// option), each as an item group with its type id and its encoded message
+    pub fn write_message_set(&mut self, set: &MessageSet) -> Result<()> {
+        for (type_id, message) in set.iter() {
+            self.write_tag(message_set::ITEM_START)?;
+            self.write_tag(message_set::TYPE_ID)?;
+            self.write_uint32(type_id)?;
+            self.write_tag(message_set::MESSAGE)?;
+            self.write_bytes(message)?;
+            self.write_tag(message_set::ITEM_END)?;
+        }
+        Ok(())
+    }
+
+    /// Writes already encoded bytes as is, e.g. as read by `Reader::read_unknown_raw`
+    pub fn write_raw_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.consume(bytes.len())?;
+        self.inner.write_all(bytes).map_err(|e| e.into())
+    }
+
+    /// Writes unknown fields, tags included, as set by `set_sort_unknown_fields`
+    ///
+    /// - by default, in the order they have been read in, so a message is written back byte for byte
+    ///   if its known fields were written in increasing number order
+    /// - sorted, by increasing field number, fields with the same number keeping the order they
+    ///   have been read in (which matters for repeated fields and merged messages)
+    ///
+    /// In both cases they are written after all the known fields
+    pub fn write_unknown_fields(&mut self, fields: &UnknownFields) -> Result<()> {
+        let mut fields = fields.iter().collect::<Vec<_>>();
+        if self.sort_unknown_fields {
+            fields.sort_by_key(|f| f.0 >> 3);
+        }
+        for (tag, value) in fields {
+            self.write_tag(tag)?;
+            self.write_raw_bytes(value)?;
+        }
+        Ok(())
+    }
+
     /// Writes tag then `int32`
     pub fn write_int32_with_tag(&mut self, tag: u32, v: i32) -> Result<()> {
         self.write_tag(tag)?;
@@ -188,37 +358,41 @@ impl<W: Write> Writer<W> {
     /// Writes tag then `fixed64`
     pub fn write_fixed64_with_tag(&mut self, tag: u32, v: u64) -> Result<()> {
         self.write_tag(tag)?;
+        self.consume(8)?;
         self.inner.write_u64::<LE>(v).map_err(|e| e.into())
     }
 
     /// Writes tag then `fixed32`
     pub fn write_fixed32_with_tag(&mut self, tag: u32, v: u32) -> Result<()> {
         self.write_tag(tag)?;
+        self.consume(4)?;
         self.inner.write_u32::<LE>(v).map_err(|e| e.into())
     }
 
     /// Writes tag then `sfixed64`
     pub fn write_sfixed64_with_tag(&mut self, tag: u32, v: i64) -> Result<()> {
         self.write_tag(tag)?;
+        self.consume(8)?;
         self.inner.write_i64::<LE>(v).map_err(|e| e.into())
     }
 
     /// Writes tag then `sfixed32`
     pub fn write_sfixed32_with_tag(&mut self, tag: u32, v: i32) -> Result<()> {
         self.write_tag(tag)?;
+        self.consume(4)?;
         self.inner.write_i32::<LE>(v).map_err(|e| e.into())
     }
 
     /// Writes tag then `float`
     pub fn write_float_with_tag(&mut self, tag: u32, v: f32) -> Result<()> {
         self.write_tag(tag)?;
-        self.inner.write_f32::<LE>(v).map_err(|e| e.into())
+        self.write_float(v)
     }
 
     /// Writes tag then `double`
     pub fn write_double_with_tag(&mut self, tag: u32, v: f64) -> Result<()> {
         self.write_tag(tag)?;
-        self.inner.write_f64::<LE>(v).map_err(|e| e.into())
+        self.write_double(v)
     }
 
     /// Writes tag then `bool`
@@ -230,8 +404,7 @@ impl<W: Write> Writer<W> {
     /// Writes tag then `bytes`
     pub fn write_bytes_with_tag(&mut self, tag: u32, bytes: &[u8]) -> Result<()> {
         self.write_tag(tag)?;
-        self.write_varint(bytes.len() as u64)?;
-        self.inner.write_all(bytes).map_err(|e| e.into())
+        self.write_bytes(bytes)
     }
 
     /// Writes tag then `string`
@@ -264,6 +437,21 @@ impl<W: Write> Writer<W> {
         Ok(())
     }
 
+    /// Writes tag then packed repeated field from an iterator, see `write_packed_from_iter`
+    ///
+    /// Writes nothing if the iterator is empty
+    pub fn write_packed_from_iter_with_tag<T, I, F, S>(&mut self, tag: u32, iter: I, write: F, size: S) -> Result<()>
+        where I: Iterator<Item = T> + Clone,
+              F: FnMut(&mut Self, T) -> Result<()>,
+              S: Fn(&T) -> usize,
+    {
+        if iter.clone().next().is_none() {
+            return Ok(());
+        }
+        self.write_tag(tag)?;
+        self.write_packed_from_iter(iter, write, size)
+    }
+
     /// Writes tag then repeated field with fixed length item size
     ///
     /// If array is empty, then do nothing (do not even write the tag)
@@ -275,9 +463,27 @@ impl<W: Write> Writer<W> {
             return Ok(());
         }
         self.write_tag(tag)?;
-        let len = v.len() * item_size;
-        let bytes = unsafe { ::std::slice::from_raw_parts(v as *const [M] as *const M as *const u8, len) };
-        self.write_bytes(bytes)
+        self.write_packed_fixed_size(v, item_size)
+    }
+
+    /// Writes tag then packed repeated `float` field, all at once unless NaN values must be
+    /// canonicalized (`set_canonicalize_nan`)
+    pub fn write_packed_float_with_tag(&mut self, tag: u32, v: &[f32]) -> Result<()> {
+        if self.canonicalize_nan && v.iter().any(|f| f.is_nan()) {
+            self.write_packed_repeated_field_with_tag(tag, v, |w, f| w.write_float(*f), &|_| 4)
+        } else {
+            self.write_packed_fixed_size_with_tag(tag, v, 4)
+        }
+    }
+
+    /// Writes tag then packed repeated `double` field, all at once unless NaN values must be
+    /// canonicalized (`set_canonicalize_nan`)
+    pub fn write_packed_double_with_tag(&mut self, tag: u32, v: &[f64]) -> Result<()> {
+        if self.canonicalize_nan && v.iter().any(|f| f.is_nan()) {
+            self.write_packed_repeated_field_with_tag(tag, v, |w, f| w.write_double(*f), &|_| 8)
+        } else {
+            self.write_packed_fixed_size_with_tag(tag, v, 8)
+        }
     }
 
     /// Writes tag then message
@@ -1,8 +1,19 @@
 extern crate quick_protobuf;
 
+use std::borrow::Cow;
 use std::io::{Read, Write};
-use quick_protobuf::{Reader, MessageRead, Writer, MessageWrite, Result};
+use std::sync::Arc;
+use quick_protobuf::{Reader, ReaderOptions, MessageRead, MessageReadBorrowed, Writer, MessageWrite, ProstMessage, Result, UnknownFields};
+use quick_protobuf::errors::ErrorKind;
 use quick_protobuf::sizeofs::*;
+use quick_protobuf::reader::{StringOrBytes, Packed, WireType};
+use quick_protobuf::text::{TextFormat, TextFormatter};
+use quick_protobuf::line_delimited::{LineDelimitedReader, LineDelimitedWriter};
+use quick_protobuf::validate::{Constraint, Validate, ValidationError};
+use quick_protobuf::message_set::MessageSet;
+use quick_protobuf::field_value::{FieldByNumber, FieldValue};
+use quick_protobuf::lazy::LazyMessage;
+use quick_protobuf::pool::{Pool, PooledVec};
 
 macro_rules! write_read_primitive {
     ($name:ident, $read:ident, $write:ident) => (write_read_primitive!($name, $read, $write, 145););
@@ -29,6 +40,53 @@ write_read_primitive!(wr_uint32, read_uint32, write_uint32);
 write_read_primitive!(wr_uint64, read_uint64, write_uint64);
 write_read_primitive!(wr_sint32, read_sint32, write_sint32);
 write_read_primitive!(wr_sint64, read_sint64, write_sint64);
+// Encodings as emitted by protoc
+const SINT32_GOLDEN: &[(i32, &[u8])] = &[
+    (0, &[0x00]),
+    (-1, &[0x01]),
+    (1, &[0x02]),
+    (-2, &[0x03]),
+    (63, &[0x7E]),
+    (-64, &[0x7F]),
+    (64, &[0x80, 0x01]),
+    (2147483647, &[0xFE, 0xFF, 0xFF, 0xFF, 0x0F]),
+    (-2147483648, &[0xFF, 0xFF, 0xFF, 0xFF, 0x0F]),
+];
+
+const SINT64_GOLDEN: &[(i64, &[u8])] = &[
+    (0, &[0x00]),
+    (-1, &[0x01]),
+    (1, &[0x02]),
+    (-64, &[0x7F]),
+    (64, &[0x80, 0x01]),
+    (2147483647, &[0xFE, 0xFF, 0xFF, 0xFF, 0x0F]),
+    (-2147483648, &[0xFF, 0xFF, 0xFF, 0xFF, 0x0F]),
+    (9223372036854775807, &[0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]),
+    (-9223372036854775808, &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]),
+];
+
+macro_rules! golden_primitive {
+    ($name:ident, $read:ident, $write:ident, $sizeof:ident, $golden:expr) => (
+#[test]
+fn $name(){
+    for &(v, bytes) in $golden {
+        let mut buf = Vec::new();
+        {
+            let mut w = Writer::new(&mut buf);
+            w.$write(v).unwrap();
+        }
+        assert_eq!(bytes, &*buf, "writing {}", v);
+        assert_eq!(bytes.len(), $sizeof(v), "size of {}", v);
+        let mut r = Reader::from_reader(bytes, bytes.len());
+        assert_eq!(v, r.$read().unwrap());
+    }
+}
+    );
+}
+
+golden_primitive!(golden_sint32, read_sint32, write_sint32, sizeof_sint32, SINT32_GOLDEN);
+golden_primitive!(golden_sint64, read_sint64, write_sint64, sizeof_sint64, SINT64_GOLDEN);
+
 write_read_primitive!(wr_bool, read_bool, write_bool, true);
 write_read_primitive!(wr_fixed32, read_fixed32, write_fixed32);
 write_read_primitive!(wr_fixed64, read_fixed64, write_fixed64);
@@ -51,6 +109,25 @@ fn wr_bytes(){
     assert_eq!(v, &*r.read_bytes().unwrap());
 }
 
+#[test]
+fn wr_bytes_into(){
+    let v = b"test_write_read";
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_bytes(v).unwrap();
+        w.write_bytes(v).unwrap();
+    }
+    let len = buf.len();
+    let mut buf = &*buf;
+    let mut r = Reader::from_reader(&mut buf, len);
+    let mut out = [0u8; 32];
+    let n = r.read_bytes_into(&mut out).unwrap();
+    assert_eq!(v, &out[..n]);
+    let mut small = [0u8; 4];
+    assert!(r.read_bytes_into(&mut small).is_err());
+}
+
 #[test]
 fn wr_string(){
     let v = "test_write_read";
@@ -65,6 +142,22 @@ fn wr_string(){
     assert_eq!(v, &*r.read_string().unwrap());
 }
 
+#[test]
+fn wr_string_or_bytes(){
+    let v: &[u8] = &[b't', b'e', 0xFF, b's', b't'];
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_string("test").unwrap();
+        w.write_bytes(v).unwrap();
+    }
+    let len = buf.len();
+    let mut buf = &*buf;
+    let mut r = Reader::from_reader(&mut buf, len);
+    assert_eq!(StringOrBytes::String("test".to_string()), r.read_string_or_bytes().unwrap());
+    assert_eq!(StringOrBytes::Bytes(v.to_vec()), r.read_string_or_bytes().unwrap());
+}
+
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 enum TestEnum {
     A = 0,
@@ -83,13 +176,19 @@ impl From<i32> for TestEnum {
     }
 }
 
+impl From<TestEnum> for i32 {
+    fn from(v: TestEnum) -> i32 {
+        v as i32
+    }
+}
+
 #[test]
 fn wr_enum(){
     let v = TestEnum::C;
     let mut buf = Vec::new();
     {
         let mut w = Writer::new(&mut buf);
-        w.write_enum(v as i32).unwrap();
+        w.write_enum(v.into()).unwrap();
     }
     let len = buf.len();
     let mut buf = &*buf;
@@ -104,17 +203,27 @@ struct TestMessage {
 }
 
 impl MessageRead for TestMessage {
-    fn from_reader<R: Read>(mut r: &mut Reader<R>) -> Result<Self> {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
         let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
         while !r.is_eof() {
             match r.next_tag() {
-                Ok(10) => msg.id = Some(r.read_uint32()?),
-                Ok(18) => msg.val.push(r.read_sint64()?),
+                Ok(10) => self.id = Some(r.read_uint32()?),
+                Ok(18) => self.val.push(r.read_sint64()?),
                 Ok(t) => { r.read_unknown(t)?; }
                 Err(e) => return Err(e),
             }
         }
-        Ok(msg)
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.id = None;
+        self.val.clear();
     }
 }
 
@@ -131,6 +240,8 @@ impl MessageWrite for TestMessage {
     }
 }
 
+impl ProstMessage for TestMessage {}
+
 #[test]
 fn wr_message(){
 
@@ -152,6 +263,452 @@ fn wr_message(){
     assert_eq!(buf.len(), sizeof_varint(8) + v.get_size());
 }
 
+#[test]
+fn wr_message_boxed(){
+    let v = TestMessage { id: Some(63), val: vec![53, 5] };
+    let mut buf = Vec::new();
+    Writer::new(&mut buf).write_message(&v).unwrap();
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    assert_eq!(Box::new(v.clone()), r.read_message_boxed::<TestMessage>().unwrap());
+    assert!(r.is_eof());
+
+    // through the MessageRead impl of Box<M>
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    assert_eq!(Box::new(v), r.read_message::<Box<TestMessage>>().unwrap());
+}
+
+#[test]
+fn wr_string_interned(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        for s in &["red", "green", "red", "", "red"] {
+            w.write_string(s).unwrap();
+        }
+    }
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    let strings = (0..5).map(|_| r.read_string_interned().unwrap()).collect::<Vec<_>>();
+    assert!(r.is_eof());
+    assert_eq!(vec!["red", "green", "red", "", "red"], strings.iter().map(|s| &**s).collect::<Vec<_>>());
+    assert!(Arc::ptr_eq(&strings[0], &strings[2]) && Arc::ptr_eq(&strings[0], &strings[4]));
+
+    // strings are shared with the messages read by another reader
+    let interner = r.take_interner();
+    assert_eq!(3, interner.len());
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    r.set_interner(interner);
+    assert!(Arc::ptr_eq(&strings[0], &r.read_string_interned().unwrap()));
+
+    let invalid = [2, 0xc3, 0x28];
+    let mut r = Reader::from_reader(&invalid[..], invalid.len());
+    assert!(r.read_string_interned().is_err());
+}
+
+#[test]
+fn wr_reader_send(){
+    fn assert_send<T: Send>() {}
+    assert_send::<Reader<&'static [u8]>>();
+    assert_send::<Reader<::std::fs::File>>();
+    assert_send::<::quick_protobuf::intern::Interner>();
+}
+
+#[test]
+fn wr_line_delimited(){
+    let messages = vec![
+        TestMessage { id: Some(63), val: vec![53, 5] },
+        TestMessage::default(),
+        TestMessage { id: Some(1), val: vec![] },
+        TestMessage { id: None, val: vec![-1, 743] },
+    ];
+    let mut w = LineDelimitedWriter::new(Vec::new());
+    for m in &messages {
+        w.write_message(m).unwrap();
+    }
+    let buf = w.into_inner();
+    assert_eq!(b"Cj8SahIK\n\nCgE=\nEgESzgs=\n", &*buf);
+
+    let mut r = LineDelimitedReader::new(&*buf);
+    for m in &messages {
+        assert_eq!(Some(m.clone()), r.read_message().unwrap());
+    }
+    assert_eq!(None, r.read_message::<TestMessage>().unwrap());
+
+    // invalid lines fail but do not stop the stream, padding and trailing newline are optional
+    let mut r = LineDelimitedReader::new(&b"Cj8SahIK\r\nC$A=\nCgE\nEgESzgs="[..]);
+    assert_eq!(Some(messages[0].clone()), r.read_message().unwrap());
+    match *r.read_message::<TestMessage>().unwrap_err().kind() {
+        ErrorKind::Base64(2) => (),
+        ref e => panic!("expecting Base64, got {:?}", e),
+    }
+    assert_eq!(Some(messages[2].clone()), r.read_message().unwrap());
+    assert_eq!(Some(messages[3].clone()), r.read_message().unwrap());
+    assert_eq!(None, r.read_message::<TestMessage>().unwrap());
+}
+
+#[test]
+fn wr_all_messages(){
+    let messages = vec![
+        TestMessage { id: Some(1), val: vec![2] },
+        TestMessage::default(),
+        TestMessage { id: None, val: vec![-3, 4] },
+    ];
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        for m in &messages {
+            w.write_message(m).unwrap();
+        }
+    }
+    let read = |bytes: &[u8], max| {
+        let mut buf = bytes;
+        let mut r = Reader::from_reader(&mut buf, bytes.len());
+        r.read_all_messages::<TestMessage>(max)
+    };
+    assert_eq!(messages, read(&buf, usize::MAX).unwrap());
+    assert_eq!(messages, read(&buf, 3).unwrap());
+    match *read(&buf, 2).unwrap_err().kind() {
+        ErrorKind::MessageLimit(2) => (),
+        ref e => panic!("expecting MessageLimit, got {:?}", e),
+    }
+    match *read(&buf[..buf.len() - 1], 3).unwrap_err().kind() {
+        ErrorKind::UnexpectedEof => (),
+        ref e => panic!("expecting UnexpectedEof, got {:?}", e),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Message(TestMessage),
+    Id(u32),
+}
+
+#[test]
+fn wr_tagged_union(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_tagged_union(1, &TestMessage { id: Some(3), val: vec![4] }).unwrap();
+        w.write_tagged_union(2, &TestMessage { id: Some(5), val: vec![6] }).unwrap();
+        w.write_tagged_union(7, &TestMessage::default()).unwrap();
+    }
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    let read = |r: &mut Reader<&[u8]>| r.read_tagged_union(|d, r| match d {
+        1 => Ok(Event::Message(TestMessage::from_reader(r)?)),
+        // reads the id only, the rest of the body is skipped
+        2 => Ok(Event::Id(FirstField::from_reader(r)?.id)),
+        d => Err(ErrorKind::UnknownDiscriminator(d).into()),
+    });
+    assert_eq!(Some(Event::Message(TestMessage { id: Some(3), val: vec![4] })), read(&mut r).unwrap());
+    assert_eq!(Some(Event::Id(5)), read(&mut r).unwrap());
+    match *read(&mut r).unwrap_err().kind() {
+        ErrorKind::UnknownDiscriminator(7) => (),
+        ref e => panic!("expecting UnknownDiscriminator, got {:?}", e),
+    }
+    assert_eq!(None, read(&mut r).unwrap());
+
+    for len in &[1, 4] {
+        let mut r = Reader::from_reader(&buf[..*len], *len);
+        match *read(&mut r).unwrap_err().kind() {
+            ErrorKind::UnexpectedEof => (),
+            ref e => panic!("expecting UnexpectedEof, got {:?}", e),
+        }
+    }
+}
+
+#[test]
+fn wr_wire_type_mismatch(){
+    // generated code matches whole tags: field 1 with another wire type is skipped as an unknown field,
+    // according to its own wire type, so that the following fields are still read
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_fixed64_with_tag(1 << 3 | 1, 63).unwrap();
+        w.write_fixed32_with_tag(1 << 3 | 5, 63).unwrap();
+        w.write_sint64_with_tag(18, -5).unwrap();
+    }
+    let len = buf.len();
+    let mut buf = &*buf;
+    let mut r = Reader::from_reader(&mut buf, len);
+    assert_eq!(TestMessage { id: None, val: vec![-5] }, TestMessage::from_reader(&mut r).unwrap());
+}
+
+#[test]
+fn wr_message_unbounded(){
+    let v = TestMessage { id: Some(63), val: vec![53, 5, 76, 743] };
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_message(&v).unwrap();
+    }
+    // e.g. the compressed length, smaller than the actual data
+    let mut buf = &*buf;
+    let mut r = Reader::from_reader(&mut buf, 3);
+    r.set_bounded(false);
+    assert_eq!(v, r.read_message().unwrap());
+    assert!(r.is_eof());
+}
+
+#[test]
+fn wr_varint_limits(){
+    let max = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01];
+    let mut r = Reader::from_reader(&max[..], max.len());
+    assert_eq!(u64::MAX, r.read_uint64().unwrap());
+    assert!(r.is_eof());
+    let mut r = Reader::from_reader(&max[..], max.len());
+    assert_eq!(10, r.read_unknown_raw(8).unwrap().len());
+
+    // bits beyond bit 63, or an eleventh byte
+    for last in &[0x02, 0x81] {
+        let mut overlong = max;
+        overlong[9] = *last;
+        let mut r = Reader::from_reader(&overlong[..], overlong.len());
+        match *r.read_uint64().unwrap_err().kind() {
+            ErrorKind::Varint => (),
+            ref e => panic!("expecting Varint, got {:?}", e),
+        }
+        let mut r = Reader::from_reader(&overlong[..], overlong.len());
+        match *r.read_unknown_raw(8).unwrap_err().kind() {
+            ErrorKind::Varint => (),
+            ref e => panic!("expecting Varint, got {:?}", e),
+        }
+    }
+
+    // the bytes are there but past the length
+    let mut r = Reader::from_reader(&max[..], 5);
+    match *r.read_uint64().unwrap_err().kind() {
+        ErrorKind::UnexpectedEof => (),
+        ref e => panic!("expecting UnexpectedEof, got {:?}", e),
+    }
+}
+
+#[test]
+fn wr_negative_int32_encodings(){
+    let mut buf = Vec::new();
+    Writer::new(&mut buf).write_int32(-2).unwrap();
+    assert_eq!(vec![0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01], buf);
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    assert_eq!(-2, r.read_int32().unwrap());
+
+    // as written by encoders which don't sign-extend int32
+    let short = [0xFE, 0xFF, 0xFF, 0xFF, 0x0F];
+    let mut r = Reader::from_reader(&short[..], short.len());
+    assert_eq!(-2, r.read_int32().unwrap());
+    assert!(r.is_eof());
+}
+
+#[test]
+fn wr_declared_length(){
+    // a 5 GB length, which would be truncated to 1 GB by a 32-bit `as usize`
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_varint(5 << 30).unwrap();
+        w.write_raw_bytes(b"abc").unwrap();
+    }
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    match *r.read_bytes().unwrap_err().kind() {
+        ErrorKind::UnexpectedEof => (),
+        ref e => panic!("expecting UnexpectedEof, got {:?}", e),
+    }
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    match *r.read_message::<TestMessage>().unwrap_err().kind() {
+        ErrorKind::UnexpectedEof => (),
+        ref e => panic!("expecting UnexpectedEof, got {:?}", e),
+    }
+}
+
+#[test]
+fn wr_message_into(){
+    let v = TestMessage { id: Some(63), val: vec![53, 5, 76] };
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_message(&v).unwrap();
+        w.write_message(&TestMessage { id: None, val: vec![1] }).unwrap();
+    }
+    let len = buf.len();
+    let mut buf = &*buf;
+    let mut r = Reader::from_reader(&mut buf, len);
+    let mut out = TestMessage::default();
+    r.read_message_into(&mut out).unwrap();
+    assert_eq!(v, out);
+    let capacity = out.val.capacity();
+    r.read_message_into(&mut out).unwrap();
+    assert_eq!(TestMessage { id: None, val: vec![1] }, out);
+    assert_eq!(capacity, out.val.capacity());
+    assert!(r.is_eof());
+}
+
+#[test]
+fn wr_map_entry(){
+    let entries: &[&[u8]] = &[
+        &[4, 0x08, 0x07, 0x10, 0x2A],                // key 7, value 42
+        &[2, 0x10, 0x2A],                            // only a value
+        &[7, 0x08, 0x07, 0x1A, 1, 0xFF, 0x10, 0x2A], // extra field 3
+        &[2, 0x0D, 0x07],                            // key with a fixed32 wire type
+    ];
+    let read = |bytes: &[u8]| {
+        let mut buf = bytes;
+        let mut r = Reader::from_reader(&mut buf, bytes.len());
+        r.read_map(8, 16, |r| r.read_int32(), |r| r.read_uint64())
+    };
+    assert_eq!((7, 42), read(entries[0]).unwrap());
+    assert_eq!((0, 42), read(entries[1]).unwrap());
+    assert_eq!((7, 42), read(entries[2]).unwrap());
+    match *read(entries[3]).unwrap_err().kind() {
+        ErrorKind::MapEntryInvalid(0x0D) => (),
+        ref e => panic!("expecting MapEntryInvalid, got {:?}", e),
+    }
+}
+
+#[test]
+fn wr_merge_message(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_message(&TestMessage { id: Some(63), val: vec![53, 5] }).unwrap();
+        w.write_message(&TestMessage { id: None, val: vec![76] }).unwrap();
+    }
+    let len = buf.len();
+    let mut buf = &*buf;
+    let mut r = Reader::from_reader(&mut buf, len);
+    let mut out = TestMessage::default();
+    r.merge_message(&mut out).unwrap();
+    r.merge_message(&mut out).unwrap();
+    assert_eq!(TestMessage { id: Some(63), val: vec![53, 5, 76] }, out);
+    assert!(r.is_eof());
+}
+
+#[test]
+fn wr_fields(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_uint32_with_tag(8, 63).unwrap();
+        w.write_fixed64_with_tag(17, 53).unwrap();
+        w.write_sint64_with_tag(16, -5).unwrap();
+        w.write_string_with_tag(26, "unknown").unwrap();
+    }
+    let len = buf.len();
+    let mut buf = &*buf;
+    let mut r = Reader::from_reader(&mut buf, len);
+    let mut fields = r.fields();
+    assert_eq!(Some((1, WireType::Varint)), fields.next_field().unwrap());
+    assert_eq!(63, fields.reader().read_uint32().unwrap());
+    assert_eq!(Some((2, WireType::Fixed64)), fields.next_field().unwrap());
+    fields.skip().unwrap();
+    assert_eq!(Some((2, WireType::Varint)), fields.next_field().unwrap());
+    assert_eq!(-5, fields.reader().read_sint64().unwrap());
+    assert_eq!(Some((3, WireType::LengthDelimited)), fields.next_field().unwrap());
+    fields.skip().unwrap();
+    assert_eq!(None, fields.next_field().unwrap());
+}
+
+#[test]
+fn wr_next_field(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_uint32_with_tag(8, 63).unwrap();
+        w.write_string_with_tag(26, "s").unwrap();
+    }
+    let len = buf.len();
+    let mut buf = &*buf;
+    let mut r = Reader::from_reader(&mut buf, len);
+    assert_eq!(Some((1, WireType::Varint)), r.next_field().unwrap());
+    assert_eq!(63, r.read_uint32().unwrap());
+    assert_eq!(Some((3, WireType::LengthDelimited)), r.next_field().unwrap());
+    assert_eq!("s", r.read_string().unwrap());
+    assert_eq!(None, r.next_field().unwrap());
+}
+
+#[test]
+fn wr_prost_message(){
+    let mut m = TestMessage { id: Some(63), val: vec![53, 5] };
+    let buf = m.encode_to_vec();
+    assert_eq!(m.get_size(), buf.len());
+    assert_eq!(buf.len(), m.encoded_len());
+    assert_eq!(m, TestMessage::decode(&buf).unwrap());
+
+    let mut appended = vec![0xFF];
+    m.encode(&mut appended).unwrap();
+    assert_eq!(buf, &appended[1..]);
+
+    m.merge(&[0x12, 0x02]).unwrap();
+    assert_eq!(TestMessage { id: Some(63), val: vec![53, 5, 1] }, m);
+}
+
+#[test]
+fn wr_skip_field(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_fixed64_with_tag(9, 1).unwrap();
+        w.write_string_with_tag(18, "skipped").unwrap();
+        w.write_group_raw(3, &[0x08, 0x01]).unwrap();
+        w.write_uint32_with_tag(32, 63).unwrap();
+    }
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    while let Some((number, wire_type)) = r.next_field().unwrap() {
+        if number == 4 {
+            assert_eq!(63, r.read_uint32().unwrap());
+        } else {
+            r.skip_field(number, wire_type).unwrap();
+        }
+    }
+    assert!(r.is_eof());
+    match *r.skip_field(5, WireType::EndGroup).unwrap_err().kind() {
+        ErrorKind::UnmatchedEndGroup(5) => (),
+        ref e => panic!("expecting UnmatchedEndGroup, got {:?}", e),
+    }
+
+    // fixed values longer than the bytes left
+    let mut r = Reader::from_reader(&[0u8, 0, 0][..], 3);
+    assert!(r.skip_field(1, WireType::Fixed64).is_err());
+    assert!(r.skip_field(1, WireType::Fixed32).is_err());
+    assert_eq!(3, r.len());
+}
+
+#[test]
+fn wr_scan(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_uint32_with_tag(8, 300).unwrap();
+        w.write_fixed64_with_tag(17, 53).unwrap();
+        w.write_string_with_tag(26, "unknown").unwrap();
+        w.write_group_raw(4, &[0x08, 0x01]).unwrap();
+    }
+    let len = buf.len();
+    let mut buf = &*buf;
+    let mut r = Reader::from_reader(&mut buf, len);
+    let mut fields = Vec::new();
+    r.scan(|number, wire_type, len| fields.push((number, wire_type, len))).unwrap();
+    assert_eq!(vec![(1, WireType::Varint, 2),
+                    (2, WireType::Fixed64, 8),
+                    (3, WireType::LengthDelimited, 8),
+                    (4, WireType::StartGroup, 3)], fields);
+    assert!(r.is_eof());
+}
+
+#[test]
+fn wr_next_tag_in(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_uint32_with_tag(8, 63).unwrap();
+        w.write_string_with_tag(18, "skipped").unwrap();
+        w.write_sint64_with_tag(24, -5).unwrap();
+        w.write_fixed64_with_tag(33, 53).unwrap();
+    }
+    let len = buf.len();
+    let mut buf = &*buf;
+    let mut r = Reader::from_reader(&mut buf, len);
+    assert_eq!(Some(24), r.next_tag_in(&[24]).unwrap());
+    assert_eq!(-5, r.read_sint64().unwrap());
+    assert_eq!(None, r.next_tag_in(&[24]).unwrap());
+}
+
 #[test]
 fn wr_packed_uint32(){
     let v = vec![43, 54, 64, 234, 6123, 643];
@@ -166,3 +723,1173 @@ fn wr_packed_uint32(){
     assert_eq!(v, &*r.read_packed_repeated_field(|r| r.read_uint32()).unwrap());
 }
 
+
+#[test]
+fn wr_packed_blob(){
+    let v = vec![0i64, -1, 150, i64::MIN];
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        for i in &v {
+            w.write_sint64(*i).unwrap();
+        }
+    }
+    let read = Packed::sint64(&buf).collect::<Result<Vec<_>>>().unwrap();
+    assert_eq!(v, read);
+    let read = Packed::double(&[0, 0, 0, 0, 0, 0, 0xF0, 0x3F]).collect::<Result<Vec<_>>>().unwrap();
+    assert_eq!(vec![1.0], read);
+}
+
+#[test]
+fn wr_max_len(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.set_max_len(8);
+        w.write_string("1234").unwrap();
+        assert!(w.write_string("1234").is_err());
+    }
+    assert_eq!(5, buf.len());
+
+    let v = TestMessage { id: Some(63), val: vec![-5, 1, 1 << 40] };
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.set_max_len(v.get_size());
+        assert!(w.write_message(&v).is_err());
+    }
+    assert!(buf.is_empty(), "nested message must not be partially written");
+}
+
+#[test]
+fn wire_type_from_tag(){
+    assert_eq!((1, WireType::Varint), WireType::from_tag(8).unwrap());
+    assert_eq!((2, WireType::LengthDelimited), WireType::from_tag(18).unwrap());
+    assert_eq!((536870911, WireType::Fixed32), WireType::from_tag(0xFFFFFFFD).unwrap());
+    assert_eq!(5, WireType::Fixed32.as_u8());
+    assert!(WireType::from_tag(14).is_err());
+}
+
+#[test]
+fn wr_unexpected_eof(){
+    let mut buf: &[u8] = &[1, 2, 3];
+    let mut r = Reader::from_reader(&mut buf, 8);
+    match *r.read_fixed64().unwrap_err().kind() {
+        ErrorKind::UnexpectedEof => (),
+        ref e => panic!("expecting UnexpectedEof, got {:?}", e),
+    }
+}
+
+#[test]
+fn wr_packed_fixed_size(){
+    let v = vec![43u64, 54, 1 << 40];
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_packed_fixed_size(&v, 8).unwrap();
+    }
+    let len = buf.len();
+    let mut buf = &*buf;
+    let mut r = Reader::from_reader(&mut buf, len);
+    let read = r.read_packed_fixed_size(8, |r| r.read_fixed64()).unwrap();
+    assert_eq!(v, read);
+    assert_eq!(3, read.capacity());
+}
+
+#[test]
+fn wr_packed_fixed(){
+    let v = vec![1.5f64, -0.25, 1e300];
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_packed_fixed_size_with_tag(10, &v, 8).unwrap();
+        w.write_packed_fixed_size_with_tag(18, &[-3i32, 4], 4).unwrap();
+        w.write_packed_fixed_size_with_tag(26, &[0u32; 0], 4).unwrap();
+    }
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    assert_eq!(10, r.next_tag().unwrap());
+    assert_eq!(v, r.read_packed_fixed::<f64>().unwrap());
+    assert_eq!(18, r.next_tag().unwrap());
+    assert_eq!(vec![-3, 4], r.read_packed_fixed::<i32>().unwrap());
+    assert!(r.is_eof());
+
+    let truncated = [6, 1, 2, 3, 4, 5, 6];
+    let mut r = Reader::from_reader(&truncated[..], truncated.len());
+    match *r.read_packed_fixed::<u32>().unwrap_err().kind() {
+        ErrorKind::PackedLength(6, 4) => (),
+        ref e => panic!("expecting PackedLength, got {:?}", e),
+    }
+}
+
+#[test]
+fn wr_packed_fixed_borrowed(){
+    let mut packed = Vec::new();
+    Writer::new(&mut packed).write_packed_fixed_size_with_tag(10, &[7u32, 1 << 31], 4).unwrap();
+    let packed = &packed[1..];
+
+    // a `u32` buffer, so that the values are aligned right after their one byte length
+    let mut storage = vec![0u32; 4];
+    let bytes = unsafe { std::slice::from_raw_parts_mut(storage.as_mut_ptr() as *mut u8, 16) };
+    bytes[3..3 + packed.len()].copy_from_slice(packed);
+    let mut r = Reader::from_reader(&bytes[3..3 + packed.len()], packed.len());
+    let values = r.read_packed_fixed_borrowed::<u32>().unwrap();
+    assert_eq!(&[7, 1 << 31], &*values);
+    assert_eq!(cfg!(target_endian = "little"), matches!(values, Cow::Borrowed(_)));
+    assert!(r.is_eof());
+
+    // unaligned values are copied
+    bytes[..packed.len()].copy_from_slice(packed);
+    let mut r = Reader::from_reader(&bytes[..packed.len()], packed.len());
+    let values = r.read_packed_fixed_borrowed::<u32>().unwrap();
+    assert_eq!(&[7, 1 << 31], &*values);
+    assert!(matches!(values, Cow::Owned(_)));
+
+    let mut r = Reader::from_reader(&packed[..5], 5);
+    assert!(r.read_packed_fixed_borrowed::<u32>().is_err());
+}
+
+#[test]
+fn wr_packed_truncated(){
+    // the last varint of the 2 bytes packed field (150 = [0x96, 0x01]) is cut by its end, then a field 2
+    let bytes = [2, 1, 0x96, 0x01, 16, 2];
+    let is_truncated = |e: quick_protobuf::errors::Error| matches!(*e.kind(), ErrorKind::PackedTruncated(2));
+    let mut r = Reader::from_reader(&bytes[..], bytes.len());
+    assert!(is_truncated(r.read_packed_repeated_field(|r| r.read_uint32()).unwrap_err()));
+    let mut r = Reader::from_reader(&bytes[..], bytes.len());
+    r.set_bounded(false);
+    assert!(is_truncated(r.read_packed_repeated_field(|r| r.read_uint32()).unwrap_err()));
+
+    let bytes = [3, 1, 0x96, 0x01, 16, 2];
+    let mut r = Reader::from_reader(&bytes[..], bytes.len());
+    assert_eq!(vec![1, 150], r.read_packed_repeated_field(|r| r.read_uint32()).unwrap());
+
+    // 6 bytes of fixed32 values
+    let bytes = [6, 1, 0, 0, 0, 2, 0, 16, 2];
+    let mut r = Reader::from_reader(&bytes[..], bytes.len());
+    match *r.read_packed_fixed_size(4, |r| r.read_fixed32()).unwrap_err().kind() {
+        ErrorKind::PackedLength(6, 4) => (),
+        ref e => panic!("expecting PackedLength, got {:?}", e),
+    }
+}
+
+#[test]
+fn wr_message_to_vec(){
+    let v = (0..10).map(|i| TestMessage { id: Some(i), val: vec![i as i64; 20] }).collect::<Vec<_>>();
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        for m in &v {
+            w.write_message_with_tag(10, m).unwrap();
+        }
+    }
+    let len = buf.len();
+    let mut buf = &*buf;
+    let mut r = Reader::from_reader(&mut buf, len);
+    let mut read = Vec::new();
+    assert_eq!(10, r.next_tag().unwrap());
+    r.read_message_to_vec(&mut read).unwrap();
+    assert!(read.capacity() >= 10, "room for all messages is reserved at once");
+    while !r.is_eof() {
+        assert_eq!(10, r.next_tag().unwrap());
+        r.read_message_to_vec(&mut read).unwrap();
+    }
+    assert_eq!(v, read);
+
+    // the declared length of the reader is much larger than its input
+    for &len in &[1 << 30, 1 << 40, usize::MAX] {
+        let mut buf = &[10, 2, 10, 1][..];
+        let mut r = Reader::from_reader(&mut buf, len);
+        let mut read = Vec::<TestMessage>::new();
+        assert_eq!(10, r.next_tag().unwrap());
+        r.read_message_to_vec(&mut read).unwrap();
+        assert_eq!(Some(1), read[0].id);
+        assert!(read.capacity() <= 1024);
+    }
+}
+
+#[test]
+fn wr_group_raw(){
+    // field 1 = 150, group 2 { field 3 = "ab", group 4 { field 5 = fixed32 } }, field 6 = 1
+    let group = [0x1a, 0x02, b'a', b'b', 0x23, 0x2d, 1, 2, 3, 4, 0x24];
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_uint32_with_tag(8, 150).unwrap();
+        w.write_group_raw(2, &group).unwrap();
+        w.write_uint32_with_tag(48, 1).unwrap();
+    }
+    assert_eq!(3 + 1 + group.len() + 1 + 2, buf.len());
+
+    let len = buf.len();
+    let mut b = &*buf;
+    let mut r = Reader::from_reader(&mut b, len);
+    assert_eq!(Some(48), r.next_tag_in(&[48]).unwrap());
+    assert_eq!(1, r.read_uint32().unwrap());
+    assert!(r.is_eof());
+
+    let mut b = &*buf;
+    let mut r = Reader::from_reader(&mut b, len);
+    assert_eq!(8, r.next_tag().unwrap());
+    assert_eq!(150, r.read_uint32().unwrap());
+    assert_eq!(19, r.next_tag().unwrap());
+    assert_eq!(&group[..], &*r.read_group_raw(2).unwrap());
+    assert_eq!(48, r.next_tag().unwrap());
+
+    let mut b: &[u8] = &[0x0c];
+    let mut r = Reader::from_reader(&mut b, 1);
+    match *r.read_unknown(0x13).unwrap_err().kind() {
+        ErrorKind::UnmatchedEndGroup(1) => (),
+        ref e => panic!("expecting UnmatchedEndGroup, got {:?}", e),
+    }
+}
+
+/// Reads only its first field, leaving the others to `Reader::read_message`
+#[derive(Debug, Default)]
+struct FirstField {
+    id: u32,
+    unknown_fields: UnknownFields,
+}
+
+impl MessageRead for FirstField {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
+        r.next_tag()?;
+        Ok(FirstField { id: r.read_uint32()?, unknown_fields: UnknownFields::default() })
+    }
+
+    fn unknown_fields_mut(&mut self) -> Option<&mut UnknownFields> {
+        Some(&mut self.unknown_fields)
+    }
+}
+
+#[test]
+fn wr_message_trailing_fields(){
+    let mut inner = Vec::new();
+    {
+        let mut w = Writer::new(&mut inner);
+        w.write_uint32_with_tag(8, 63).unwrap();
+        w.write_sint64_with_tag(16, -5).unwrap();
+        w.write_string_with_tag(26, "abc").unwrap();
+    }
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_bytes(&inner).unwrap();
+        w.write_uint32(1).unwrap();
+    }
+    let len = buf.len();
+    let mut buf = &*buf;
+    let mut r = Reader::from_reader(&mut buf, len);
+    let first: FirstField = r.read_message().unwrap();
+    assert_eq!(63, first.id);
+    assert_eq!(vec![(16, &[9][..]), (26, &[3, b'a', b'b', b'c'][..])], first.unknown_fields.iter().collect::<Vec<_>>());
+    assert_eq!(1, r.read_uint32().unwrap());
+    assert!(r.is_eof());
+
+    let mut out = Vec::new();
+    {
+        let mut w = Writer::new(&mut out);
+        w.write_uint32_with_tag(8, first.id).unwrap();
+        first.unknown_fields.write(&mut w).unwrap();
+    }
+    assert_eq!(inner, out);
+    assert_eq!(inner.len(), 2 + first.unknown_fields.get_size());
+}
+
+#[test]
+fn wr_unknown_fields_sorted(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_string_with_tag(26, "b").unwrap();
+        w.write_uint32_with_tag(16, 5).unwrap();
+        w.write_string_with_tag(26, "a").unwrap();
+        w.write_fixed32_with_tag(13, 7).unwrap();
+    }
+    let mut unknown_fields = UnknownFields::default();
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    while !r.is_eof() {
+        let tag = r.next_tag().unwrap();
+        unknown_fields.read_field(&mut r, tag).unwrap();
+    }
+
+    // fields with the same number keep their order
+    let sorted = unknown_fields.sorted();
+    assert_eq!(vec![(13, &[7, 0, 0, 0][..]), (16, &[5][..]), (26, &[1, b'b'][..]), (26, &[1, b'a'][..])],
+               sorted.iter().collect::<Vec<_>>());
+    assert_eq!(unknown_fields.get_size(), sorted.get_size());
+
+    let write = |sort: bool| {
+        let mut out = Vec::new();
+        {
+            let mut w = Writer::new(&mut out);
+            w.set_sort_unknown_fields(sort);
+            unknown_fields.write(&mut w).unwrap();
+        }
+        out
+    };
+    assert_eq!(buf, write(false));
+    let mut expected = Vec::new();
+    sorted.write(&mut Writer::new(&mut expected)).unwrap();
+    assert_eq!(expected, write(true));
+}
+
+#[test]
+fn wr_index_fields(){
+    let inner = TestMessage { id: Some(63), val: vec![53, 5] };
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_string_with_tag(10, "abc").unwrap();
+        w.write_uint32_with_tag(16, 300).unwrap();
+        w.write_message_with_tag(26, &inner).unwrap();
+        w.write_uint32_with_tag(16, 5).unwrap();
+        w.write_fixed64_with_tag(33, 7).unwrap();
+    }
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    let index = r.index_fields().unwrap();
+    assert!(r.is_eof());
+    assert_eq!(5, index.len());
+    assert_eq!(vec![(1, 0), (2, 5), (3, 8), (2, 8 + 2 + inner.get_size()), (4, 8 + 4 + inner.get_size())],
+               index.iter().map(|f| (f.number, f.start)).collect::<Vec<_>>());
+    assert_eq!(WireType::LengthDelimited, index.get(1).unwrap().wire_type);
+    assert_eq!(1..5, index.get(1).unwrap().value);
+    assert_eq!(buf.len(), index.get(4).unwrap().value.end);
+    assert!(index.get(5).is_none());
+
+    // fields are read on demand, the last occurrence of a singular field being kept
+    assert_eq!("abc", index.get(1).unwrap().reader(&buf).read_string().unwrap());
+    assert_eq!(inner, index.get(3).unwrap().reader(&buf).read_message::<TestMessage>().unwrap());
+    assert_eq!(5, index.get(2).unwrap().reader(&buf).read_uint32().unwrap());
+    assert_eq!(vec![300, 5], index.get_all(2).map(|f| f.reader(&buf).read_uint32().unwrap()).collect::<Vec<_>>());
+    assert_eq!(7, index.get(4).unwrap().reader(&buf).read_fixed64().unwrap());
+}
+
+#[test]
+fn wr_invalid_utf8_position(){
+    let inner = TestMessage { id: Some(63), val: vec![53, 5] };
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_message_with_tag(10, &inner).unwrap();
+        w.write_bytes_with_tag(26, b"ab\xffc").unwrap();
+    }
+    let offset = 2 + inner.get_size() + 2;
+
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    assert_eq!(10, r.next_tag().unwrap());
+    assert_eq!(inner, r.read_message::<TestMessage>().unwrap());
+    assert_eq!(2 + inner.get_size(), r.position());
+    assert_eq!(26, r.next_tag().unwrap());
+    match *r.read_string().unwrap_err().kind() {
+        ErrorKind::InvalidUtf8(3, p) => assert_eq!(offset + 2, p),
+        ref e => panic!("expecting InvalidUtf8, got {:?}", e),
+    }
+    assert_eq!(buf.len(), r.position());
+
+    let mut r = Reader::from_reader(&buf[offset - 2..], buf.len() - offset + 2);
+    r.next_tag().unwrap();
+    assert!(matches!(*r.read_string_interned().unwrap_err().kind(), ErrorKind::InvalidUtf8(3, 4)));
+    let mut r = Reader::from_reader(&buf[offset - 2..], buf.len() - offset + 2);
+    r.next_tag().unwrap();
+    assert!(matches!(*r.read_string_borrowed().unwrap_err().kind(), ErrorKind::InvalidUtf8(3, 4)));
+}
+
+#[test]
+fn wr_check_required(){
+    let mut r = Reader::from_reader(&[][..], 0);
+    r.check_required(&[true, false], &["a", "b"]).unwrap();
+    r.set_check_required(true);
+    r.check_required(&[true, true], &["a", "b"]).unwrap();
+    let err = r.check_required(&[true, false], &["a", "b"]).unwrap_err().in_field("items[2]").in_field("inner");
+    match *err.kind() {
+        ErrorKind::MissingRequired(ref path) => assert_eq!("inner.items[2].b", path),
+        ref e => panic!("expecting MissingRequired, got {:?}", e),
+    }
+    assert!(matches!(*quick_protobuf::errors::Error::from(ErrorKind::Varint).in_field("a").kind(), ErrorKind::Varint));
+}
+
+#[test]
+fn wr_message_set(){
+    let a = TestMessage { id: Some(63), val: vec![53, 5] };
+    let b = TestMessage { id: Some(1), val: vec![] };
+    let mut set = MessageSet::new();
+    set.insert(1000, &a).unwrap();
+    set.insert(7, &b).unwrap();
+    let mut buf = Vec::new();
+    Writer::new(&mut buf).write_message_set(&set).unwrap();
+    assert_eq!(set.get_size(), buf.len());
+    assert_eq!(&[11, 16, 232, 7, 26], &buf[..5]);
+
+    let read = Reader::from_reader(&*buf, buf.len()).read_message_set().unwrap();
+    assert_eq!(set, read);
+    assert_eq!(a, read.get::<TestMessage>(1000).unwrap().unwrap());
+    assert!(read.get::<TestMessage>(8).is_none());
+
+    // items of the same type id are merged, the message may come before the type id
+    let mut w = Writer::new(&mut buf);
+    w.write_tag(11).unwrap();
+    w.write_bytes_with_tag(26, &[18, 8]).unwrap();
+    w.write_uint32_with_tag(16, 7).unwrap();
+    w.write_tag(12).unwrap();
+    let read = Reader::from_reader(&*buf, buf.len()).read_message_set().unwrap();
+    assert_eq!(2, read.len());
+    assert_eq!(TestMessage { id: Some(1), val: vec![4] }, read.get::<TestMessage>(7).unwrap().unwrap());
+
+    let item = [11, 26, 0, 12];
+    assert!(matches!(*Reader::from_reader(&item[..], 4).read_message_set().unwrap_err().kind(),
+                     ErrorKind::MissingField("type_id")));
+}
+
+#[test]
+fn wr_extract_field(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_message_with_tag(10, &TestMessage { id: Some(63), val: vec![53, 5] }).unwrap();
+        w.write_group_raw(2, &[8, 1]).unwrap();
+        w.write_uint64_with_tag(24, 42).unwrap();
+        w.write_uint64_with_tag(24, 43).unwrap();
+    }
+    assert_eq!(Some(42), Reader::extract_field(&buf, 3, |r| r.read_uint64()).unwrap());
+    assert_eq!(None, Reader::extract_field(&buf, 4, |r| r.read_uint64()).unwrap());
+    let m = Reader::extract_field(&buf, 1, |r| r.read_message::<TestMessage>()).unwrap();
+    assert_eq!(Some(63), m.unwrap().id);
+
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    assert_eq!(Some(WireType::Varint), r.seek_field(3).unwrap());
+    assert_eq!(42, r.read_uint64().unwrap());
+    assert_eq!(Some(WireType::Varint), r.seek_field(3).unwrap());
+    assert_eq!(43, r.read_uint64().unwrap());
+    assert_eq!(None, r.seek_field(3).unwrap());
+}
+
+#[test]
+fn wr_error_source(){
+    use std::error::Error as StdError;
+    fn is_send_sync<T: Send + Sync + 'static>(_: &T) {}
+
+    let e = String::from_utf8(vec![0xff]).map_err(quick_protobuf::errors::Error::from).unwrap_err();
+    is_send_sync(&e);
+    assert!(matches!(*e.kind(), ErrorKind::Utf8(_)));
+    assert_eq!(e.to_string(), e.source().unwrap().to_string());
+
+    let e = quick_protobuf::errors::Error::from(::std::io::Error::other("oops"));
+    assert_eq!("io error: oops", e.to_string());
+    assert_eq!("oops", e.source().unwrap().to_string());
+    assert!(ErrorKind::Varint.to_string().contains("varint"));
+
+    let io: ::std::io::Error = quick_protobuf::errors::Error::from(ErrorKind::RepeatedLimit(2)).into();
+    assert_eq!(::std::io::ErrorKind::InvalidData, io.kind());
+}
+
+#[test]
+fn wr_lazy_message(){
+    let m = TestMessage { id: Some(63), val: vec![53, 5] };
+    let mut buf = Vec::new();
+    Writer::new(&mut buf).write_message(&m).unwrap();
+
+    let mut lazy = Reader::from_reader(&*buf, buf.len()).read_message::<LazyMessage<TestMessage>>().unwrap();
+    assert!(!lazy.is_parsed());
+    assert_eq!(Some(&buf[1..]), lazy.raw());
+    assert_eq!(&m, lazy.get().unwrap());
+    assert!(lazy.is_parsed());
+    assert_eq!(LazyMessage::new(m.clone()), lazy);
+
+    // written as is until modified
+    let mut out = Vec::new();
+    Writer::new(&mut out).write_message(&lazy).unwrap();
+    assert_eq!(buf, out);
+    lazy.get_mut().unwrap().id = Some(1);
+    assert!(lazy.raw().is_none());
+    out.clear();
+    Writer::new(&mut out).write_message(&lazy).unwrap();
+    let read = Reader::from_reader(&*out, out.len()).read_message::<TestMessage>().unwrap();
+    assert_eq!(Some(1), read.id);
+
+    // merged occurrences are parsed together
+    let mut lazy = LazyMessage::<TestMessage>::default();
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    r.merge_message(&mut lazy).unwrap();
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    r.merge_message(&mut lazy).unwrap();
+    assert_eq!(vec![53, 5, 53, 5], lazy.into_inner().unwrap().val);
+
+    let invalid = LazyMessage::<TestMessage>::from_bytes(vec![0x80]);
+    assert!(invalid.get().is_err());
+}
+
+#[test]
+fn wr_written(){
+    let mut buf = Vec::new();
+    let mut w = Writer::new(&mut buf);
+    w.write_string_with_tag(10, "abc").unwrap();
+    assert_eq!(5, w.written());
+    w.write_fixed64(1).unwrap();
+    assert_eq!(13, w.written());
+}
+
+#[test]
+fn wr_fixed_little_endian(){
+    // fixed size types are little endian on the wire, whatever the host endianness
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_fixed32(0x01020304).unwrap();
+        w.write_sfixed64(-2).unwrap();
+        w.write_float(1.0).unwrap();
+        w.write_double(-2.5).unwrap();
+        w.write_packed_fixed_size(&[0x0102u32, 0x0304], 4).unwrap();
+        w.write_packed_fixed_size(&[1.0f64], 8).unwrap();
+    }
+    let expected: &[u8] = &[
+        4, 3, 2, 1,
+        0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        0, 0, 0x80, 0x3F,
+        0, 0, 0, 0, 0, 0, 0x04, 0xC0,
+        8, 2, 1, 0, 0, 4, 3, 0, 0,
+        8, 0, 0, 0, 0, 0, 0, 0xF0, 0x3F];
+    assert_eq!(expected, &*buf);
+
+    let mut buf = expected;
+    let mut r = Reader::from_reader(&mut buf, expected.len());
+    assert_eq!(0x01020304, r.read_fixed32().unwrap());
+    assert_eq!(-2, r.read_sfixed64().unwrap());
+    assert_eq!(1.0, r.read_float().unwrap());
+    assert_eq!(-2.5, r.read_double().unwrap());
+    assert_eq!(vec![0x0102, 0x0304], r.read_packed_fixed_size(4, |r| r.read_fixed32()).unwrap());
+    assert_eq!(vec![1.0], r.read_packed_fixed_size(8, |r| r.read_double()).unwrap());
+    assert!(r.is_eof());
+}
+
+#[test]
+fn wr_reader_options(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_bytes(b"0123456789").unwrap();
+    }
+    let options = ReaderOptions { max_alloc: 8, ..ReaderOptions::default() };
+    let mut r = Reader::from_bytes_with_options(&buf, options).unwrap();
+    match *r.read_bytes().unwrap_err().kind() {
+        ErrorKind::AllocLimit(10, 8) => (),
+        ref e => panic!("expecting AllocLimit, got {:?}", e),
+    }
+
+    let options = ReaderOptions { max_len: 8, ..ReaderOptions::default() };
+    assert!(Reader::from_bytes_with_options(&buf, options).is_err());
+
+    // group 1 { group 1 { } }, once the first start group tag is read
+    let nested = [0x0b, 0x0c, 0x0c];
+    let options = ReaderOptions { max_depth: 1, ..ReaderOptions::default() };
+    let mut r = Reader::from_bytes_with_options(&nested, options).unwrap();
+    match *r.read_unknown(0x0b).unwrap_err().kind() {
+        ErrorKind::RecursionLimit(1) => (),
+        ref e => panic!("expecting RecursionLimit, got {:?}", e),
+    }
+    let options = ReaderOptions { max_depth: 2, ..ReaderOptions::default() };
+    let mut r = Reader::from_bytes_with_options(&nested, options).unwrap();
+    r.read_unknown(0x0b).unwrap();
+    assert!(r.is_eof());
+}
+
+#[test]
+fn wr_repeated_limit(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_packed_repeated_field(&[1u32, 2, 3], |w, v| w.write_uint32(*v), &|v| sizeof_uint32(*v)).unwrap();
+        w.write_packed_fixed_size(&[1u32, 2, 3], 4).unwrap();
+        w.write_bytes(&[8, 1]).unwrap();
+    }
+    let is_limit = |e: quick_protobuf::errors::Error| matches!(*e.kind(), ErrorKind::RepeatedLimit(2));
+    let options = ReaderOptions { max_repeated_len: 2, ..ReaderOptions::default() };
+    let mut r = Reader::from_bytes_with_options(&buf, options).unwrap();
+    assert!(is_limit(r.read_packed_repeated_field(|r| r.read_uint32()).unwrap_err()));
+    let mut r = Reader::from_bytes_with_options(&buf[4..], options).unwrap();
+    assert!(is_limit(r.read_packed_fixed::<u32>().unwrap_err()));
+    let mut r = Reader::from_bytes_with_options(&buf[17..], options).unwrap();
+    let mut v = vec![FirstField::default(), FirstField::default()];
+    assert!(is_limit(r.read_message_to_vec(&mut v).unwrap_err()));
+    v.pop();
+    r.read_message_to_vec(&mut v).unwrap();
+    assert_eq!(1, v[1].id);
+    assert!(r.check_repeated_len(1).is_ok());
+    assert!(is_limit(r.check_repeated_len(2).unwrap_err()));
+
+    let options = ReaderOptions { max_repeated_len: 3, ..ReaderOptions::default() };
+    let mut r = Reader::from_bytes_with_options(&buf, options).unwrap();
+    assert_eq!(vec![1, 2, 3], r.read_packed_repeated_field(|r| r.read_uint32()).unwrap());
+    assert_eq!(vec![1, 2, 3], r.read_packed_fixed::<u32>().unwrap());
+}
+
+#[test]
+fn wr_float_bits(){
+    // signaling NaNs
+    let (f, d) = (0x7FA0_0001u32, 0x7FF0_0000_0000_0001u64);
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_fixed32(f).unwrap();
+        w.write_fixed64(d).unwrap();
+    }
+    let len = buf.len();
+    let mut buf = &*buf;
+    let mut r = Reader::from_reader(&mut buf, len);
+    assert_eq!(f, r.read_float_bits().unwrap());
+    assert_eq!(d, r.read_double_bits().unwrap());
+    assert!(r.is_eof());
+}
+
+#[derive(Debug)]
+struct Point {
+    x: i32,
+    name: String,
+    next: Option<Box<Point>>,
+}
+
+impl TextFormat for Point {
+    fn fmt_text(&self, t: &mut TextFormatter) -> ::std::fmt::Result {
+        t.scalar("x", &self.x)?;
+        t.string("name", &self.name)?;
+        if let Some(ref p) = self.next { t.message("next", &**p)?; }
+        Ok(())
+    }
+}
+
+impl ::std::fmt::Display for Point {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        TextFormatter::new(f).write(self)
+    }
+}
+
+#[test]
+fn wr_text_format(){
+    let p = Point {
+        x: 1,
+        name: "a\"\u{1}".to_string(),
+        next: Some(Box::new(Point { x: -2, name: String::new(), next: None })),
+    };
+    assert_eq!("x: 1 name: \"a\\\"\\001\" next { x: -2 name: \"\" }", p.to_string());
+    assert_eq!("x: 1\nname: \"a\\\"\\001\"\nnext {\n  x: -2\n  name: \"\"\n}\n", format!("{:#}", p));
+}
+
+impl FieldByNumber for Point {
+    fn field_by_number(&self, n: u32) -> Option<FieldValue<'_>> {
+        match n {
+            1 => Some(FieldValue::Int64(i64::from(self.x))),
+            2 => Some(FieldValue::String(::std::borrow::Cow::Borrowed(&*self.name))),
+            3 => self.next.as_ref().map(|v| FieldValue::Message(&**v)),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn wr_field_by_number(){
+    let p = Point { x: 1, name: "a".to_string(), next: Some(Box::new(Point { x: -2, name: String::new(), next: None })) };
+    assert!(matches!(p.field_by_number(1), Some(FieldValue::Int64(1))));
+    assert!(matches!(p.field_by_number(2), Some(FieldValue::String(ref s)) if s == "a"));
+    assert!(p.field_by_number(4).is_none());
+    match p.field_by_number(3) {
+        Some(FieldValue::Message(m)) => {
+            assert!(matches!(m.field_by_number(1), Some(FieldValue::Int64(-2))));
+            assert!(m.field_by_number(3).is_none());
+        }
+        v => panic!("expecting a message, got {:?}", v),
+    }
+}
+
+/// Borrows its labels from the input
+#[derive(Debug, Default, PartialEq)]
+struct Labels<'a> {
+    labels: Vec<&'a str>,
+}
+
+impl<'a> MessageReadBorrowed<'a> for Labels<'a> {
+    fn from_reader(r: &mut Reader<&'a [u8]>) -> Result<Self> {
+        let mut labels = Vec::new();
+        while !r.is_eof() {
+            match r.next_tag()? {
+                10 => labels.push(r.read_string_borrowed()?),
+                t => r.read_unknown(t)?,
+            }
+        }
+        Ok(Labels { labels })
+    }
+}
+
+#[test]
+fn wr_borrowed(){
+    let mut inner = Vec::new();
+    {
+        let mut w = Writer::new(&mut inner);
+        w.write_string_with_tag(10, "a").unwrap();
+        w.write_uint32_with_tag(16, 3).unwrap();
+        w.write_string_with_tag(10, "bc").unwrap();
+    }
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_bytes(b"raw").unwrap();
+        w.write_bytes(&inner).unwrap();
+        w.write_bytes(&inner).unwrap();
+    }
+    let mut r = Reader::from_bytes_with_options(&buf, ReaderOptions::default()).unwrap();
+    let raw = r.read_bytes_borrowed().unwrap();
+    assert_eq!(b"raw", raw);
+    assert_eq!(buf[1..4].as_ptr(), raw.as_ptr());
+    let labels: Labels = r.read_message_borrowed().unwrap();
+    assert_eq!(vec!["a", "bc"], labels.labels);
+    let mut merged = Labels { labels: vec!["z"] };
+    r.merge_message_borrowed(&mut merged).unwrap();
+    assert_eq!(labels, merged);
+    assert!(r.is_eof());
+    assert_eq!(labels, Labels::from_bytes(&inner).unwrap());
+
+    // truncated, and invalid utf8
+    let mut r = Reader::from_bytes_with_options(&[3, b'a'], ReaderOptions::default()).unwrap();
+    assert!(r.read_bytes_borrowed().is_err());
+    let mut r = Reader::from_bytes_with_options(&[1, 0xff], ReaderOptions::default()).unwrap();
+    assert!(r.read_string_borrowed().is_err());
+}
+
+#[test]
+fn wr_nested_overrun(){
+    // a nested message of 3 bytes, whose label claims 4 bytes: the 3 bytes after it,
+    // which belong to the parent, must not be read as the end of the label
+    let buf = [3, 10, 4, b'a', b'b', b'c', b'd'];
+    let mut r = Reader::from_reader(&buf[..], buf.len());
+    match *r.read_message_borrowed::<Labels>().unwrap_err().kind() {
+        ErrorKind::WrongLen(4, 1) => (),
+        ref e => panic!("expecting WrongLen, got {:?}", e),
+    }
+
+    // at the top level, it is a truncated input
+    match *Labels::from_bytes(&buf[1..4]).unwrap_err().kind() {
+        ErrorKind::UnexpectedEof => (),
+        ref e => panic!("expecting UnexpectedEof, got {:?}", e),
+    }
+}
+
+#[test]
+fn wr_packed_from_iter(){
+    let v = vec![1u32, 300, 70000];
+    let mut expected = Vec::new();
+    Writer::new(&mut expected).write_packed_repeated_field_with_tag(10, &v, |w, m| w.write_uint32(*m),
+                                                                     &|m| sizeof_varint(*m as u64)).unwrap();
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_packed_from_iter_with_tag(10, v.iter().cloned(), |w, m| w.write_uint32(m),
+                                          |m| sizeof_varint(*m as u64)).unwrap();
+        // empty iterators are not written
+        w.write_packed_from_iter_with_tag(18, 0..0u32, |w, m| w.write_uint32(m),
+                                          |m| sizeof_varint(*m as u64)).unwrap();
+    }
+    assert_eq!(expected, buf);
+
+    let mut r = Reader::from_reader(&buf[1..], buf.len() - 1);
+    assert_eq!(v, r.read_packed_repeated_field(|r| r.read_uint32()).unwrap());
+}
+
+/// A message with a bounded `count` and a nested message
+struct Bounded {
+    count: i32,
+    nested: Option<Box<Bounded>>,
+}
+
+impl Validate for Bounded {
+    fn validate_into(&self, path: &str, errors: &mut Vec<ValidationError>) {
+        if self.count > 10 { errors.push(ValidationError::new(path, "count", Constraint::Max(10.))); }
+        if let Some(ref m) = self.nested {
+            m.validate_into(&format!("{}nested.", path), errors);
+        }
+    }
+}
+
+#[test]
+fn wr_validate(){
+    let mut m = Bounded { count: 1, nested: Some(Box::new(Bounded { count: 11, nested: None })) };
+    let errors = m.validate().unwrap_err();
+    assert_eq!(vec![ValidationError { field: "nested.count".to_string(), constraint: Constraint::Max(10.) }], errors);
+    assert_eq!("nested.count must be at most 10", errors[0].to_string());
+    m.nested = None;
+    assert_eq!(Ok(()), m.validate());
+}
+
+#[test]
+fn wr_canonical_nan(){
+    let nan32 = f32::from_bits(0x7fc0_0001);
+    let nan64 = f64::from_bits(0x7ff8_0000_0000_0001);
+    let write = |canonicalize: bool| {
+        let mut buf = Vec::new();
+        {
+            let mut w = Writer::new(&mut buf);
+            w.set_canonicalize_nan(canonicalize);
+            w.write_float_with_tag(13, nan32).unwrap();
+            w.write_double_with_tag(17, nan64).unwrap();
+            w.write_packed_float_with_tag(26, &[1., nan32]).unwrap();
+            w.write_packed_double_with_tag(34, &[nan64]).unwrap();
+        }
+        let mut r = Reader::from_reader(&*buf, buf.len());
+        assert_eq!(13, r.next_tag().unwrap());
+        let f = r.read_float().unwrap().to_bits();
+        assert_eq!(17, r.next_tag().unwrap());
+        let d = r.read_double().unwrap().to_bits();
+        assert_eq!(26, r.next_tag().unwrap());
+        let pf = r.read_packed_fixed::<f32>().unwrap().iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        assert_eq!(34, r.next_tag().unwrap());
+        let pd = r.read_packed_fixed::<f64>().unwrap().iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        (f, d, pf, pd)
+    };
+    assert_eq!((0x7fc0_0001, 0x7ff8_0000_0000_0001, vec![1f32.to_bits(), 0x7fc0_0001], vec![0x7ff8_0000_0000_0001]), write(false));
+    let (c32, c64) = (f32::NAN.to_bits(), f64::NAN.to_bits());
+    assert_eq!((c32, c64, vec![1f32.to_bits(), c32], vec![c64]), write(true));
+}
+
+#[test]
+fn wr_merge_length_delimited(){
+    let whole = TestMessage { id: Some(63), val: vec![53, 5, 7] };
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_message(&TestMessage { id: Some(63), val: vec![53] }).unwrap();
+        w.write_message(&TestMessage { id: None, val: vec![5, 7] }).unwrap();
+    }
+
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    let mut msg = TestMessage::default();
+    assert!(r.merge_length_delimited(&mut msg).unwrap());
+    assert_eq!(TestMessage { id: Some(63), val: vec![53] }, msg);
+    assert!(r.merge_length_delimited(&mut msg).unwrap());
+    assert!(!r.merge_length_delimited(&mut msg).unwrap());
+    assert_eq!(whole, msg);
+}
+
+#[test]
+fn wr_encode_decode_io(){
+    let m = TestMessage { id: Some(63), val: vec![53, 5] };
+    let mut buf = Vec::new();
+    m.encode_to_writer(&mut buf).unwrap();
+    assert_eq!(m.get_size(), buf.len());
+    assert_eq!(m, TestMessage::decode_from_reader(::std::io::Cursor::new(&buf), buf.len()).unwrap());
+}
+
+#[test]
+fn wr_trace(){
+    use quick_protobuf::reader::TraceEvent;
+    use quick_protobuf::reader::WireType::*;
+    use std::sync::{Arc, Mutex};
+
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_uint32_with_tag(8, 150).unwrap();
+        w.write_message_with_tag(18, &TestMessage { id: Some(3), val: vec![] }).unwrap();
+        w.write_fixed32_with_tag(29, 7).unwrap();
+    }
+    let events = Arc::new(Mutex::new(Vec::new()));
+    // the last value is truncated
+    let mut r = Reader::from_reader(&buf[..buf.len() - 2], buf.len());
+    let trace = events.clone();
+    r.set_trace(move |e| trace.lock().unwrap().push(e));
+    assert_eq!(8, r.next_tag().unwrap());
+    r.read_uint32().unwrap();
+    assert_eq!(18, r.next_tag().unwrap());
+    r.read_message::<TestMessage>().unwrap();
+    assert_eq!(29, r.next_tag().unwrap());
+    assert!(r.read_fixed32().is_err());
+    assert_eq!(vec![
+        TraceEvent::Field { position: 0, depth: 0, field_number: 1, wire_type: Varint },
+        TraceEvent::Value(150),
+        TraceEvent::Field { position: 3, depth: 0, field_number: 2, wire_type: LengthDelimited },
+        TraceEvent::Value(2),
+        TraceEvent::Field { position: 5, depth: 1, field_number: 1, wire_type: LengthDelimited },
+        TraceEvent::Value(3),
+        TraceEvent::Field { position: 7, depth: 0, field_number: 3, wire_type: Fixed32 },
+    ], *events.lock().unwrap());
+}
+
+#[test]
+fn wr_read_message_from_deque(){
+    use std::collections::VecDeque;
+
+    let (m1, m2) = (TestMessage { id: Some(63), val: vec![53, 5] }, TestMessage { id: Some(1), val: vec![] });
+    let mut frames = Vec::new();
+    {
+        let mut w = Writer::new(&mut frames);
+        w.write_message(&m1).unwrap();
+        w.write_message(&m2).unwrap();
+    }
+
+    // the bytes wrap around the end of the deque
+    let mut buf = VecDeque::with_capacity(frames.len());
+    let start = buf.capacity() - 3;
+    buf.extend(vec![0; start]);
+    buf.drain(..start - 1);
+    buf.extend(&frames[..frames.len() - 1]);
+    buf.pop_front();
+    assert!(!buf.as_slices().1.is_empty());
+
+    let (msg, len) = Reader::read_message_from_deque::<TestMessage>(&buf).unwrap().unwrap();
+    assert_eq!(m1, msg);
+    buf.drain(..len);
+    assert!(Reader::read_message_from_deque::<TestMessage>(&buf).unwrap().is_none());
+    buf.push_back(frames[frames.len() - 1]);
+    assert_eq!(Some((m2, buf.len())), Reader::read_message_from_deque(&buf).unwrap());
+}
+
+#[test]
+fn wr_merge_from_bytes(){
+    let encode = |m: &TestMessage| {
+        let mut buf = Vec::new();
+        m.encode_to_writer(&mut buf).unwrap();
+        buf
+    };
+    let mut m = TestMessage { id: None, val: vec![1, 2] };
+    m.merge_from_bytes(&encode(&TestMessage { id: Some(7), val: vec![] })).unwrap();
+    assert_eq!(TestMessage { id: Some(7), val: vec![1, 2] }, m);
+    m.merge_from_bytes(&encode(&TestMessage { id: Some(8), val: vec![3, 4] })).unwrap();
+    assert_eq!(TestMessage { id: Some(8), val: vec![1, 2, 3, 4] }, m);
+}
+
+#[test]
+fn wr_read_message_exact(){
+    let m = TestMessage { id: Some(63), val: vec![53, 5] };
+    let mut buf = Vec::new();
+    m.encode_to_writer(&mut buf).unwrap();
+    let len = buf.len();
+    let read = |bytes: &[u8], len: usize| Reader::from_reader(bytes, len).read_message_exact::<TestMessage>();
+
+    assert_eq!(m, read(&buf, len).unwrap());
+    buf.push(0);
+    match *read(&buf, len).unwrap_err().kind() {
+        ErrorKind::LengthMismatch(l) => assert_eq!(len, l),
+        ref e => panic!("expecting LengthMismatch, got {:?}", e),
+    }
+    match *read(&buf[..len - 1], len).unwrap_err().kind() {
+        ErrorKind::UnexpectedEof => (),
+        ref e => panic!("expecting UnexpectedEof, got {:?}", e),
+    }
+}
+
+#[test]
+fn wr_validate_message(){
+    // checks the packed `val` of a `TestMessage`, without building it
+    fn validate_fields(r: &mut Reader<&[u8]>) -> Result<()> {
+        while !r.is_eof() {
+            match r.next_tag()? {
+                18 => r.read_packed_for_each(|r| r.read_sint64().map(|_| ()))?,
+                t => r.read_unknown(t)?,
+            }
+        }
+        Ok(())
+    }
+    let mut inner = Vec::new();
+    {
+        let mut w = Writer::new(&mut inner);
+        w.write_tag(18).unwrap();
+        w.write_packed_repeated_field(&[1i64, -2], |w, v| w.write_sint64(*v), &|v| sizeof_sint64(*v)).unwrap();
+    }
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_tag(10).unwrap();
+        w.write_bytes(&inner).unwrap();
+    }
+    let mut r = Reader::from_reader(&*buf, buf.len());
+    assert_eq!(10, r.next_tag().unwrap());
+    r.validate_message(validate_fields).unwrap();
+    assert!(r.is_eof());
+
+    let mut r = Reader::from_reader(&buf[..buf.len() - 1], buf.len() - 1);
+    assert_eq!(10, r.next_tag().unwrap());
+    assert!(r.validate_message(validate_fields).is_err());
+
+    // a fixed size value exceeding the remaining length
+    let mut r = Reader::from_reader(&[1u8, 2, 3, 4][..], 3);
+    assert!(r.read_fixed32().is_err());
+}
+
+#[test]
+fn wr_hashing_writer(){
+    // a FNV-1a hasher, hashing the bytes written to it
+    struct Fnv(u64);
+
+    impl ::std::io::Write for Fnv {
+        fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+            for b in buf {
+                self.0 = (self.0 ^ *b as u64).wrapping_mul(0x100000001b3);
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> ::std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let v = TestMessage { id: Some(63), val: vec![53, 5, 76, 743, 23, 753] };
+    let mut writer = Writer::new(Fnv(0xcbf29ce484222325));
+    v.write_message(&mut writer).unwrap();
+    assert_eq!(v.get_size(), writer.written());
+    let hash = writer.into_inner().0;
+
+    let mut buf = Vec::new();
+    v.write_message(&mut Writer::new(&mut buf)).unwrap();
+    let mut fnv = Fnv(0xcbf29ce484222325);
+    ::std::io::Write::write_all(&mut fnv, &buf).unwrap();
+    assert_eq!(fnv.0, hash);
+}
+
+#[derive(PartialEq, Debug, Default)]
+struct TestPooled {
+    items: PooledVec<TestMessage>,
+}
+
+impl MessageRead for TestPooled {
+    fn from_reader<R: Read>(r: &mut Reader<R>) -> Result<Self> {
+        let mut msg = Self::default();
+        msg.merge_from(r)?;
+        Ok(msg)
+    }
+
+    fn merge_from<R: Read>(&mut self, r: &mut Reader<R>) -> Result<()> {
+        while !r.is_eof() {
+            match r.next_tag()? {
+                10 => r.read_message_to_pooled_vec(&mut self.items)?,
+                t => r.read_unknown(t)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.items.clear();
+    }
+}
+
+#[test]
+fn wr_pool(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        for id in 0..2 {
+            w.write_message_with_tag(10, &TestMessage { id: Some(id), val: vec![1, 2, 3] }).unwrap();
+        }
+    }
+    let mut pool = Pool::<TestPooled>::new();
+    let m = pool.read_message(&mut Reader::from_reader(&*buf, buf.len())).unwrap();
+    assert_eq!(2, m.items.len());
+    assert_eq!(Some(1), m.items[1].id);
+    let val = m.items[1].val.as_ptr();
+    pool.put(m);
+
+    // the nested messages are kept, with their own allocations
+    let m = pool.get();
+    assert!(pool.is_empty());
+    assert_eq!((0, 2), (m.items.len(), m.items.spares()));
+    pool.put(m);
+    let m = pool.read_message(&mut Reader::from_reader(&*buf, buf.len())).unwrap();
+    assert_eq!(TestMessage { id: Some(1), val: vec![1, 2, 3] }, m.items[1]);
+    assert_eq!(val, m.items[1].val.as_ptr());
+    pool.put(m);
+
+    // a failed read returns the message to the pool
+    assert!(pool.read_message(&mut Reader::from_reader(&buf[..buf.len() - 1], buf.len() - 1)).is_err());
+    assert_eq!(1, pool.len());
+    assert_eq!(0, pool.get().items.len());
+}
+
+#[test]
+fn wr_invalid_tag(){
+    let invalid = |bytes: &[u8]| {
+        let mut r = Reader::from_reader(bytes, bytes.len());
+        match *r.next_tag().unwrap_err().kind() {
+            ErrorKind::InvalidTag(tag) => tag,
+            ref e => panic!("expecting InvalidTag, got {:?}", e),
+        }
+    };
+    // field number 0
+    assert_eq!(2, invalid(&[2, 0]));
+    // wire type 7
+    assert_eq!(15, invalid(&[15, 0]));
+    assert_eq!(14, invalid(&[14, 0]));
+
+    // also checked when unbounded
+    let mut r = Reader::from_reader(&[15u8, 0][..], 2);
+    r.set_bounded(false);
+    assert!(matches!(*r.next_tag().unwrap_err().kind(), ErrorKind::InvalidTag(15)));
+    let mut r = Reader::from_reader(&[0u8, 0][..], 2);
+    r.set_bounded(false);
+    assert!(matches!(*r.next_field().unwrap_err().kind(), ErrorKind::InvalidTag(0)));
+
+    assert_eq!(Some((1, WireType::Varint)), Reader::from_reader(&[8u8, 0][..], 2).next_field().unwrap());
+    assert!(TestMessage::from_reader(&mut Reader::from_reader(&[8u8, 1, 0, 0][..], 4)).is_err());
+}
+
+#[test]
+fn wr_unknown_fields_base64(){
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_int32_with_tag(8, 150).unwrap();
+        w.write_string_with_tag(18, "abc").unwrap();
+    }
+    let fields = UnknownFields::from_bytes(&buf).unwrap();
+    assert_eq!(2, fields.len());
+    assert_eq!(buf, fields.to_bytes());
+    assert_eq!("CJYBEgNhYmM=", fields.to_base64());
+    assert_eq!(fields, UnknownFields::from_base64("CJYBEgNhYmM").unwrap());
+    assert!(UnknownFields::from_base64("CJYB!gNhYmM=").is_err());
+    assert!(UnknownFields::from_base64("CJYBEg==").is_err());
+    assert!(UnknownFields::from_base64("").unwrap().is_empty());
+}
+
+#[test]
+fn wr_truncated_unknown_fixed(){
+    // unknown fixed64 field 31 with a single byte left
+    let top = [0x08, 0x01, 0xF9, 0x01, 0x00];
+    assert!(TestMessage::from_reader(&mut Reader::from_reader(&top[..], top.len())).is_err());
+
+    // the same, in a nested message of field 1
+    let nested = [0x0A, 0x05, 0x08, 0x01, 0xF9, 0x01, 0x00];
+    let mut r = Reader::from_reader(&nested[..], nested.len());
+    assert_eq!(10, r.next_tag().unwrap());
+    assert!(r.read_message::<TestMessage>().is_err());
+
+    let fixed64 = [0x09, 0x00];
+    let fixed32 = [0x0D, 0x00];
+    for bytes in &[&fixed64[..], &fixed32[..]] {
+        let reader = || {
+            let mut r = Reader::from_reader(*bytes, bytes.len());
+            let tag = r.next_tag().unwrap();
+            (r, tag)
+        };
+        let (mut r, tag) = reader();
+        assert!(r.read_unknown(tag).is_err());
+        let (mut r, tag) = reader();
+        assert!(r.read_unknown_raw(tag).is_err());
+        assert!(Reader::from_reader(*bytes, bytes.len()).scan(|_, _, _| ()).is_err());
+        assert!(Reader::from_reader(*bytes, bytes.len()).index_fields().is_err());
+    }
+}
+
+#[test]
+fn wr_unknown_raw_length_overflow(){
+    // unknown bytes field 1 declaring a length of `usize::MAX`, after its length prefix
+    let mut buf = Vec::new();
+    {
+        let mut w = Writer::new(&mut buf);
+        w.write_tag(10).unwrap();
+        w.write_varint(usize::MAX as u64).unwrap();
+    }
+    let len = buf.len();
+    let mut r = Reader::from_reader(&*buf, len);
+    r.set_bounded(false);
+    let tag = r.next_tag().unwrap();
+    match *r.read_unknown_raw(tag).unwrap_err().kind() {
+        ErrorKind::LengthOverflow(_) => (),
+        ref e => panic!("expecting LengthOverflow, got {:?}", e),
+    }
+}